@@ -2,7 +2,7 @@ use std::env::current_dir;
 
 use cosmwasm_schema::{export_schema_with_title, schema_for, write_api};
 
-use andromeda_cw721_timelock::msg::{Cw721HookMsg, ExecuteMsg, InstantiateMsg, QueryMsg};
+use andromeda_cw721_timelock::msg::{Cw721HookMsg, ExecuteMsg, InstantiateMsg, QueryMsg, SudoMsg};
 
 fn main() {
     let mut out_dir = current_dir().unwrap();
@@ -11,6 +11,7 @@ fn main() {
         instantiate: InstantiateMsg,
         execute: ExecuteMsg,
         query: QueryMsg,
+        sudo: SudoMsg,
     };
     export_schema_with_title(&schema_for!(Cw721HookMsg), &out_dir, "cw721receive");
 }
@@ -1,38 +1,71 @@
-use andromeda_non_fungible_tokens::cw721::ExecuteMsg as Cw721ExecuteMsg;
+use andromeda_non_fungible_tokens::cw721::{ExecuteMsg as Cw721ExecuteMsg, TokenExtension};
 use andromeda_std::{
     ado_base::{permissioning::Permission, InstantiateMsg as BaseInstantiateMsg, MigrateMsg},
-    ado_contract::ADOContract,
+    ado_contract::{permissioning::permissions, ADOContract},
     amp::{AndrAddr, Recipient},
     common::{
-        context::ExecuteContext, encode_binary, milliseconds::Milliseconds,
-        milliseconds::MillisecondsDuration,
+        context::ExecuteContext,
+        encode_binary,
+        milliseconds::{Milliseconds, MillisecondsDuration, MillisecondsExpiration},
     },
     error::ContractError,
 };
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
 use cosmwasm_std::{
-    attr, ensure, from_json, Binary, CosmosMsg, Deps, DepsMut, Empty, Env, MessageInfo, Response,
-    WasmMsg,
+    attr, coin, ensure, from_json, Addr, Attribute, BankMsg, Binary, BlockInfo, Coin, CosmosMsg,
+    Decimal, Deps, DepsMut, Empty, Env, Event, HexBinary, MessageInfo, Order, Reply, Response,
+    StdError, StdResult, Storage, SubMsg, SubMsgResult, Uint128, WasmMsg,
 };
 
 use crate::msg::{
-    Cw721HookMsg, ExecuteMsg, InstantiateMsg, IsLockedResponse, NftDetailsResponse, QueryMsg,
-    UnlockTimeResponse,
+    ArbiterDecision, ArbiterSet, ArbiterVote, ArbiterVotesResponse, Arbiters, Asset, AssetAmount,
+    AuthorizedTokenAddressesResponse, Bundle, BundleItem, BundleResponse, ClaimWindow, Cw20HookMsg,
+    Cw721HookMsg, EarlyUnlockCaller, EarlyUnlockPolicy, ExecuteMsg, Fallback, FeeBalanceResponse,
+    HeightLimits, InstantiateMsg, IsLockedResponse, KeeperBountyResponse, LockHistoryResponse,
+    LockInfoResponse, LockPolicy, LockPolicyResponse, LockRecord, LockStatus, LocksResponse,
+    NftDetailsResponse, PendingRewardsResponse, QueryMsg, Rate, RateAction, RateResponse, Receipt,
+    ReceiptContractResponse, RewardAmount, RewardConfig, RewardConfigResponse, RewardPoolsResponse,
+    Schedule, ScheduleResponse, SimulateFeeResponse, SudoMsg, SwapNft, SwapOffer,
+    SwapOfferResponse, UnlockCondition, UnlockTimeResponse, VotingPowerResponse,
+};
+use crate::state::{
+    get_lock_policy, timelocks, Config, Delivery, LockReward, TimelockInfo, ARBITER_VOTES,
+    BOUNTY_POOL, BUNDLES, BUNDLE_COUNT, CONFIG, CRANK_DELIVERIES, FEE_BALANCES, KEEPER_BOUNTY,
+    LEGACY_MIGRATION_CURSOR, LEGACY_TIMELOCKS, LOCK_COUNT, LOCK_HISTORY, LOCK_POLICIES,
+    OWED_REWARDS, RATES, RECEIPTS, RECEIPT_CONTRACT, RECEIPT_COUNT, REWARD_CONFIGS, REWARD_POOLS,
+    SCHEDULES, SCHEDULE_COUNT, SWAP_COUNT, SWAP_OFFERS, TOTAL_VOTING_POWER, UNDELIVERED,
+    VOTING_POWER,
 };
-use crate::state::{TimelockInfo, TIMELOCKS};
 
 use cw2::set_contract_version;
-use cw721::Cw721ReceiveMsg;
+use cw20::{Cw20ExecuteMsg, Cw20ReceiveMsg};
+use cw721::{Cw721QueryMsg, Cw721ReceiveMsg, OwnerOfResponse};
+use cw_storage_plus::{Bound, KeyDeserialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
 
 // version info for migration info
 const CONTRACT_NAME: &str = "crates.io:andromeda-cw721-timelock";
 const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
 const SEND_NFT_ACTION: &str = "SEND_NFT";
+/// Gates the depositors of new locks, checked against the sender of the NFT.
+const DEPOSIT_ACTION: &str = "DEPOSIT";
+/// Gates the recipients of new locks and of locks handed over to someone else.
+const RECEIVE_LOCK_ACTION: &str = "RECEIVE_LOCK";
 
 const ONE_DAY: u64 = 24 * 60 * 60;
 const ONE_YEAR: u64 = 365 * 24 * 60 * 60;
 
+/// Bounds the gas of claiming a bundle.
+const MAX_BUNDLE_SIZE: usize = 30;
+
+const DEFAULT_LIMIT: u32 = 10;
+const MAX_LIMIT: u32 = 30;
+
+/// A lock with its `(cw721_contract, token_id)` key.
+type KeyedLock = ((Addr, String), TimelockInfo);
+
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn instantiate(
     deps: DepsMut,
@@ -57,6 +90,13 @@ pub fn instantiate(
         },
     )?;
 
+    CONFIG.save(
+        deps.storage,
+        &Config {
+            default_lock_policy: default_lock_policy(),
+        },
+    )?;
+
     if let Some(authorized_token_addresses) = msg.authorized_token_addresses {
         if !authorized_token_addresses.is_empty() {
             ADOContract::default().permission_action(SEND_NFT_ACTION, deps.storage)?;
@@ -98,10 +138,105 @@ pub fn execute(
 pub fn handle_execute(ctx: ExecuteContext, msg: ExecuteMsg) -> Result<Response, ContractError> {
     match msg {
         ExecuteMsg::ReceiveNft(msg) => handle_receive_cw721(ctx, msg),
+        ExecuteMsg::Receive(msg) => handle_receive_cw20(ctx, msg),
         ExecuteMsg::ClaimNft {
             cw721_contract,
             token_id,
         } => execute_claim_cw721(ctx, cw721_contract, token_id),
+        ExecuteMsg::ClaimHashLockedNft {
+            cw721_contract,
+            token_id,
+            preimage,
+        } => execute_claim_hash_locked_cw721(ctx, cw721_contract, token_id, preimage),
+        ExecuteMsg::RefundHashLockedNft {
+            cw721_contract,
+            token_id,
+        } => execute_refund_hash_locked_cw721(ctx, cw721_contract, token_id),
+        ExecuteMsg::ReclaimSwap { offer_id } => execute_reclaim_swap(ctx, offer_id),
+        ExecuteMsg::EarlyUnlock {
+            cw721_contract,
+            token_id,
+        } => execute_early_unlock(ctx, cw721_contract, token_id),
+        ExecuteMsg::ReleaseToFallback {
+            cw721_contract,
+            token_id,
+        } => execute_release_to_fallback(ctx, cw721_contract, token_id),
+        ExecuteMsg::ArbitrateLock {
+            cw721_contract,
+            token_id,
+            decision,
+            reason,
+        } => execute_arbitrate_lock(ctx, cw721_contract, token_id, decision, reason),
+        ExecuteMsg::OpenBundle {
+            recipient,
+            unlock_condition,
+        } => execute_open_bundle(ctx, recipient, unlock_condition),
+        ExecuteMsg::ClaimBundle { bundle_id } => execute_claim_bundle(ctx, bundle_id),
+        ExecuteMsg::CreateSchedule {
+            recipient,
+            cliff,
+            period,
+            per_period,
+        } => execute_create_schedule(ctx, recipient, cliff, period, per_period),
+        ExecuteMsg::ClaimNfts { items } => execute_claim_cw721s(ctx, items),
+        ExecuteMsg::ClaimAllUnlocked {
+            recipient,
+            start_after,
+            limit,
+        } => execute_claim_all_unlocked(ctx, recipient, start_after, limit),
+        ExecuteMsg::ReleaseExpired { limit } => execute_release_expired(ctx, limit),
+        ExecuteMsg::MigrateLegacyLocks { limit } => execute_migrate_legacy_locks(ctx, limit),
+        ExecuteMsg::ClaimUndelivered {
+            cw721_contract,
+            token_id,
+        } => execute_claim_undelivered(ctx, cw721_contract, token_id),
+        ExecuteMsg::FundBountyPool {} => execute_fund_bounty_pool(ctx),
+        ExecuteMsg::UpdateRate { action, rate } => execute_update_rate(ctx, action, rate),
+        ExecuteMsg::DepositFeeBalance { recipient } => execute_deposit_fee_balance(ctx, recipient),
+        ExecuteMsg::WithdrawFeeBalance { asset, amount } => {
+            execute_withdraw_fee_balance(ctx, asset, amount)
+        }
+        ExecuteMsg::UpdateKeeperBounty { bounty } => execute_update_keeper_bounty(ctx, bounty),
+        ExecuteMsg::UpdateRewardConfig {
+            cw721_contract,
+            config,
+        } => execute_update_reward_config(ctx, cw721_contract, config),
+        ExecuteMsg::FundRewardPool {} => execute_fund_reward_pool(ctx),
+        ExecuteMsg::ClaimRewards { start_after, limit } => {
+            execute_claim_rewards(ctx, start_after, limit)
+        }
+        ExecuteMsg::UpdateReceiptContract { address } => {
+            execute_update_receipt_contract(ctx, address)
+        }
+        ExecuteMsg::RevokeLock {
+            cw721_contract,
+            token_id,
+            new_recipient,
+        } => execute_revoke_lock(ctx, cw721_contract, token_id, new_recipient),
+        ExecuteMsg::ExtendLock {
+            cw721_contract,
+            token_id,
+            additional_duration,
+        } => execute_extend_lock(ctx, cw721_contract, token_id, additional_duration),
+        ExecuteMsg::Heartbeat {
+            cw721_contract,
+            token_id,
+        } => execute_heartbeat(ctx, cw721_contract, token_id),
+        ExecuteMsg::TransferBeneficiary {
+            cw721_contract,
+            token_id,
+            new_recipient,
+        } => execute_transfer_beneficiary(ctx, cw721_contract, token_id, new_recipient),
+        ExecuteMsg::UpdateLockPolicy {
+            cw721_contract,
+            policy,
+        } => execute_update_lock_policy(ctx, cw721_contract, policy),
+        ExecuteMsg::AddAuthorizedTokenAddress { address } => {
+            execute_add_authorized_token_address(ctx, address)
+        }
+        ExecuteMsg::RemoveAuthorizedTokenAddress { address } => {
+            execute_remove_authorized_token_address(ctx, address)
+        }
         _ => ADOContract::default().execute(ctx, msg),
     }
 }
@@ -110,161 +245,3721 @@ fn handle_receive_cw721(
     ctx: ExecuteContext,
     msg: Cw721ReceiveMsg,
 ) -> Result<Response, ContractError> {
-    ADOContract::default().is_permissioned(
-        ctx.deps.storage,
-        ctx.env.clone(),
-        SEND_NFT_ACTION,
-        ctx.info.sender.clone(),
-    )?;
+    let hook: Cw721HookMsg = from_json(&msg.msg)?;
+    // Redeeming a receipt takes an NFT out rather than depositing one
+    if !matches!(hook, Cw721HookMsg::RedeemReceipt {}) {
+        ADOContract::default().is_permissioned(
+            ctx.deps.storage,
+            ctx.env.clone(),
+            DEPOSIT_ACTION,
+            msg.sender.clone(),
+        )?;
+    }
 
-    match from_json(&msg.msg)? {
+    match hook {
         Cw721HookMsg::TimelockNft {
             lock_duration,
             recipient,
-        } => execute_timelock_cw721(ctx, msg.sender, msg.token_id, lock_duration, recipient),
+            revocable,
+            arbiters,
+            claim_window,
+            receipt,
+        } => {
+            let unlock_time = unlock_time_after(&ctx.env, lock_duration)?;
+            execute_timelock_cw721(
+                ctx,
+                msg.sender,
+                msg.token_id,
+                UnlockCondition::Time(unlock_time),
+                recipient,
+                revocable,
+                arbiters,
+                claim_window,
+                receipt,
+                None,
+            )
+        }
+        // The depositor can always take back a heartbeat lock, so it is created as revocable
+        Cw721HookMsg::HeartbeatLockNft {
+            interval,
+            recipient,
+        } => {
+            let unlock_time = unlock_time_after(&ctx.env, interval)?;
+            execute_timelock_cw721(
+                ctx,
+                msg.sender,
+                msg.token_id,
+                UnlockCondition::Time(unlock_time),
+                recipient,
+                true,
+                None,
+                None,
+                false,
+                Some(interval),
+            )
+        }
+        Cw721HookMsg::TimelockNftUntil {
+            unlock_condition,
+            recipient,
+            revocable,
+            arbiters,
+            claim_window,
+            receipt,
+        } => execute_timelock_cw721(
+            ctx,
+            msg.sender,
+            msg.token_id,
+            unlock_condition,
+            recipient,
+            revocable,
+            arbiters,
+            claim_window,
+            receipt,
+            None,
+        ),
+        // Receipts come from the receipt contract, which is not one of the authorized collections
+        Cw721HookMsg::RedeemReceipt {} => execute_redeem_receipt(ctx, msg.sender, msg.token_id),
+        Cw721HookMsg::OpenSwap {
+            ask_cw721_contract,
+            ask_token_id,
+            counterparty,
+            deadline,
+        } => execute_open_swap(
+            ctx,
+            msg.sender,
+            msg.token_id,
+            ask_cw721_contract,
+            ask_token_id,
+            counterparty,
+            deadline,
+        ),
+        Cw721HookMsg::AcceptSwap { offer_id } => {
+            execute_accept_swap(ctx, msg.sender, msg.token_id, offer_id)
+        }
+        Cw721HookMsg::AddToBundle { bundle_id } => {
+            execute_add_to_bundle(ctx, msg.sender, msg.token_id, bundle_id)
+        }
+        Cw721HookMsg::AddToSchedule { schedule_id } => {
+            execute_add_to_schedule(ctx, msg.sender, msg.token_id, schedule_id)
+        }
+        Cw721HookMsg::HashLockNft {
+            hash,
+            counterparty,
+            refund_after,
+        } => execute_hash_lock_cw721(
+            ctx,
+            msg.sender,
+            msg.token_id,
+            hash,
+            counterparty,
+            refund_after,
+        ),
     }
 }
 
 #[allow(clippy::too_many_arguments)]
 fn execute_timelock_cw721(
     ctx: ExecuteContext,
-    _sender: String,
+    sender: String,
     token_id: String,
-    lock_duration: MillisecondsDuration,
+    unlock_condition: UnlockCondition,
     recipient: Recipient,
+    revocable: bool,
+    arbiters: Option<Arbiters>,
+    claim_window: Option<ClaimWindow>,
+    mint_receipt: bool,
+    heartbeat_interval: Option<MillisecondsDuration>,
 ) -> Result<Response<Empty>, ContractError> {
     let ExecuteContext {
         deps, info, env, ..
     } = ctx;
 
+    ADOContract::default().is_permissioned(
+        deps.storage,
+        env.clone(),
+        SEND_NFT_ACTION,
+        info.sender.clone(),
+    )?;
+
+    let policy = get_lock_policy(deps.storage, &info.sender)?;
+    validate_unlock_condition(&env, &policy, &unlock_condition)?;
     ensure!(
-        lock_duration.seconds() >= ONE_DAY,
-        ContractError::LockTimeTooShort {}
-    );
-    ensure!(
-        lock_duration.seconds() <= ONE_YEAR,
-        ContractError::LockTimeTooLong {}
+        !revocable || policy.allow_revocable,
+        ContractError::Unauthorized {}
     );
+    let has_forced_recipient = policy.forced_recipient.is_some();
+    let recipient = policy.forced_recipient.unwrap_or(recipient);
 
     let lock_id = (&info.sender, token_id.as_str());
+    // Saving over a lock would erase it from the lock history
+    ensure!(
+        !timelocks().has(deps.storage, lock_id),
+        ContractError::LockedNFT {}
+    );
 
     let recipient_addr =
         AndrAddr::from_string(recipient.get_addr()).get_raw_address(&deps.as_ref())?;
-    let timelock_info = TimelockInfo {
-        unlock_time: Milliseconds::from_seconds(env.block.time.seconds() + lock_duration.seconds()),
+    ADOContract::default().is_permissioned(
+        deps.storage,
+        env.clone(),
+        RECEIVE_LOCK_ACTION,
+        recipient_addr.clone(),
+    )?;
+    let depositor = deps.api.addr_validate(&sender)?;
+    let arbiters = arbiters
+        .map(|arbiters| validate_arbiters(&deps.as_ref(), arbiters))
+        .transpose()?;
+    let fallback = match claim_window {
+        Some(claim_window) => {
+            // The fallback would take the NFT away from the forced recipient
+            ensure!(!has_forced_recipient, ContractError::Unauthorized {});
+            ensure!(
+                unlock_condition.time().is_some(),
+                ContractError::InvalidTimestamp {
+                    msg: "A claim window requires an unlock time".to_string(),
+                }
+            );
+            // An empty window would hand the NFT to the fallback as soon as it unlocks
+            ensure!(
+                claim_window.duration.milliseconds() > 0,
+                ContractError::InvalidTimestamp {
+                    msg: "A claim window cannot be empty".to_string(),
+                }
+            );
+            ensure!(
+                claim_window.duration <= policy.max_duration,
+                ContractError::LockTimeTooLong {}
+            );
+            let fallback_addr = claim_window
+                .fallback
+                .address
+                .get_raw_address(&deps.as_ref())?;
+            ADOContract::default().is_permissioned(
+                deps.storage,
+                env.clone(),
+                RECEIVE_LOCK_ACTION,
+                fallback_addr.clone(),
+            )?;
+            Some(Fallback {
+                claim_window: claim_window.duration,
+                recipient: fallback_addr,
+                recipient_msg: claim_window.fallback.msg,
+            })
+        }
+        None => None,
+    };
+
+    let mut resp = Response::new().add_messages(charge_fee(
+        deps.storage,
+        &RateAction::TimelockNft,
+        &depositor,
+    )?);
+    let receipt = match mint_receipt {
+        true => {
+            let receipt_contract = RECEIPT_CONTRACT
+                .may_load(deps.storage)?
+                .ok_or(ContractError::UnsupportedOperation {})?;
+            // A revoked, refunded or forfeited lock would leave its receipt behind with nothing
+            // to claim
+            ensure!(
+                !revocable && arbiters.is_none() && fallback.is_none(),
+                ContractError::UnsupportedOperation {}
+            );
+
+            let receipt_id = RECEIPT_COUNT.may_load(deps.storage)?.unwrap_or_default() + 1;
+            RECEIPT_COUNT.save(deps.storage, &receipt_id)?;
+            let receipt = Receipt {
+                cw721_contract: receipt_contract,
+                token_id: receipt_id.to_string(),
+            };
+            RECEIPTS.save(
+                deps.storage,
+                (&receipt.cw721_contract, receipt.token_id.as_str()),
+                &(info.sender.clone(), token_id.clone()),
+            )?;
+            resp = resp
+                .add_message(WasmMsg::Execute {
+                    contract_addr: receipt.cw721_contract.to_string(),
+                    msg: encode_binary(&Cw721ExecuteMsg::Mint {
+                        token_id: receipt.token_id.clone(),
+                        owner: recipient_addr.to_string(),
+                        token_uri: None,
+                        extension: TokenExtension {
+                            publisher: env.contract.address.to_string(),
+                        },
+                    })?,
+                    funds: vec![],
+                })
+                .add_attribute("receipt_token_id", receipt.token_id.clone());
+            Some(receipt)
+        }
+        false => None,
+    };
+
+    // The receipt can change hands, so rewards and voting power would go to a recipient who
+    // may no longer hold the claim
+    let reward = match receipt {
+        Some(_) => None,
+        None => REWARD_CONFIGS
+            .may_load(deps.storage, &info.sender)?
+            .and_then(|config| lock_reward(&env, &config, &unlock_condition)),
+    };
+
+    let id = next_lock_id(deps.storage)?;
+
+    let mut timelock_info = TimelockInfo {
+        lock_id: id,
+        locked_at: Milliseconds::from_seconds(env.block.time.seconds()),
+        unlock_condition,
         recipient: recipient_addr,
+        recipient_msg: recipient.msg,
+        depositor: depositor.clone(),
+        revocable,
+        receipt,
+        reward,
+        voting_power: Uint128::zero(),
+        hash_lock: None,
+        swap_offer: None,
+        arbiters,
+        frozen: false,
+        bundle: None,
+        schedule: None,
+        fallback,
+        heartbeat_interval,
+        claim_fee: RATES.may_load(deps.storage, &RateAction::ClaimNft.to_string())?,
+        set_aside: false,
     };
+    let voting_power = lock_voting_power(&env, &timelock_info);
+    set_voting_power(deps.storage, &env.block, &mut timelock_info, voting_power)?;
 
-    TIMELOCKS.save(deps.storage, lock_id, &timelock_info)?;
+    timelocks().save(deps.storage, lock_id, &timelock_info)?;
 
-    Ok(Response::new().add_attributes(vec![
+    Ok(resp.add_attributes(vec![
         attr("method", "timelock_cw721"),
-        attr("contract_address", &info.sender.to_string()),
+        attr("contract_address", info.sender.to_string()),
         attr("token_id", token_id.clone()),
+        attr("depositor", depositor),
+        attr("lock_id", id.to_string()),
     ]))
 }
 
-fn execute_claim_cw721(
+fn next_lock_id(storage: &mut dyn Storage) -> StdResult<u64> {
+    let id = LOCK_COUNT.may_load(storage)?.unwrap_or_default() + 1;
+    LOCK_COUNT.save(storage, &id)?;
+    Ok(id)
+}
+
+/// Locks an NFT for a counterparty behind a sha256 hash, so it can be swapped atomically
+/// against assets held elsewhere. The lock policy's minimum duration does not apply.
+fn execute_hash_lock_cw721(
+    ctx: ExecuteContext,
+    sender: String,
+    token_id: String,
+    hash: HexBinary,
+    counterparty: AndrAddr,
+    refund_after: MillisecondsExpiration,
+) -> Result<Response<Empty>, ContractError> {
+    let ExecuteContext {
+        deps, info, env, ..
+    } = ctx;
+
+    ADOContract::default().is_permissioned(
+        deps.storage,
+        env.clone(),
+        SEND_NFT_ACTION,
+        info.sender.clone(),
+    )?;
+
+    ensure!(
+        hash.len() == 32,
+        ContractError::UnexpectedNumberOfBytes {
+            expected: 32,
+            actual: hash.len(),
+        }
+    );
+    let policy = get_lock_policy(deps.storage, &info.sender)?;
+    validate_escrow_expiration(&env, &policy, refund_after)?;
+
+    let lock_id = (&info.sender, token_id.as_str());
+    ensure!(
+        !timelocks().has(deps.storage, lock_id),
+        ContractError::LockedNFT {}
+    );
+
+    let counterparty = counterparty.get_raw_address(&deps.as_ref())?;
+    ADOContract::default().is_permissioned(
+        deps.storage,
+        env.clone(),
+        RECEIVE_LOCK_ACTION,
+        counterparty.clone(),
+    )?;
+    let depositor = deps.api.addr_validate(&sender)?;
+    let id = next_lock_id(deps.storage)?;
+    timelocks().save(
+        deps.storage,
+        lock_id,
+        &TimelockInfo {
+            lock_id: id,
+            locked_at: Milliseconds::from_seconds(env.block.time.seconds()),
+            unlock_condition: UnlockCondition::Time(refund_after),
+            recipient: counterparty.clone(),
+            recipient_msg: None,
+            depositor: depositor.clone(),
+            revocable: false,
+            receipt: None,
+            reward: None,
+            voting_power: Uint128::zero(),
+            hash_lock: Some(hash.clone()),
+            swap_offer: None,
+            arbiters: None,
+            frozen: false,
+            bundle: None,
+            schedule: None,
+            fallback: None,
+            heartbeat_interval: None,
+            claim_fee: None,
+            set_aside: false,
+        },
+    )?;
+
+    Ok(Response::new().add_attributes(vec![
+        attr("method", "hash_lock_cw721"),
+        attr("contract_address", info.sender.to_string()),
+        attr("token_id", token_id),
+        attr("depositor", depositor),
+        attr("lock_id", id.to_string()),
+        attr("counterparty", counterparty),
+        attr("hash", hash.to_hex()),
+    ]))
+}
+
+fn execute_claim_hash_locked_cw721(
     ctx: ExecuteContext,
     cw721_contract: AndrAddr,
     token_id: String,
+    preimage: Binary,
 ) -> Result<Response<Empty>, ContractError> {
     let ExecuteContext { deps, env, .. } = ctx;
 
-    let lock_id = (
-        &cw721_contract.get_raw_address(&deps.as_ref())?,
-        token_id.as_str(),
-    );
-    let timelock_info = TIMELOCKS
-        .load(deps.storage, lock_id)
+    let cw721_contract = cw721_contract.get_raw_address(&deps.as_ref())?;
+    let mut timelock_info = timelocks()
+        .load(deps.storage, (&cw721_contract, token_id.as_str()))
         .map_err(|_| ContractError::NFTNotFound {})?;
+    let hash = timelock_info
+        .hash_lock
+        .clone()
+        .ok_or(ContractError::UnsupportedOperation {})?;
 
-    if env.block.time.seconds() < timelock_info.unlock_time.seconds() {
-        return Err(ContractError::LockedNFT {});
-    }
-
-    let transfer_msg = CosmosMsg::Wasm(WasmMsg::Execute {
-        contract_addr: cw721_contract
-            .get_raw_address(&deps.as_ref())?
-            .into_string(),
-        msg: encode_binary(&Cw721ExecuteMsg::TransferNft {
-            recipient: AndrAddr::from_string(timelock_info.recipient.to_string()),
-            token_id: token_id.clone(),
-        })?,
-        funds: vec![],
-    });
+    ensure!(
+        !timelock_info.unlock_condition.is_met(&env.block),
+        ContractError::Expired {}
+    );
+    ensure!(
+        Sha256::digest(preimage.as_slice()).as_slice() == hash.as_slice(),
+        ContractError::Unauthorized {}
+    );
 
-    TIMELOCKS.remove(deps.storage, lock_id);
+    let recipient = timelock_info.recipient.clone();
+    let transfer_msg = generate_transfer_msg(&cw721_contract, &token_id, &recipient)?;
+    close_lock(
+        deps.storage,
+        &env.block,
+        &cw721_contract,
+        &token_id,
+        &mut timelock_info,
+        LockStatus::Claimed,
+        &recipient,
+    )?;
 
+    // The preimage is published so the other side of the swap can be completed with it
     Ok(Response::new()
         .add_message(transfer_msg)
-        .add_attribute("method", "claim_nft")
+        .add_attribute("method", "claim_hash_locked_nft")
         .add_attribute("token_id", token_id)
-        .add_attribute("recipient", timelock_info.recipient.to_string()))
-}
-
-#[cfg_attr(not(feature = "library"), entry_point)]
-pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> Result<Binary, ContractError> {
-    match msg {
-        QueryMsg::UnlockTime {
-            cw721_contract,
-            token_id,
-        } => encode_binary(&query_unlock_time(deps, cw721_contract, token_id)?),
-        QueryMsg::NftDetails {
-            cw721_contract,
-            token_id,
-        } => encode_binary(&query_nft_details(deps, cw721_contract, token_id)?),
-        QueryMsg::IsLocked {
-            cw721_contract,
-            token_id,
-        } => encode_binary(&query_is_locked(deps, env, cw721_contract, token_id)?),
-        _ => ADOContract::default().query(deps, env, msg),
-    }
+        .add_attribute("recipient", recipient)
+        .add_attribute("preimage", HexBinary::from(preimage.as_slice()).to_hex()))
 }
 
-fn query_unlock_time(
-    deps: Deps,
+fn execute_refund_hash_locked_cw721(
+    ctx: ExecuteContext,
     cw721_contract: AndrAddr,
     token_id: String,
-) -> Result<UnlockTimeResponse, ContractError> {
-    let lock_id = (&cw721_contract.get_raw_address(&deps)?, token_id.as_str());
-    let timelock = TIMELOCKS.load(deps.storage, lock_id)?;
+) -> Result<Response<Empty>, ContractError> {
+    let ExecuteContext {
+        deps, info, env, ..
+    } = ctx;
 
-    Ok(UnlockTimeResponse {
-        unlock_time: timelock.unlock_time.seconds(),
-    })
+    let cw721_contract = cw721_contract.get_raw_address(&deps.as_ref())?;
+    let mut timelock_info = timelocks()
+        .load(deps.storage, (&cw721_contract, token_id.as_str()))
+        .map_err(|_| ContractError::NFTNotFound {})?;
+
+    ensure!(
+        timelock_info.hash_lock.is_some(),
+        ContractError::UnsupportedOperation {}
+    );
+    ensure!(
+        info.sender == timelock_info.depositor,
+        ContractError::Unauthorized {}
+    );
+    ensure!(
+        timelock_info.unlock_condition.is_met(&env.block),
+        ContractError::LockedNFT {}
+    );
+
+    let depositor = timelock_info.depositor.clone();
+    let transfer_msg = generate_transfer_msg(&cw721_contract, &token_id, &depositor)?;
+    close_lock(
+        deps.storage,
+        &env.block,
+        &cw721_contract,
+        &token_id,
+        &mut timelock_info,
+        LockStatus::Revoked,
+        &depositor,
+    )?;
+
+    Ok(Response::new()
+        .add_message(transfer_msg)
+        .add_attribute("method", "refund_hash_locked_nft")
+        .add_attribute("token_id", token_id)
+        .add_attribute("recipient", depositor))
 }
 
-fn query_nft_details(
-    deps: Deps,
-    cw721_contract: AndrAddr,
+/// Holds the sent NFT for its depositor until the deadline, unless it is swapped first.
+#[allow(clippy::too_many_arguments)]
+fn execute_open_swap(
+    ctx: ExecuteContext,
+    sender: String,
     token_id: String,
-) -> Result<NftDetailsResponse, ContractError> {
-    let lock_id = (&cw721_contract.get_raw_address(&deps)?, token_id.as_str());
-    let timelock = TIMELOCKS.load(deps.storage, lock_id)?;
+    ask_cw721_contract: AndrAddr,
+    ask_token_id: String,
+    counterparty: Option<AndrAddr>,
+    deadline: MillisecondsExpiration,
+) -> Result<Response<Empty>, ContractError> {
+    let ExecuteContext {
+        deps, info, env, ..
+    } = ctx;
 
-    Ok(NftDetailsResponse {
-        unlock_time: timelock.unlock_time.seconds(),
-        recipient: timelock.recipient,
-    })
+    ADOContract::default().is_permissioned(
+        deps.storage,
+        env.clone(),
+        SEND_NFT_ACTION,
+        info.sender.clone(),
+    )?;
+
+    let policy = get_lock_policy(deps.storage, &info.sender)?;
+    validate_escrow_expiration(&env, &policy, deadline)?;
+
+    let lock_id = (&info.sender, token_id.as_str());
+    ensure!(
+        !timelocks().has(deps.storage, lock_id),
+        ContractError::LockedNFT {}
+    );
+
+    let maker = deps.api.addr_validate(&sender)?;
+    let counterparty = counterparty
+        .map(|counterparty| counterparty.get_raw_address(&deps.as_ref()))
+        .transpose()?;
+    if let Some(counterparty) = &counterparty {
+        ADOContract::default().is_permissioned(
+            deps.storage,
+            env.clone(),
+            RECEIVE_LOCK_ACTION,
+            counterparty.clone(),
+        )?;
+    }
+    let offer = SwapOffer {
+        maker: maker.clone(),
+        offered: SwapNft {
+            cw721_contract: info.sender.clone(),
+            token_id: token_id.clone(),
+        },
+        ask: SwapNft {
+            cw721_contract: ask_cw721_contract.get_raw_address(&deps.as_ref())?,
+            token_id: ask_token_id,
+        },
+        counterparty,
+    };
+    let offer_id = SWAP_COUNT.may_load(deps.storage)?.unwrap_or_default() + 1;
+    SWAP_COUNT.save(deps.storage, &offer_id)?;
+    SWAP_OFFERS.save(deps.storage, offer_id, &offer)?;
+
+    let id = next_lock_id(deps.storage)?;
+    timelocks().save(
+        deps.storage,
+        lock_id,
+        &TimelockInfo {
+            lock_id: id,
+            locked_at: Milliseconds::from_seconds(env.block.time.seconds()),
+            unlock_condition: UnlockCondition::Time(deadline),
+            recipient: maker.clone(),
+            recipient_msg: None,
+            depositor: maker.clone(),
+            revocable: false,
+            receipt: None,
+            reward: None,
+            voting_power: Uint128::zero(),
+            hash_lock: None,
+            swap_offer: Some(offer_id),
+            arbiters: None,
+            frozen: false,
+            bundle: None,
+            schedule: None,
+            fallback: None,
+            heartbeat_interval: None,
+            claim_fee: None,
+            set_aside: false,
+        },
+    )?;
+
+    Ok(Response::new().add_attributes(vec![
+        attr("method", "open_swap"),
+        attr("contract_address", info.sender.to_string()),
+        attr("token_id", token_id),
+        attr("depositor", maker),
+        attr("lock_id", id.to_string()),
+        attr("offer_id", offer_id.to_string()),
+    ]))
 }
 
-fn query_is_locked(
-    deps: Deps,
-    env: Env,
-    cw721_contract: AndrAddr,
+/// Completes a swap with the asked NFT, which is passed straight on to the maker.
+fn execute_accept_swap(
+    ctx: ExecuteContext,
+    sender: String,
     token_id: String,
-) -> Result<IsLockedResponse, ContractError> {
-    let lock_id = (&cw721_contract.get_raw_address(&deps)?, token_id.as_str());
-    let timelock = TIMELOCKS.load(deps.storage, lock_id)?;
-    let unlock_time = timelock.unlock_time.seconds();
-    let current_time = env.block.time.seconds();
-    let is_locked = unlock_time > current_time;
+    offer_id: u64,
+) -> Result<Response<Empty>, ContractError> {
+    let ExecuteContext {
+        deps, info, env, ..
+    } = ctx;
+
+    ADOContract::default().is_permissioned(
+        deps.storage,
+        env.clone(),
+        SEND_NFT_ACTION,
+        info.sender.clone(),
+    )?;
+
+    let offer = SWAP_OFFERS
+        .may_load(deps.storage, offer_id)?
+        .ok_or(ContractError::NFTNotFound {})?;
+    ensure!(
+        offer.ask.cw721_contract == info.sender && offer.ask.token_id == token_id,
+        ContractError::InvalidAsset {
+            asset: format!("{}:{token_id}", info.sender),
+        }
+    );
+    let taker = deps.api.addr_validate(&sender)?;
+    ensure!(
+        offer
+            .counterparty
+            .as_ref()
+            .map_or(true, |counterparty| *counterparty == taker),
+        ContractError::Unauthorized {}
+    );
+    // The taker receives the offered NFT, so open offers are checked when they are taken up
+    ADOContract::default().is_permissioned(
+        deps.storage,
+        env.clone(),
+        RECEIVE_LOCK_ACTION,
+        taker.clone(),
+    )?;
+
+    let offered = offer.offered;
+    let mut timelock_info = timelocks().load(
+        deps.storage,
+        (&offered.cw721_contract, offered.token_id.as_str()),
+    )?;
+    ensure!(
+        !timelock_info.unlock_condition.is_met(&env.block),
+        ContractError::Expired {}
+    );
+    close_lock(
+        deps.storage,
+        &env.block,
+        &offered.cw721_contract,
+        &offered.token_id,
+        &mut timelock_info,
+        LockStatus::Claimed,
+        &taker,
+    )?;
 
-    Ok(IsLockedResponse { is_locked })
+    Ok(Response::new()
+        .add_message(generate_transfer_msg(
+            &offered.cw721_contract,
+            &offered.token_id,
+            &taker,
+        )?)
+        .add_message(generate_transfer_msg(
+            &info.sender,
+            &token_id,
+            &offer.maker,
+        )?)
+        .add_attribute("method", "accept_swap")
+        .add_attribute("offer_id", offer_id.to_string())
+        .add_attribute("maker", offer.maker)
+        .add_attribute("taker", taker))
 }
 
-#[cfg_attr(not(feature = "imported"), entry_point)]
-pub fn migrate(deps: DepsMut, _env: Env, _msg: MigrateMsg) -> Result<Response, ContractError> {
-    ADOContract::default().migrate(deps, CONTRACT_NAME, CONTRACT_VERSION)
+/// Returns the offered NFT to the maker once the deadline has passed. The offer was never taken
+/// up, so the lock is closed as revoked and no claim fee is charged.
+fn execute_reclaim_swap(
+    ctx: ExecuteContext,
+    offer_id: u64,
+) -> Result<Response<Empty>, ContractError> {
+    let ExecuteContext { deps, env, .. } = ctx;
+
+    let offer = SWAP_OFFERS
+        .may_load(deps.storage, offer_id)?
+        .ok_or(ContractError::NFTNotFound {})?;
+    let offered = offer.offered;
+    let mut timelock_info = timelocks().load(
+        deps.storage,
+        (&offered.cw721_contract, offered.token_id.as_str()),
+    )?;
+    ensure!(
+        timelock_info.unlock_condition.is_met(&env.block),
+        ContractError::LockedNFT {}
+    );
+
+    let transfer_msg =
+        generate_transfer_msg(&offered.cw721_contract, &offered.token_id, &offer.maker)?;
+    close_lock(
+        deps.storage,
+        &env.block,
+        &offered.cw721_contract,
+        &offered.token_id,
+        &mut timelock_info,
+        LockStatus::Revoked,
+        &offer.maker,
+    )?;
+
+    Ok(Response::new()
+        .add_message(transfer_msg)
+        .add_attribute("method", "reclaim_swap")
+        .add_attribute("offer_id", offer_id.to_string())
+        .add_attribute("recipient", offer.maker))
+}
+
+fn execute_open_bundle(
+    ctx: ExecuteContext,
+    recipient: Recipient,
+    unlock_condition: UnlockCondition,
+) -> Result<Response<Empty>, ContractError> {
+    let ExecuteContext {
+        deps, info, env, ..
+    } = ctx;
+
+    // Each NFT added is checked against the lock policy of its own collection
+    ensure!(
+        !unlock_condition.is_met(&env.block),
+        ContractError::ExpirationInPast {}
+    );
+    let recipient_addr = recipient.address.get_raw_address(&deps.as_ref())?;
+    ADOContract::default().is_permissioned(
+        deps.storage,
+        env,
+        RECEIVE_LOCK_ACTION,
+        recipient_addr.clone(),
+    )?;
+
+    let bundle_id = BUNDLE_COUNT.may_load(deps.storage)?.unwrap_or_default() + 1;
+    BUNDLE_COUNT.save(deps.storage, &bundle_id)?;
+    BUNDLES.save(
+        deps.storage,
+        bundle_id,
+        &Bundle {
+            owner: info.sender.clone(),
+            recipient: recipient_addr.clone(),
+            recipient_msg: recipient.msg,
+            unlock_condition,
+            items: vec![],
+        },
+    )?;
+
+    Ok(Response::new()
+        .add_attribute("method", "open_bundle")
+        .add_attribute("bundle_id", bundle_id.to_string())
+        .add_attribute("owner", info.sender)
+        .add_attribute("recipient", recipient_addr))
+}
+
+/// Locks the sent NFT under the terms of a bundle. Bundled NFTs cannot be revocable, have
+/// arbiters or receipts, as they all have to be released together.
+fn execute_add_to_bundle(
+    ctx: ExecuteContext,
+    sender: String,
+    token_id: String,
+    bundle_id: u64,
+) -> Result<Response<Empty>, ContractError> {
+    let ExecuteContext {
+        mut deps,
+        info,
+        env,
+        ..
+    } = ctx;
+
+    ADOContract::default().is_permissioned(
+        deps.storage,
+        env.clone(),
+        SEND_NFT_ACTION,
+        info.sender.clone(),
+    )?;
+
+    let mut bundle = BUNDLES
+        .may_load(deps.storage, bundle_id)?
+        .ok_or(ContractError::NFTNotFound {})?;
+    let depositor = deps.api.addr_validate(&sender)?;
+    ensure!(depositor == bundle.owner, ContractError::Unauthorized {});
+    ensure!(
+        bundle.items.len() < MAX_BUNDLE_SIZE,
+        ContractError::UnsupportedOperation {}
+    );
+
+    let (id, fee_msgs) = lock_group_item(
+        &mut deps,
+        &env,
+        &info.sender,
+        &token_id,
+        TimelockInfo {
+            lock_id: 0,
+            locked_at: Milliseconds::from_seconds(env.block.time.seconds()),
+            unlock_condition: bundle.unlock_condition.clone(),
+            recipient: bundle.recipient.clone(),
+            recipient_msg: bundle.recipient_msg.clone(),
+            depositor,
+            revocable: false,
+            receipt: None,
+            reward: None,
+            voting_power: Uint128::zero(),
+            hash_lock: None,
+            swap_offer: None,
+            arbiters: None,
+            frozen: false,
+            bundle: Some(bundle_id),
+            schedule: None,
+            fallback: None,
+            heartbeat_interval: None,
+            claim_fee: None,
+            set_aside: false,
+        },
+    )?;
+
+    bundle.items.push(BundleItem {
+        cw721_contract: info.sender.clone(),
+        token_id: token_id.clone(),
+    });
+    BUNDLES.save(deps.storage, bundle_id, &bundle)?;
+
+    Ok(Response::new()
+        .add_messages(fee_msgs)
+        .add_attribute("method", "add_to_bundle")
+        .add_attribute("bundle_id", bundle_id.to_string())
+        .add_attribute("contract_address", info.sender)
+        .add_attribute("token_id", token_id)
+        .add_attribute("lock_id", id.to_string()))
+}
+
+/// Creates the lock of an NFT added to a bundle or schedule, on the group's terms, after
+/// checking them against the collection's lock policy. Returns the lock ID and the fee
+/// payouts.
+fn lock_group_item(
+    deps: &mut DepsMut,
+    env: &Env,
+    cw721_contract: &Addr,
+    token_id: &str,
+    mut timelock_info: TimelockInfo,
+) -> Result<(u64, Vec<CosmosMsg>), ContractError> {
+    let policy = get_lock_policy(deps.storage, cw721_contract)?;
+    validate_unlock_condition(env, &policy, &timelock_info.unlock_condition)?;
+    if let Some(forced_recipient) = policy.forced_recipient {
+        // Resolved like the recipient of a single lock, as the policy may name a VFS path
+        let forced_recipient_addr = forced_recipient.address.get_raw_address(&deps.as_ref())?;
+        ensure!(
+            forced_recipient_addr == timelock_info.recipient,
+            ContractError::Unauthorized {}
+        );
+    }
+
+    let lock_id = (cw721_contract, token_id);
+    ensure!(
+        !timelocks().has(deps.storage, lock_id),
+        ContractError::LockedNFT {}
+    );
+
+    let fee_msgs = charge_fee(
+        deps.storage,
+        &RateAction::TimelockNft,
+        &timelock_info.depositor,
+    )?;
+    timelock_info.claim_fee = RATES.may_load(deps.storage, &RateAction::ClaimNft.to_string())?;
+    timelock_info.reward = REWARD_CONFIGS
+        .may_load(deps.storage, cw721_contract)?
+        .and_then(|config| lock_reward(env, &config, &timelock_info.unlock_condition));
+    timelock_info.lock_id = next_lock_id(deps.storage)?;
+    let voting_power = lock_voting_power(env, &timelock_info);
+    set_voting_power(deps.storage, &env.block, &mut timelock_info, voting_power)?;
+    timelocks().save(deps.storage, lock_id, &timelock_info)?;
+
+    Ok((timelock_info.lock_id, fee_msgs))
+}
+
+/// Callable by anyone once the bundle has unlocked, like `ClaimNft`. The claim fee is charged
+/// for each NFT.
+fn execute_claim_bundle(
+    ctx: ExecuteContext,
+    bundle_id: u64,
+) -> Result<Response<Empty>, ContractError> {
+    let ExecuteContext { deps, env, .. } = ctx;
+
+    let bundle = BUNDLES
+        .may_load(deps.storage, bundle_id)?
+        .ok_or(ContractError::NFTNotFound {})?;
+    ensure!(
+        bundle.unlock_condition.is_met(&env.block),
+        ContractError::LockedNFT {}
+    );
+
+    let mut msgs = vec![];
+    for item in &bundle.items {
+        let timelock_info =
+            timelocks().load(deps.storage, (&item.cw721_contract, item.token_id.as_str()))?;
+        msgs.extend(release_lock(
+            deps.storage,
+            &env.block,
+            &item.cw721_contract,
+            &item.token_id,
+            timelock_info,
+        )?);
+    }
+    BUNDLES.remove(deps.storage, bundle_id);
+
+    Ok(Response::new()
+        .add_messages(msgs)
+        .add_attribute("method", "claim_bundle")
+        .add_attribute("bundle_id", bundle_id.to_string())
+        .add_attribute("recipient", bundle.recipient)
+        .add_attribute("claimed", bundle.items.len().to_string()))
+}
+
+fn execute_create_schedule(
+    ctx: ExecuteContext,
+    recipient: Recipient,
+    cliff: MillisecondsDuration,
+    period: MillisecondsDuration,
+    per_period: u32,
+) -> Result<Response<Empty>, ContractError> {
+    let ExecuteContext {
+        deps, info, env, ..
+    } = ctx;
+
+    ensure!(per_period > 0, ContractError::InvalidZeroAmount {});
+    ensure!(
+        !period.is_zero(),
+        ContractError::InvalidTimestamp {
+            msg: "Schedule period must be greater than zero".to_string(),
+        }
+    );
+    let recipient_addr = recipient.address.get_raw_address(&deps.as_ref())?;
+    ADOContract::default().is_permissioned(
+        deps.storage,
+        env.clone(),
+        RECEIVE_LOCK_ACTION,
+        recipient_addr.clone(),
+    )?;
+
+    let schedule_id = SCHEDULE_COUNT.may_load(deps.storage)?.unwrap_or_default() + 1;
+    SCHEDULE_COUNT.save(deps.storage, &schedule_id)?;
+    SCHEDULES.save(
+        deps.storage,
+        schedule_id,
+        &Schedule {
+            owner: info.sender.clone(),
+            recipient: recipient_addr.clone(),
+            recipient_msg: recipient.msg,
+            start: Milliseconds::from_seconds(env.block.time.seconds()),
+            cliff,
+            period,
+            per_period,
+            deposited: 0,
+            released: 0,
+        },
+    )?;
+
+    Ok(Response::new()
+        .add_attribute("method", "create_schedule")
+        .add_attribute("schedule_id", schedule_id.to_string())
+        .add_attribute("owner", info.sender)
+        .add_attribute("recipient", recipient_addr))
+}
+
+/// Locks the sent NFT until the tranche of the next open slot of the schedule unlocks. Each
+/// NFT can be claimed on its own once its tranche has unlocked.
+fn execute_add_to_schedule(
+    ctx: ExecuteContext,
+    sender: String,
+    token_id: String,
+    schedule_id: u64,
+) -> Result<Response<Empty>, ContractError> {
+    let ExecuteContext {
+        mut deps,
+        info,
+        env,
+        ..
+    } = ctx;
+
+    ADOContract::default().is_permissioned(
+        deps.storage,
+        env.clone(),
+        SEND_NFT_ACTION,
+        info.sender.clone(),
+    )?;
+
+    let mut schedule = SCHEDULES
+        .may_load(deps.storage, schedule_id)?
+        .ok_or(ContractError::NFTNotFound {})?;
+    let depositor = deps.api.addr_validate(&sender)?;
+    ensure!(depositor == schedule.owner, ContractError::Unauthorized {});
+
+    let unlock_time = schedule.tranche_time(schedule.deposited / schedule.per_period);
+    let (id, fee_msgs) = lock_group_item(
+        &mut deps,
+        &env,
+        &info.sender,
+        &token_id,
+        TimelockInfo {
+            lock_id: 0,
+            locked_at: Milliseconds::from_seconds(env.block.time.seconds()),
+            unlock_condition: UnlockCondition::Time(unlock_time),
+            recipient: schedule.recipient.clone(),
+            recipient_msg: schedule.recipient_msg.clone(),
+            depositor,
+            revocable: false,
+            receipt: None,
+            reward: None,
+            voting_power: Uint128::zero(),
+            hash_lock: None,
+            swap_offer: None,
+            arbiters: None,
+            frozen: false,
+            bundle: None,
+            schedule: Some(schedule_id),
+            fallback: None,
+            heartbeat_interval: None,
+            claim_fee: None,
+            set_aside: false,
+        },
+    )?;
+    schedule.deposited += 1;
+    SCHEDULES.save(deps.storage, schedule_id, &schedule)?;
+
+    Ok(Response::new()
+        .add_messages(fee_msgs)
+        .add_attribute("method", "add_to_schedule")
+        .add_attribute("schedule_id", schedule_id.to_string())
+        .add_attribute("contract_address", info.sender)
+        .add_attribute("token_id", token_id)
+        .add_attribute("lock_id", id.to_string())
+        .add_attribute("unlock_time", unlock_time.seconds().to_string()))
+}
+
+/// Claims the lock represented by a receipt for whoever sent it, and burns the receipt.
+fn execute_redeem_receipt(
+    ctx: ExecuteContext,
+    holder: String,
+    receipt_token_id: String,
+) -> Result<Response<Empty>, ContractError> {
+    let ExecuteContext {
+        deps, info, env, ..
+    } = ctx;
+
+    let (cw721_contract, token_id) = RECEIPTS
+        .may_load(deps.storage, (&info.sender, receipt_token_id.as_str()))?
+        .ok_or(ContractError::NFTNotFound {})?;
+    let lock_id = (&cw721_contract, token_id.as_str());
+    let mut timelock_info = timelocks().load(deps.storage, lock_id)?;
+
+    ensure!(
+        timelock_info.unlock_condition.is_met(&env.block),
+        ContractError::LockedNFT {}
+    );
+
+    // The receipt may have been sold, so its holder is a new recipient of the NFT
+    let holder = deps.api.addr_validate(&holder)?;
+    ADOContract::default().is_permissioned(
+        deps.storage,
+        env.clone(),
+        RECEIVE_LOCK_ACTION,
+        holder.clone(),
+    )?;
+    let fee_msgs = charge_rate(
+        deps.storage,
+        &RateAction::ClaimNft,
+        timelock_info.claim_fee.as_ref(),
+        &holder,
+    )?;
+    settle_rewards(deps.storage, &env.block, &mut timelock_info)?;
+    close_lock(
+        deps.storage,
+        &env.block,
+        &cw721_contract,
+        &token_id,
+        &mut timelock_info,
+        LockStatus::Claimed,
+        &holder,
+    )?;
+    RECEIPTS.remove(deps.storage, (&info.sender, receipt_token_id.as_str()));
+
+    let transfer_msg = generate_transfer_msg(&cw721_contract, &token_id, &holder)?;
+    let burn_msg = WasmMsg::Execute {
+        contract_addr: info.sender.to_string(),
+        msg: encode_binary(&Cw721ExecuteMsg::Burn {
+            token_id: receipt_token_id.clone(),
+        })?,
+        funds: vec![],
+    };
+
+    Ok(Response::new()
+        .add_message(transfer_msg)
+        .add_message(burn_msg)
+        .add_messages(fee_msgs)
+        .add_attribute("method", "redeem_receipt")
+        .add_attribute("token_id", token_id)
+        .add_attribute("receipt_token_id", receipt_token_id)
+        .add_attribute("recipient", holder))
+}
+
+fn execute_claim_cw721(
+    ctx: ExecuteContext,
+    cw721_contract: AndrAddr,
+    token_id: String,
+) -> Result<Response<Empty>, ContractError> {
+    let ExecuteContext {
+        deps, info, env, ..
+    } = ctx;
+
+    let cw721_contract = cw721_contract.get_raw_address(&deps.as_ref())?;
+    // Attached funds top up the fee balance the recipient pays the claim fee from
+    if !info.funds.is_empty() {
+        let recipient = timelocks()
+            .load(deps.storage, (&cw721_contract, token_id.as_str()))
+            .map_err(|_| ContractError::NFTNotFound {})?
+            .recipient;
+        for fund in &info.funds {
+            add_to_fee_balance(
+                deps.storage,
+                &recipient,
+                &Asset::Native(fund.denom.clone()),
+                fund.amount,
+            )?;
+        }
+    }
+    let (claim_msgs, recipient) = claim_lock(
+        deps.storage,
+        &env.block,
+        &cw721_contract,
+        &token_id,
+        Some(&info.sender),
+    )?;
+
+    Ok(Response::new()
+        .add_messages(claim_msgs)
+        .add_attribute("method", "claim_nft")
+        .add_attribute("token_id", token_id)
+        .add_attribute("recipient", recipient.to_string()))
+}
+
+fn execute_release_to_fallback(
+    ctx: ExecuteContext,
+    cw721_contract: AndrAddr,
+    token_id: String,
+) -> Result<Response<Empty>, ContractError> {
+    let ExecuteContext { deps, env, .. } = ctx;
+
+    let cw721_contract = cw721_contract.get_raw_address(&deps.as_ref())?;
+    let mut timelock_info = timelocks()
+        .load(deps.storage, (&cw721_contract, token_id.as_str()))
+        .map_err(|_| ContractError::NFTNotFound {})?;
+    let (Some(fallback), Some(claim_deadline)) = (
+        timelock_info.fallback.clone(),
+        timelock_info.claim_deadline(),
+    ) else {
+        return Err(ContractError::UnsupportedOperation {});
+    };
+
+    ensure!(!timelock_info.frozen, ContractError::Paused {});
+    ensure!(
+        timelock_info.unlock_condition.is_met(&env.block) && claim_deadline.is_expired(&env.block),
+        ContractError::LockedNFT {}
+    );
+
+    // The recipient missed the window, so no claim fee is charged
+    let delivery_msg = generate_delivery_msg(
+        &cw721_contract,
+        &token_id,
+        &fallback.recipient,
+        fallback.recipient_msg.as_ref(),
+    )?;
+    settle_rewards(deps.storage, &env.block, &mut timelock_info)?;
+    close_lock(
+        deps.storage,
+        &env.block,
+        &cw721_contract,
+        &token_id,
+        &mut timelock_info,
+        LockStatus::Forfeited,
+        &fallback.recipient,
+    )?;
+
+    Ok(Response::new()
+        .add_message(delivery_msg)
+        .add_attribute("method", "release_to_fallback")
+        .add_attribute("token_id", token_id)
+        .add_attribute("recipient", fallback.recipient.to_string()))
+}
+
+/// Claims each lock independently. Locks that cannot be claimed are reported with a
+/// `claim_failed` event instead of reverting the others, unless none of them succeed.
+fn execute_claim_cw721s(
+    ctx: ExecuteContext,
+    items: Vec<(AndrAddr, String)>,
+) -> Result<Response<Empty>, ContractError> {
+    let ExecuteContext {
+        deps, env, info, ..
+    } = ctx;
+    ensure!(
+        items.len() <= MAX_LIMIT as usize,
+        ContractError::UnsupportedOperation {}
+    );
+
+    let mut resp = Response::new();
+    let (mut claimed, mut failed) = (0u32, 0u32);
+    let mut first_error = None;
+    for (cw721_contract, token_id) in items {
+        let result = cw721_contract
+            .get_raw_address(&deps.as_ref())
+            .and_then(|cw721_contract| {
+                claim_lock(
+                    deps.storage,
+                    &env.block,
+                    &cw721_contract,
+                    &token_id,
+                    Some(&info.sender),
+                )
+            });
+        match result {
+            Ok((claim_msgs, _)) => {
+                claimed += 1;
+                resp = resp.add_messages(claim_msgs);
+            }
+            Err(err) => {
+                resp = resp.add_event(
+                    Event::new("claim_failed")
+                        .add_attribute("cw721_contract", cw721_contract.to_string())
+                        .add_attribute("token_id", token_id)
+                        .add_attribute("reason", err.to_string()),
+                );
+                failed += 1;
+                first_error.get_or_insert(err);
+            }
+        }
+    }
+
+    if claimed == 0 {
+        if let Some(err) = first_error {
+            return Err(err);
+        }
+    }
+
+    Ok(resp
+        .add_attribute("method", "claim_nfts")
+        .add_attribute("claimed", claimed.to_string())
+        .add_attribute("failed", failed.to_string()))
+}
+
+/// Looks at one page of the recipient's locks and claims those that can be released. Locks that
+/// fail to claim, such as those whose recipient cannot pay the claim fee, are reported with a
+/// `claim_failed` event like `ClaimNfts`.
+fn execute_claim_all_unlocked(
+    ctx: ExecuteContext,
+    recipient: AndrAddr,
+    start_after: Option<(String, String)>,
+    limit: Option<u32>,
+) -> Result<Response<Empty>, ContractError> {
+    let ExecuteContext { deps, env, .. } = ctx;
+
+    let recipient = recipient.get_raw_address(&deps.as_ref())?;
+    let locks = recipient_locks(deps.as_ref(), &recipient, start_after, limit)?;
+    let page_end = page_end_attributes(&locks, limit);
+
+    let mut resp = Response::new();
+    let (mut claimed, mut failed) = (0u32, 0u32);
+    for ((cw721_contract, token_id), timelock_info) in locks {
+        if !timelock_info.can_release(&env.block) {
+            continue;
+        }
+        match claim_lock(deps.storage, &env.block, &cw721_contract, &token_id, None) {
+            Ok((claim_msgs, _)) => {
+                claimed += 1;
+                resp = resp.add_messages(claim_msgs);
+            }
+            Err(err) => {
+                resp = resp.add_event(
+                    Event::new("claim_failed")
+                        .add_attribute("cw721_contract", cw721_contract.to_string())
+                        .add_attribute("token_id", token_id)
+                        .add_attribute("reason", err.to_string()),
+                );
+                failed += 1;
+            }
+        }
+    }
+
+    Ok(resp
+        .add_attribute("method", "claim_all_unlocked")
+        .add_attribute("recipient", recipient)
+        .add_attribute("claimed", claimed.to_string())
+        .add_attribute("failed", failed.to_string())
+        .add_attributes(page_end))
+}
+
+/// A page of the recipient's locks in `(cw721_contract, token_id)` order, so messages and
+/// queries that go over all of a recipient's locks do a bounded amount of work per call.
+fn recipient_locks(
+    deps: Deps,
+    recipient: &Addr,
+    start_after: Option<(String, String)>,
+    limit: Option<u32>,
+) -> Result<Vec<KeyedLock>, ContractError> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = match start_after {
+        Some((cw721_contract, token_id)) => Some(Bound::exclusive((
+            deps.api.addr_validate(&cw721_contract)?,
+            token_id,
+        ))),
+        None => None,
+    };
+
+    Ok(timelocks()
+        .idx
+        .recipient
+        .prefix(recipient.clone())
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .collect::<StdResult<Vec<_>>>()?)
+}
+
+/// The last lock of a full page, which the next page starts after. A page that is not full is
+/// the last one.
+fn page_end_attributes(locks: &[KeyedLock], limit: Option<u32>) -> Vec<Attribute> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    match locks.last() {
+        Some(((cw721_contract, token_id), _)) if locks.len() == limit => vec![
+            attr("last_cw721_contract", cw721_contract),
+            attr("last_token_id", token_id),
+        ],
+        _ => vec![],
+    }
+}
+
+fn execute_release_expired(
+    ctx: ExecuteContext,
+    limit: Option<u32>,
+) -> Result<Response<Empty>, ContractError> {
+    let ExecuteContext {
+        deps, info, env, ..
+    } = ctx;
+
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let (mut resp, released, scanned) = release_expired(deps.storage, &env.block, limit)?;
+    let power_expired = expire_voting_power(deps.storage, &env.block, limit - scanned)?;
+
+    let mut bounty_paid = Coin::default();
+    if let Some(bounty) = KEEPER_BOUNTY.may_load(deps.storage)? {
+        let pool = BOUNTY_POOL
+            .may_load(deps.storage, &bounty.denom)?
+            .unwrap_or_default();
+        let amount = bounty
+            .amount
+            .checked_mul(Uint128::from(released as u128))?
+            .min(pool);
+        if !amount.is_zero() {
+            match pool - amount {
+                remaining if remaining.is_zero() => BOUNTY_POOL.remove(deps.storage, &bounty.denom),
+                remaining => BOUNTY_POOL.save(deps.storage, &bounty.denom, &remaining)?,
+            }
+            bounty_paid = coin(amount.u128(), bounty.denom);
+            resp = resp.add_message(BankMsg::Send {
+                to_address: info.sender.to_string(),
+                amount: vec![bounty_paid.clone()],
+            });
+        }
+    }
+
+    Ok(resp
+        .add_attribute("method", "release_expired")
+        .add_attribute("released", released.to_string())
+        .add_attribute("power_expired", power_expired.to_string())
+        .add_attribute("bounty", bounty_paid.to_string()))
+}
+
+/// Takes the voting power away from up to `limit` locks whose unlock time has passed but which
+/// have not been released, e.g. because they are frozen or wait for their recipient to claim.
+fn expire_voting_power(
+    storage: &mut dyn Storage,
+    block: &BlockInfo,
+    limit: usize,
+) -> Result<usize, ContractError> {
+    let now = Milliseconds::from_seconds(block.time.seconds());
+    let max = Bound::exclusive((now.milliseconds() + 1, (Addr::unchecked(""), String::new())));
+    let expired = timelocks()
+        .idx
+        .power_expiry
+        .range(storage, None, Some(max), Order::Ascending)
+        .take(limit)
+        .collect::<StdResult<Vec<_>>>()?;
+
+    let count = expired.len();
+    for ((cw721_contract, token_id), mut timelock_info) in expired {
+        set_voting_power(storage, block, &mut timelock_info, Uint128::zero())?;
+        timelocks().save(storage, (&cw721_contract, &token_id), &timelock_info)?;
+    }
+    Ok(count)
+}
+
+/// Looks at up to `limit` locks whose release time has passed, earliest first, and releases
+/// them. Each NFT is delivered in a submessage so a recipient that rejects it cannot revert
+/// the others, see `reply`. Locks whose recipient cannot cover the claim fee are set aside, as
+/// are locks that fail to release, which are reported in a `release_failed` event. Returns the
+/// response with the deliveries, how many locks were released and how many were looked at.
+fn release_expired(
+    storage: &mut dyn Storage,
+    block: &BlockInfo,
+    limit: usize,
+) -> Result<(Response, usize, usize), ContractError> {
+    CRANK_DELIVERIES.clear(storage);
+
+    let now = Milliseconds::from_nanos(block.time.nanos());
+    let max = Bound::inclusive((now.milliseconds(), (Addr::unchecked(""), String::new())));
+    let due = timelocks()
+        .idx
+        .release_time
+        .range(storage, None, Some(max), Order::Ascending)
+        .take(limit)
+        .collect::<StdResult<Vec<_>>>()?;
+
+    let scanned = due.len();
+    let mut resp = Response::new();
+    let mut released = 0;
+    for ((cw721_contract, token_id), mut timelock_info) in due {
+        let claim_fee = timelock_info.claim_fee.as_ref();
+        if !covers_fee(storage, claim_fee, &timelock_info.recipient)? {
+            timelock_info.set_aside = true;
+            timelocks().save(storage, (&cw721_contract, &token_id), &timelock_info)?;
+            continue;
+        }
+
+        let lock_id = timelock_info.lock_id;
+        let (delivery, recipient) =
+            match claim_lock(storage, block, &cw721_contract, &token_id, None) {
+                Ok(claimed) => claimed,
+                Err(err) => {
+                    resp = resp.add_event(
+                        Event::new("release_failed")
+                            .add_attribute("cw721_contract", cw721_contract.to_string())
+                            .add_attribute("token_id", token_id.clone())
+                            .add_attribute("reason", err.to_string()),
+                    );
+                    timelock_info.set_aside = true;
+                    timelocks().save(storage, (&cw721_contract, &token_id), &timelock_info)?;
+                    continue;
+                }
+            };
+        // The claim fee is paid out in the reply, once the NFT has arrived, so only the delivery
+        // itself is sent here
+        if let Some(delivery) = delivery.into_iter().next() {
+            resp = resp.add_submessage(SubMsg::reply_always(delivery, lock_id));
+        }
+        CRANK_DELIVERIES.save(
+            storage,
+            lock_id,
+            &Delivery {
+                lock_id,
+                cw721_contract,
+                token_id,
+                recipient,
+                claim_fee: timelock_info.claim_fee,
+            },
+        )?;
+        released += 1;
+    }
+    Ok((resp, released, scanned))
+}
+
+/// Pays out the claim fee of an NFT the release cranks delivered. If the delivery failed, the
+/// fee goes back to the recipient's fee balance and the NFT waits for `ClaimUndelivered`.
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn reply(deps: DepsMut, _env: Env, msg: Reply) -> Result<Response, ContractError> {
+    let delivery = CRANK_DELIVERIES
+        .may_load(deps.storage, msg.id)?
+        .ok_or(ContractError::InvalidReplyId {})?;
+    CRANK_DELIVERIES.remove(deps.storage, msg.id);
+    let reason = match msg.result {
+        SubMsgResult::Ok(_) => {
+            return Ok(Response::new().add_messages(rate_payouts(delivery.claim_fee.as_ref())?));
+        }
+        SubMsgResult::Err(reason) => reason,
+    };
+
+    if let Some(rate) = &delivery.claim_fee {
+        add_to_fee_balance(deps.storage, &delivery.recipient, &rate.asset, rate.amount)?;
+    }
+    update_lock_record(deps.storage, &delivery, LockStatus::Undelivered, None, None)?;
+    UNDELIVERED.save(
+        deps.storage,
+        (&delivery.cw721_contract, &delivery.token_id),
+        &delivery,
+    )?;
+
+    Ok(Response::new().add_event(
+        Event::new("delivery_failed")
+            .add_attribute("cw721_contract", delivery.cw721_contract)
+            .add_attribute("token_id", delivery.token_id)
+            .add_attribute("recipient", delivery.recipient)
+            .add_attribute("reason", reason),
+    ))
+}
+
+/// Rewrites how the lock history records a lock the release cranks closed.
+fn update_lock_record(
+    storage: &mut dyn Storage,
+    delivery: &Delivery,
+    status: LockStatus,
+    claimer: Option<Addr>,
+    claimed_at: Option<u64>,
+) -> Result<(), ContractError> {
+    let key = (
+        &delivery.cw721_contract,
+        delivery.token_id.as_str(),
+        delivery.lock_id,
+    );
+    let mut record = LOCK_HISTORY.load(storage, key)?;
+    record.status = status;
+    record.claimer = claimer;
+    record.claimed_at = claimed_at;
+    LOCK_HISTORY.save(storage, key, &record)?;
+    Ok(())
+}
+
+/// Charges the claim fee the failed delivery gave back, like `ClaimNft`.
+fn execute_claim_undelivered(
+    ctx: ExecuteContext,
+    cw721_contract: AndrAddr,
+    token_id: String,
+) -> Result<Response<Empty>, ContractError> {
+    let ExecuteContext {
+        deps, info, env, ..
+    } = ctx;
+
+    let cw721_contract = cw721_contract.get_raw_address(&deps.as_ref())?;
+    let delivery = UNDELIVERED
+        .may_load(deps.storage, (&cw721_contract, &token_id))?
+        .ok_or(ContractError::NFTNotFound {})?;
+    ensure!(
+        info.sender == delivery.recipient,
+        ContractError::Unauthorized {}
+    );
+    // Attached funds top up the fee balance the claim fee is paid from
+    for fund in &info.funds {
+        add_to_fee_balance(
+            deps.storage,
+            &info.sender,
+            &Asset::Native(fund.denom.clone()),
+            fund.amount,
+        )?;
+    }
+    let fee_msgs = charge_rate(
+        deps.storage,
+        &RateAction::ClaimNft,
+        delivery.claim_fee.as_ref(),
+        &delivery.recipient,
+    )?;
+    update_lock_record(
+        deps.storage,
+        &delivery,
+        LockStatus::Claimed,
+        Some(delivery.recipient.clone()),
+        Some(env.block.time.seconds()),
+    )?;
+    UNDELIVERED.remove(deps.storage, (&cw721_contract, &token_id));
+
+    Ok(Response::new()
+        .add_message(generate_transfer_msg(
+            &cw721_contract,
+            &token_id,
+            &delivery.recipient,
+        )?)
+        .add_messages(fee_msgs)
+        .add_attribute("method", "claim_undelivered")
+        .add_attribute("cw721_contract", cw721_contract)
+        .add_attribute("token_id", token_id)
+        .add_attribute("recipient", delivery.recipient))
+}
+
+fn execute_fund_bounty_pool(ctx: ExecuteContext) -> Result<Response<Empty>, ContractError> {
+    let ExecuteContext { deps, info, .. } = ctx;
+
+    ensure!(
+        !info.funds.is_empty(),
+        ContractError::InvalidFunds {
+            msg: "Must send funds to fund the bounty pool".to_string(),
+        }
+    );
+    for fund in &info.funds {
+        BOUNTY_POOL.update(deps.storage, &fund.denom, |pool| -> StdResult<_> {
+            Ok(pool.unwrap_or_default() + fund.amount)
+        })?;
+    }
+
+    Ok(Response::new()
+        .add_attribute("method", "fund_bounty_pool")
+        .add_attribute("sender", info.sender)
+        .add_attribute("funds", format!("{:?}", info.funds)))
+}
+
+fn execute_update_rate(
+    ctx: ExecuteContext,
+    action: RateAction,
+    rate: Option<Rate>,
+) -> Result<Response<Empty>, ContractError> {
+    let ExecuteContext { deps, info, .. } = ctx;
+
+    ensure!(
+        ADOContract::default().is_contract_owner(deps.storage, info.sender.as_str())?,
+        ContractError::Unauthorized {}
+    );
+
+    match &rate {
+        Some(rate) => {
+            ensure!(!rate.amount.is_zero(), ContractError::InvalidZeroAmount {});
+            if let Asset::Cw20(address) = &rate.asset {
+                deps.api.addr_validate(address.as_str())?;
+            }
+            let mut total_share = Decimal::zero();
+            for recipient in &rate.recipients {
+                deps.api.addr_validate(recipient.address.as_str())?;
+                ensure!(!recipient.share.is_zero(), ContractError::InvalidRate {});
+                total_share += recipient.share;
+            }
+            ensure!(total_share == Decimal::one(), ContractError::InvalidRate {});
+            RATES.save(deps.storage, &action.to_string(), rate)?;
+        }
+        None => RATES.remove(deps.storage, &action.to_string()),
+    }
+
+    Ok(Response::new()
+        .add_attribute("method", "update_rate")
+        .add_attribute("action", action.to_string()))
+}
+
+fn execute_deposit_fee_balance(
+    ctx: ExecuteContext,
+    recipient: Option<AndrAddr>,
+) -> Result<Response<Empty>, ContractError> {
+    let ExecuteContext { deps, info, .. } = ctx;
+
+    ensure!(
+        !info.funds.is_empty(),
+        ContractError::InvalidFunds {
+            msg: "Must send funds to deposit to a fee balance".to_string(),
+        }
+    );
+    let recipient = match recipient {
+        Some(recipient) => recipient.get_raw_address(&deps.as_ref())?,
+        None => info.sender,
+    };
+    for fund in &info.funds {
+        add_to_fee_balance(
+            deps.storage,
+            &recipient,
+            &Asset::Native(fund.denom.clone()),
+            fund.amount,
+        )?;
+    }
+
+    Ok(Response::new()
+        .add_attribute("method", "deposit_fee_balance")
+        .add_attribute("recipient", recipient)
+        .add_attribute("funds", format!("{:?}", info.funds)))
+}
+
+fn execute_withdraw_fee_balance(
+    ctx: ExecuteContext,
+    asset: Asset,
+    amount: Option<Uint128>,
+) -> Result<Response<Empty>, ContractError> {
+    let ExecuteContext { deps, info, .. } = ctx;
+
+    let key = asset.key();
+    let balance = fee_balance(deps.storage, &info.sender, &asset)?;
+    ensure!(!balance.is_zero(), ContractError::NoPendingPayments {});
+    let amount = amount.unwrap_or(balance);
+    ensure!(!amount.is_zero(), ContractError::InvalidZeroAmount {});
+    ensure!(
+        amount <= balance,
+        ContractError::InvalidFunds {
+            msg: format!("Fee balance is only {balance} {key}"),
+        }
+    );
+
+    if amount == balance {
+        FEE_BALANCES.remove(deps.storage, (&info.sender, &key));
+    } else {
+        FEE_BALANCES.save(
+            deps.storage,
+            (&info.sender, &key),
+            &(asset.clone(), balance - amount),
+        )?;
+    }
+
+    Ok(Response::new()
+        .add_message(generate_asset_msg(&asset, &info.sender, amount)?)
+        .add_attribute("method", "withdraw_fee_balance")
+        .add_attribute("recipient", info.sender)
+        .add_attribute("amount", format!("{amount} {key}")))
+}
+
+fn add_to_fee_balance(
+    storage: &mut dyn Storage,
+    payer: &Addr,
+    asset: &Asset,
+    amount: Uint128,
+) -> StdResult<()> {
+    FEE_BALANCES.update(storage, (payer, &asset.key()), |balance| -> StdResult<_> {
+        let balance = balance.map_or(Uint128::zero(), |(_, balance)| balance);
+        Ok((asset.clone(), balance + amount))
+    })?;
+    Ok(())
+}
+
+fn fee_balance(storage: &dyn Storage, payer: &Addr, asset: &Asset) -> StdResult<Uint128> {
+    Ok(FEE_BALANCES
+        .may_load(storage, (payer, &asset.key()))?
+        .map_or(Uint128::zero(), |(_, balance)| balance))
+}
+
+fn covers_fee(storage: &dyn Storage, rate: Option<&Rate>, payer: &Addr) -> StdResult<bool> {
+    match rate {
+        Some(rate) => Ok(fee_balance(storage, payer, &rate.asset)? >= rate.amount),
+        None => Ok(true),
+    }
+}
+
+/// Takes the current fee for `action` from the payer's fee balance, see `charge_rate`.
+fn charge_fee(
+    storage: &mut dyn Storage,
+    action: &RateAction,
+    payer: &Addr,
+) -> Result<Vec<CosmosMsg>, ContractError> {
+    let rate = RATES.may_load(storage, &action.to_string())?;
+    charge_rate(storage, action, rate.as_ref(), payer)
+}
+
+/// Takes `rate` from the payer's fee balance and returns the messages paying it out to the
+/// rate's recipients. Nothing is written if the balance falls short.
+fn charge_rate(
+    storage: &mut dyn Storage,
+    action: &RateAction,
+    rate: Option<&Rate>,
+    payer: &Addr,
+) -> Result<Vec<CosmosMsg>, ContractError> {
+    let Some(rate) = rate else {
+        return Ok(vec![]);
+    };
+
+    let key = rate.asset.key();
+    let balance = fee_balance(storage, payer, &rate.asset)?;
+    ensure!(
+        balance >= rate.amount,
+        ContractError::InvalidFunds {
+            msg: format!(
+                "Fee balance of {payer} does not cover the {action} fee of {} {key}",
+                rate.amount
+            ),
+        }
+    );
+    if balance == rate.amount {
+        FEE_BALANCES.remove(storage, (payer, &key));
+    } else {
+        FEE_BALANCES.save(
+            storage,
+            (payer, &key),
+            &(rate.asset.clone(), balance - rate.amount),
+        )?;
+    }
+
+    rate_payouts(Some(rate))
+}
+
+/// The messages paying `rate` out to its recipients.
+fn rate_payouts(rate: Option<&Rate>) -> Result<Vec<CosmosMsg>, ContractError> {
+    let Some(rate) = rate else {
+        return Ok(vec![]);
+    };
+    rate.payouts()
+        .iter()
+        .map(|(recipient, amount)| generate_asset_msg(&rate.asset, recipient, *amount))
+        .collect()
+}
+
+fn execute_update_keeper_bounty(
+    ctx: ExecuteContext,
+    bounty: Option<Coin>,
+) -> Result<Response<Empty>, ContractError> {
+    let ExecuteContext { deps, info, .. } = ctx;
+
+    ensure!(
+        ADOContract::default().is_contract_owner(deps.storage, info.sender.as_str())?,
+        ContractError::Unauthorized {}
+    );
+
+    match &bounty {
+        Some(bounty) => KEEPER_BOUNTY.save(deps.storage, bounty)?,
+        None => KEEPER_BOUNTY.remove(deps.storage),
+    }
+
+    Ok(Response::new()
+        .add_attribute("method", "update_keeper_bounty")
+        .add_attribute(
+            "bounty",
+            bounty.map_or("none".to_string(), |bounty| bounty.to_string()),
+        ))
+}
+
+fn execute_update_receipt_contract(
+    ctx: ExecuteContext,
+    address: Option<AndrAddr>,
+) -> Result<Response<Empty>, ContractError> {
+    let ExecuteContext { deps, info, .. } = ctx;
+
+    ensure!(
+        ADOContract::default().is_contract_owner(deps.storage, info.sender.as_str())?,
+        ContractError::Unauthorized {}
+    );
+
+    // Existing receipts keep pointing at the contract they were minted on
+    let address = match address {
+        Some(address) => {
+            let address = address.get_raw_address(&deps.as_ref())?;
+            RECEIPT_CONTRACT.save(deps.storage, &address)?;
+            address.to_string()
+        }
+        None => {
+            RECEIPT_CONTRACT.remove(deps.storage);
+            "none".to_string()
+        }
+    };
+
+    Ok(Response::new()
+        .add_attribute("method", "update_receipt_contract")
+        .add_attribute("address", address))
+}
+
+fn handle_receive_cw20(
+    ctx: ExecuteContext,
+    msg: Cw20ReceiveMsg,
+) -> Result<Response<Empty>, ContractError> {
+    let ExecuteContext { deps, info, .. } = ctx;
+
+    match from_json(&msg.msg)? {
+        Cw20HookMsg::FundRewardPool {} => {
+            ensure!(
+                ADOContract::default().is_contract_owner(deps.storage, &msg.sender)?,
+                ContractError::Unauthorized {}
+            );
+            ensure!(
+                !msg.amount.is_zero(),
+                ContractError::InvalidFunds {
+                    msg: "Must send funds to fund the reward pool".to_string(),
+                }
+            );
+            let asset = Asset::Cw20(info.sender);
+            add_to_reward_pool(deps.storage, &asset, msg.amount)?;
+
+            Ok(Response::new()
+                .add_attribute("method", "fund_reward_pool")
+                .add_attribute("asset", asset.key())
+                .add_attribute("amount", msg.amount))
+        }
+        Cw20HookMsg::DepositFeeBalance { recipient } => {
+            ensure!(
+                !msg.amount.is_zero(),
+                ContractError::InvalidFunds {
+                    msg: "Must send funds to deposit to a fee balance".to_string(),
+                }
+            );
+            let recipient = match recipient {
+                Some(recipient) => recipient.get_raw_address(&deps.as_ref())?,
+                None => deps.api.addr_validate(&msg.sender)?,
+            };
+            let asset = Asset::Cw20(info.sender);
+            add_to_fee_balance(deps.storage, &recipient, &asset, msg.amount)?;
+
+            Ok(Response::new()
+                .add_attribute("method", "deposit_fee_balance")
+                .add_attribute("recipient", recipient)
+                .add_attribute("asset", asset.key())
+                .add_attribute("amount", msg.amount))
+        }
+    }
+}
+
+fn execute_update_reward_config(
+    ctx: ExecuteContext,
+    cw721_contract: AndrAddr,
+    config: Option<RewardConfig>,
+) -> Result<Response<Empty>, ContractError> {
+    let ExecuteContext { deps, info, .. } = ctx;
+
+    ensure!(
+        ADOContract::default().is_contract_owner(deps.storage, info.sender.as_str())?,
+        ContractError::Unauthorized {}
+    );
+
+    let cw721_contract = cw721_contract.get_raw_address(&deps.as_ref())?;
+    match &config {
+        Some(config) => {
+            if let Asset::Cw20(address) = &config.asset {
+                deps.api.addr_validate(address.as_str())?;
+            }
+            REWARD_CONFIGS.save(deps.storage, &cw721_contract, config)?;
+        }
+        None => REWARD_CONFIGS.remove(deps.storage, &cw721_contract),
+    }
+
+    Ok(Response::new()
+        .add_attribute("method", "update_reward_config")
+        .add_attribute("cw721_contract", cw721_contract))
+}
+
+fn execute_fund_reward_pool(ctx: ExecuteContext) -> Result<Response<Empty>, ContractError> {
+    let ExecuteContext { deps, info, .. } = ctx;
+
+    ensure!(
+        ADOContract::default().is_contract_owner(deps.storage, info.sender.as_str())?,
+        ContractError::Unauthorized {}
+    );
+    ensure!(
+        !info.funds.is_empty(),
+        ContractError::InvalidFunds {
+            msg: "Must send funds to fund the reward pool".to_string(),
+        }
+    );
+    for fund in &info.funds {
+        add_to_reward_pool(
+            deps.storage,
+            &Asset::Native(fund.denom.clone()),
+            fund.amount,
+        )?;
+    }
+
+    Ok(Response::new()
+        .add_attribute("method", "fund_reward_pool")
+        .add_attribute("funds", format!("{:?}", info.funds)))
+}
+
+fn add_to_reward_pool(storage: &mut dyn Storage, asset: &Asset, amount: Uint128) -> StdResult<()> {
+    REWARD_POOLS.update(storage, &asset.key(), |pool| -> StdResult<_> {
+        let pool = pool.map_or(Uint128::zero(), |(_, pool)| pool);
+        Ok((asset.clone(), pool + amount))
+    })?;
+    Ok(())
+}
+
+/// Settles the rewards of one page of the sender's locks and pays out everything they are owed.
+/// Rewards the pools cannot cover stay owed until the pools are topped up.
+fn execute_claim_rewards(
+    ctx: ExecuteContext,
+    start_after: Option<(String, String)>,
+    limit: Option<u32>,
+) -> Result<Response<Empty>, ContractError> {
+    let ExecuteContext {
+        deps, info, env, ..
+    } = ctx;
+
+    let locks = recipient_locks(deps.as_ref(), &info.sender, start_after, limit)?;
+    let page_end = page_end_attributes(&locks, limit);
+    for ((cw721_contract, token_id), mut timelock_info) in locks {
+        if timelock_info.reward.is_none() {
+            continue;
+        }
+        settle_rewards(deps.storage, &env.block, &mut timelock_info)?;
+        timelocks().save(
+            deps.storage,
+            (&cw721_contract, token_id.as_str()),
+            &timelock_info,
+        )?;
+    }
+
+    let owed = OWED_REWARDS
+        .prefix(&info.sender)
+        .range(deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+
+    let mut resp = Response::new();
+    let mut paid = vec![];
+    for (key, (asset, amount)) in owed {
+        let pool = REWARD_POOLS
+            .may_load(deps.storage, &key)?
+            .map_or(Uint128::zero(), |(_, pool)| pool);
+        let payout = amount.min(pool);
+        if payout.is_zero() {
+            continue;
+        }
+
+        if pool == payout {
+            REWARD_POOLS.remove(deps.storage, &key);
+        } else {
+            REWARD_POOLS.save(deps.storage, &key, &(asset.clone(), pool - payout))?;
+        }
+        if amount == payout {
+            OWED_REWARDS.remove(deps.storage, (&info.sender, &key));
+        } else {
+            OWED_REWARDS.save(
+                deps.storage,
+                (&info.sender, &key),
+                &(asset.clone(), amount - payout),
+            )?;
+        }
+
+        resp = resp.add_message(generate_asset_msg(&asset, &info.sender, payout)?);
+        paid.push(format!("{payout} {}", asset.key()));
+    }
+
+    ensure!(!paid.is_empty(), ContractError::NoPendingPayments {});
+
+    Ok(resp
+        .add_attribute("method", "claim_rewards")
+        .add_attribute("recipient", info.sender)
+        .add_attribute("rewards", paid.join(", "))
+        .add_attributes(page_end))
+}
+
+/// Fixes the emission of a new lock, applying the multiplier if the lock is committed for
+/// long enough. Locks that can unlock at a block height before their time, or have no time at
+/// all, earn nothing, as emissions would carry on after the NFT is claimable.
+fn lock_reward(
+    env: &Env,
+    config: &RewardConfig,
+    unlock_condition: &UnlockCondition,
+) -> Option<LockReward> {
+    let unlock_time = match unlock_condition {
+        UnlockCondition::Time(time) | UnlockCondition::TimeAndHeight { time, .. } => time,
+        UnlockCondition::Height(_) | UnlockCondition::TimeOrHeight { .. } => return None,
+    };
+    let now = env.block.time.seconds();
+    let duration = unlock_time.seconds().saturating_sub(now);
+    let rate_per_second = match &config.multiplier {
+        Some(multiplier) if duration >= multiplier.min_duration.seconds() => {
+            config.rate_per_second.mul_floor(multiplier.multiplier)
+        }
+        _ => config.rate_per_second,
+    };
+
+    Some(LockReward {
+        asset: config.asset.clone(),
+        rate_per_second,
+        accrued_until: now,
+    })
+}
+
+/// Moves the rewards a lock has accrued so far to its current recipient.
+fn settle_rewards(
+    storage: &mut dyn Storage,
+    block: &BlockInfo,
+    timelock_info: &mut TimelockInfo,
+) -> Result<(), ContractError> {
+    let Some((asset, amount, accrued_until)) =
+        timelock_info.unsettled_rewards(block.time.seconds())
+    else {
+        return Ok(());
+    };
+
+    if !amount.is_zero() {
+        OWED_REWARDS.update(
+            storage,
+            (&timelock_info.recipient, &asset.key()),
+            |owed| -> StdResult<_> {
+                let owed = owed.map_or(Uint128::zero(), |(_, owed)| owed);
+                Ok((asset.clone(), owed + amount))
+            },
+        )?;
+    }
+    if let Some(reward) = timelock_info.reward.as_mut() {
+        reward.accrued_until = accrued_until;
+    }
+    Ok(())
+}
+
+fn generate_asset_msg(
+    asset: &Asset,
+    recipient: &Addr,
+    amount: Uint128,
+) -> Result<CosmosMsg, ContractError> {
+    Ok(match asset {
+        Asset::Native(denom) => CosmosMsg::Bank(BankMsg::Send {
+            to_address: recipient.to_string(),
+            amount: vec![coin(amount.u128(), denom)],
+        }),
+        Asset::Cw20(address) => CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: address.to_string(),
+            msg: encode_binary(&Cw20ExecuteMsg::Transfer {
+                recipient: recipient.to_string(),
+                amount,
+            })?,
+            funds: vec![],
+        }),
+    })
+}
+
+/// Checks that a lock can be claimed and releases it. Returns the messages from
+/// `release_lock` and the recipient.
+fn claim_lock(
+    storage: &mut dyn Storage,
+    block: &BlockInfo,
+    cw721_contract: &Addr,
+    token_id: &str,
+    caller: Option<&Addr>,
+) -> Result<(Vec<CosmosMsg>, Addr), ContractError> {
+    let timelock_info = timelocks()
+        .load(storage, (cw721_contract, token_id))
+        .map_err(|_| ContractError::NFTNotFound {})?;
+
+    // Hash locks are claimed with `ClaimHashLockedNft`, expired swaps are returned with
+    // `ReclaimSwap` and bundles are claimed with `ClaimBundle`
+    ensure!(
+        !timelock_info.is_escrow() && timelock_info.bundle.is_none(),
+        ContractError::UnsupportedOperation {}
+    );
+    if !timelock_info.unlock_condition.is_met(block) {
+        return Err(ContractError::LockedNFT {});
+    }
+    ensure!(!timelock_info.frozen, ContractError::Paused {});
+    // Locks with a receipt are claimed by sending the receipt with `RedeemReceipt`
+    ensure!(
+        timelock_info.receipt.is_none(),
+        ContractError::UnsupportedOperation {}
+    );
+    // Only the recipient can claim within a claim window, and after it the NFT is forfeited
+    if let Some(claim_deadline) = timelock_info.claim_deadline() {
+        ensure!(
+            caller == Some(&timelock_info.recipient),
+            ContractError::Unauthorized {}
+        );
+        ensure!(!claim_deadline.is_expired(block), ContractError::Expired {});
+    }
+
+    let recipient = timelock_info.recipient.clone();
+    let msgs = release_lock(storage, block, cw721_contract, token_id, timelock_info)?;
+
+    Ok((msgs, recipient))
+}
+
+/// Closes an unlocked lock and charges its recipient the claim fee. Returns the message
+/// delivering the NFT, followed by the fee payouts.
+fn release_lock(
+    storage: &mut dyn Storage,
+    block: &BlockInfo,
+    cw721_contract: &Addr,
+    token_id: &str,
+    mut timelock_info: TimelockInfo,
+) -> Result<Vec<CosmosMsg>, ContractError> {
+    let mut msgs = vec![generate_claim_msg(
+        cw721_contract,
+        token_id,
+        &timelock_info,
+    )?];
+    let recipient = timelock_info.recipient.clone();
+    msgs.extend(charge_rate(
+        storage,
+        &RateAction::ClaimNft,
+        timelock_info.claim_fee.as_ref(),
+        &recipient,
+    )?);
+
+    settle_rewards(storage, block, &mut timelock_info)?;
+    close_lock(
+        storage,
+        block,
+        cw721_contract,
+        token_id,
+        &mut timelock_info,
+        LockStatus::Claimed,
+        &recipient,
+    )?;
+    Ok(msgs)
+}
+
+/// Removes a lock, giving up its voting power, and keeps it in the lock history with the
+/// address its NFT was sent to. The schedule is read first so a failure leaves the lock as is.
+fn close_lock(
+    storage: &mut dyn Storage,
+    block: &BlockInfo,
+    cw721_contract: &Addr,
+    token_id: &str,
+    timelock_info: &mut TimelockInfo,
+    status: LockStatus,
+    claimer: &Addr,
+) -> Result<(), ContractError> {
+    if let Some(schedule_id) = timelock_info.schedule {
+        let mut schedule = SCHEDULES.load(storage, schedule_id)?;
+        schedule.released += 1;
+        SCHEDULES.save(storage, schedule_id, &schedule)?;
+    }
+    set_voting_power(storage, block, timelock_info, Uint128::zero())?;
+    timelocks().remove(storage, (cw721_contract, token_id))?;
+    if let Some(offer_id) = timelock_info.swap_offer {
+        SWAP_OFFERS.remove(storage, offer_id);
+    }
+    LOCK_HISTORY.save(
+        storage,
+        (cw721_contract, token_id, timelock_info.lock_id),
+        &LockRecord {
+            lock_id: timelock_info.lock_id,
+            unlock_condition: timelock_info.unlock_condition.clone(),
+            depositor: timelock_info.depositor.clone(),
+            recipient: timelock_info.recipient.clone(),
+            status,
+            claimer: Some(claimer.clone()),
+            claimed_at: Some(block.time.seconds()),
+        },
+    )?;
+    Ok(())
+}
+
+fn execute_early_unlock(
+    ctx: ExecuteContext,
+    cw721_contract: AndrAddr,
+    token_id: String,
+) -> Result<Response<Empty>, ContractError> {
+    let ExecuteContext {
+        deps, info, env, ..
+    } = ctx;
+
+    let cw721_contract = cw721_contract.get_raw_address(&deps.as_ref())?;
+    let timelock_info = timelocks()
+        .load(deps.storage, (&cw721_contract, token_id.as_str()))
+        .map_err(|_| ContractError::NFTNotFound {})?;
+
+    ensure!(!timelock_info.frozen, ContractError::Paused {});
+    ensure!(
+        !timelock_info.unlock_condition.is_met(&env.block),
+        ContractError::Expired {}
+    );
+    let (policy, price) =
+        early_unlock_price(deps.storage, &env.block, &cw721_contract, &timelock_info)?
+            .ok_or(ContractError::UnsupportedOperation {})?;
+    let caller = match policy.caller {
+        EarlyUnlockCaller::Recipient => &timelock_info.recipient,
+        EarlyUnlockCaller::Depositor => &timelock_info.depositor,
+    };
+    ensure!(info.sender == caller, ContractError::Unauthorized {});
+
+    let paid = info
+        .funds
+        .iter()
+        .find(|fund| fund.denom == price.denom)
+        .map(|fund| fund.amount)
+        .unwrap_or_default();
+    ensure!(
+        paid >= price.amount && info.funds.iter().all(|fund| fund.denom == price.denom),
+        ContractError::InvalidFunds {
+            msg: format!("Early unlock requires a penalty of {price}"),
+        }
+    );
+
+    let recipient = timelock_info.recipient.clone();
+    // The recipient pays the claim fee as for any other release
+    let mut resp = Response::new().add_messages(release_lock(
+        deps.storage,
+        &env.block,
+        &cw721_contract,
+        &token_id,
+        timelock_info,
+    )?);
+    if !price.amount.is_zero() {
+        resp = resp.add_submessage(
+            policy
+                .treasury
+                .generate_direct_msg(&deps.as_ref(), vec![price.clone()])?,
+        );
+    }
+    let excess = paid - price.amount;
+    if !excess.is_zero() {
+        resp = resp.add_message(BankMsg::Send {
+            to_address: info.sender.to_string(),
+            amount: vec![coin(excess.u128(), &price.denom)],
+        });
+    }
+
+    Ok(resp
+        .add_attribute("method", "early_unlock")
+        .add_attribute("token_id", token_id)
+        .add_attribute("recipient", recipient)
+        .add_attribute("penalty", price.to_string()))
+}
+
+/// The penalty for releasing a lock now, with the policy allowing it. Escrows, receipt locks,
+/// frozen locks and locks without an unlock time cannot be unlocked early.
+fn early_unlock_price(
+    storage: &dyn Storage,
+    block: &BlockInfo,
+    cw721_contract: &Addr,
+    timelock_info: &TimelockInfo,
+) -> Result<Option<(EarlyUnlockPolicy, Coin)>, ContractError> {
+    if timelock_info.is_escrow()
+        || timelock_info.is_grouped()
+        || timelock_info.receipt.is_some()
+        || timelock_info.frozen
+        || timelock_info.heartbeat_interval.is_some()
+        || timelock_info.unlock_condition.is_met(block)
+    {
+        return Ok(None);
+    }
+    let Some(unlock_time) = timelock_info.unlock_condition.time() else {
+        return Ok(None);
+    };
+
+    Ok(get_lock_policy(storage, cw721_contract)?
+        .early_unlock
+        .map(|policy| {
+            let remaining_seconds = unlock_time.seconds().saturating_sub(block.time.seconds());
+            let price = policy.penalty.penalty(remaining_seconds);
+            (policy, price)
+        }))
+}
+
+/// Records an arbiter's vote and carries out the decision once enough arbiters agree. Only each
+/// arbiter's latest vote since the last decision was carried out counts.
+fn execute_arbitrate_lock(
+    ctx: ExecuteContext,
+    cw721_contract: AndrAddr,
+    token_id: String,
+    decision: ArbiterDecision,
+    reason: String,
+) -> Result<Response<Empty>, ContractError> {
+    let ExecuteContext {
+        deps, info, env, ..
+    } = ctx;
+
+    let cw721_contract = cw721_contract.get_raw_address(&deps.as_ref())?;
+    let lock_id = (&cw721_contract, token_id.as_str());
+    let mut timelock_info = timelocks()
+        .load(deps.storage, lock_id)
+        .map_err(|_| ContractError::NFTNotFound {})?;
+    let arbiters = timelock_info
+        .arbiters
+        .clone()
+        .ok_or(ContractError::Unauthorized {})?;
+    ensure!(
+        arbiters.members.contains(&info.sender),
+        ContractError::Unauthorized {}
+    );
+
+    let mut votes = ARBITER_VOTES
+        .may_load(deps.storage, timelock_info.lock_id)?
+        .unwrap_or_default();
+    let mut latest_votes = BTreeMap::from([(&info.sender, &decision)]);
+    for vote in votes.iter().rev().take_while(|vote| !vote.executed) {
+        latest_votes.entry(&vote.arbiter).or_insert(&vote.decision);
+    }
+    let approvals = latest_votes
+        .values()
+        .filter(|vote| ***vote == decision)
+        .count();
+    let executed = approvals >= arbiters.threshold as usize;
+    votes.push(ArbiterVote {
+        arbiter: info.sender.clone(),
+        decision: decision.clone(),
+        reason: reason.clone(),
+        time: env.block.time.seconds(),
+        executed,
+    });
+    ARBITER_VOTES.save(deps.storage, timelock_info.lock_id, &votes)?;
+
+    let mut resp = Response::new().add_event(
+        Event::new("arbiter_vote")
+            .add_attribute("cw721_contract", cw721_contract.to_string())
+            .add_attribute("token_id", token_id.clone())
+            .add_attribute("lock_id", timelock_info.lock_id.to_string())
+            .add_attribute("arbiter", info.sender)
+            .add_attribute("decision", decision.to_string())
+            .add_attribute("reason", reason.clone()),
+    );
+    if executed {
+        match decision {
+            ArbiterDecision::Freeze | ArbiterDecision::Unfreeze => {
+                timelock_info.frozen = decision == ArbiterDecision::Freeze;
+                timelocks().save(deps.storage, lock_id, &timelock_info)?;
+            }
+            ArbiterDecision::Release => {
+                resp = resp.add_messages(release_lock(
+                    deps.storage,
+                    &env.block,
+                    &cw721_contract,
+                    &token_id,
+                    timelock_info.clone(),
+                )?);
+            }
+            ArbiterDecision::Refund => {
+                let depositor = timelock_info.depositor.clone();
+                let transfer_msg = generate_transfer_msg(&cw721_contract, &token_id, &depositor)?;
+                settle_rewards(deps.storage, &env.block, &mut timelock_info)?;
+                close_lock(
+                    deps.storage,
+                    &env.block,
+                    &cw721_contract,
+                    &token_id,
+                    &mut timelock_info,
+                    LockStatus::Revoked,
+                    &depositor,
+                )?;
+                resp = resp.add_message(transfer_msg);
+            }
+        }
+        resp = resp.add_event(
+            Event::new("arbiter_decision")
+                .add_attribute("cw721_contract", cw721_contract.to_string())
+                .add_attribute("token_id", token_id.clone())
+                .add_attribute("lock_id", timelock_info.lock_id.to_string())
+                .add_attribute("decision", decision.to_string())
+                .add_attribute("reason", reason),
+        );
+    }
+
+    Ok(resp
+        .add_attribute("method", "arbitrate_lock")
+        .add_attribute("token_id", token_id)
+        .add_attribute("executed", executed.to_string()))
+}
+
+fn execute_revoke_lock(
+    ctx: ExecuteContext,
+    cw721_contract: AndrAddr,
+    token_id: String,
+    new_recipient: Option<Recipient>,
+) -> Result<Response<Empty>, ContractError> {
+    let ExecuteContext {
+        deps, info, env, ..
+    } = ctx;
+
+    let cw721_contract = cw721_contract.get_raw_address(&deps.as_ref())?;
+    let lock_id = (&cw721_contract, token_id.as_str());
+    let mut timelock_info = timelocks()
+        .load(deps.storage, lock_id)
+        .map_err(|_| ContractError::NFTNotFound {})?;
+
+    ensure!(
+        timelock_info.revocable && info.sender == timelock_info.depositor,
+        ContractError::Unauthorized {}
+    );
+    // The arbiters decide where a frozen NFT goes
+    ensure!(!timelock_info.frozen, ContractError::Paused {});
+    // Once unlocked the NFT belongs to the recipient, unless the depositor of a heartbeat lock
+    // takes it back before it is claimed
+    ensure!(
+        timelock_info.heartbeat_interval.is_some()
+            || !timelock_info.unlock_condition.is_met(&env.block),
+        ContractError::Expired {}
+    );
+    // Rewards accrued so far belong to the current recipient
+    settle_rewards(deps.storage, &env.block, &mut timelock_info)?;
+
+    match new_recipient {
+        Some(new_recipient) => {
+            // Collections with a forced recipient only allow their NFTs to go back to the
+            // depositor
+            ensure!(
+                get_lock_policy(deps.storage, &cw721_contract)?
+                    .forced_recipient
+                    .is_none(),
+                ContractError::Unauthorized {}
+            );
+            let new_recipient_addr = new_recipient.address.get_raw_address(&deps.as_ref())?;
+            ADOContract::default().is_permissioned(
+                deps.storage,
+                env.clone(),
+                RECEIVE_LOCK_ACTION,
+                new_recipient_addr.clone(),
+            )?;
+            let previous_recipient = change_recipient(
+                deps.storage,
+                &env.block,
+                &mut timelock_info,
+                new_recipient_addr,
+                new_recipient.msg,
+            )?;
+            timelocks().save(deps.storage, lock_id, &timelock_info)?;
+
+            Ok(Response::new()
+                .add_event(beneficiary_event(
+                    &cw721_contract,
+                    &token_id,
+                    &previous_recipient,
+                    &timelock_info.recipient,
+                ))
+                .add_attribute("method", "revoke_lock")
+                .add_attribute("token_id", token_id)
+                .add_attribute("previous_recipient", previous_recipient)
+                .add_attribute("recipient", timelock_info.recipient))
+        }
+        None => {
+            let transfer_msg =
+                generate_transfer_msg(&cw721_contract, &token_id, &timelock_info.depositor)?;
+            let depositor = timelock_info.depositor.clone();
+            close_lock(
+                deps.storage,
+                &env.block,
+                &cw721_contract,
+                &token_id,
+                &mut timelock_info,
+                LockStatus::Revoked,
+                &depositor,
+            )?;
+
+            Ok(Response::new()
+                .add_message(transfer_msg)
+                .add_attribute("method", "revoke_lock")
+                .add_attribute("token_id", token_id)
+                .add_attribute("recipient", depositor))
+        }
+    }
+}
+
+fn execute_extend_lock(
+    ctx: ExecuteContext,
+    cw721_contract: AndrAddr,
+    token_id: String,
+    additional_duration: MillisecondsDuration,
+) -> Result<Response<Empty>, ContractError> {
+    let ExecuteContext {
+        deps, info, env, ..
+    } = ctx;
+
+    let cw721_contract = cw721_contract.get_raw_address(&deps.as_ref())?;
+    let lock_id = (&cw721_contract, token_id.as_str());
+    let mut timelock_info = timelocks()
+        .load(deps.storage, lock_id)
+        .map_err(|_| ContractError::NFTNotFound {})?;
+
+    // Either side can extend, the lock policy's maximum duration measured from the lock's
+    // start keeps the depositor from holding the NFT back indefinitely
+    ensure!(
+        info.sender == timelock_info.recipient || info.sender == timelock_info.depositor,
+        ContractError::Unauthorized {}
+    );
+    ensure!(
+        !timelock_info.unlock_condition.is_met(&env.block),
+        ContractError::Expired {}
+    );
+    // The expiration is part of the swap terms agreed with the counterparty, set by the bundle
+    // or schedule, renewed by the depositor's heartbeats, or part of what the receipt was
+    // traded for
+    ensure!(
+        !timelock_info.is_escrow()
+            && !timelock_info.is_grouped()
+            && timelock_info.heartbeat_interval.is_none()
+            && timelock_info.receipt.is_none(),
+        ContractError::UnsupportedOperation {}
+    );
+
+    // Only the time component can be extended, height conditions are left untouched
+    let previous_unlock_time = timelock_info
+        .unlock_condition
+        .time()
+        .ok_or(ContractError::ExpirationNotSpecified {})?;
+    let unlock_time = previous_unlock_time.seconds() + additional_duration.seconds();
+    let policy = get_lock_policy(deps.storage, &cw721_contract)?;
+    // The whole lock, not just what is left of it, has to fit within the maximum duration
+    validate_max_lock_duration(
+        &policy,
+        unlock_time.saturating_sub(timelock_info.locked_at.seconds()),
+    )?;
+
+    timelock_info.unlock_condition = timelock_info
+        .unlock_condition
+        .with_time(Milliseconds::from_seconds(unlock_time))
+        .ok_or(ContractError::ExpirationNotSpecified {})?;
+    let voting_power = lock_voting_power(&env, &timelock_info);
+    set_voting_power(deps.storage, &env.block, &mut timelock_info, voting_power)?;
+    timelocks().save(deps.storage, lock_id, &timelock_info)?;
+
+    Ok(Response::new()
+        .add_attribute("method", "extend_lock")
+        .add_attribute("token_id", token_id)
+        .add_attribute(
+            "previous_unlock_time",
+            previous_unlock_time.seconds().to_string(),
+        )
+        .add_attribute("unlock_time", unlock_time.to_string()))
+}
+
+fn execute_heartbeat(
+    ctx: ExecuteContext,
+    cw721_contract: AndrAddr,
+    token_id: String,
+) -> Result<Response<Empty>, ContractError> {
+    let ExecuteContext {
+        deps, info, env, ..
+    } = ctx;
+
+    let cw721_contract = cw721_contract.get_raw_address(&deps.as_ref())?;
+    let lock_id = (&cw721_contract, token_id.as_str());
+    let mut timelock_info = timelocks()
+        .load(deps.storage, lock_id)
+        .map_err(|_| ContractError::NFTNotFound {})?;
+
+    let interval = timelock_info
+        .heartbeat_interval
+        .ok_or(ContractError::UnsupportedOperation {})?;
+    ensure!(
+        info.sender == timelock_info.depositor,
+        ContractError::Unauthorized {}
+    );
+    // Once check-ins stopped the recipient can claim, so a late heartbeat cannot take that away
+    ensure!(
+        !timelock_info.unlock_condition.is_met(&env.block),
+        ContractError::Expired {}
+    );
+
+    let unlock_time = Milliseconds::from_seconds(env.block.time.seconds() + interval.seconds());
+    timelock_info.unlock_condition = UnlockCondition::Time(unlock_time);
+    timelocks().save(deps.storage, lock_id, &timelock_info)?;
+
+    Ok(Response::new()
+        .add_attribute("method", "heartbeat")
+        .add_attribute("token_id", token_id)
+        .add_attribute("unlock_time", unlock_time.seconds().to_string()))
+}
+
+fn execute_transfer_beneficiary(
+    ctx: ExecuteContext,
+    cw721_contract: AndrAddr,
+    token_id: String,
+    new_recipient: Recipient,
+) -> Result<Response<Empty>, ContractError> {
+    let ExecuteContext {
+        deps, info, env, ..
+    } = ctx;
+
+    let cw721_contract = cw721_contract.get_raw_address(&deps.as_ref())?;
+    let lock_id = (&cw721_contract, token_id.as_str());
+    let mut timelock_info = timelocks()
+        .load(deps.storage, lock_id)
+        .map_err(|_| ContractError::NFTNotFound {})?;
+
+    ensure!(
+        info.sender == timelock_info.recipient,
+        ContractError::Unauthorized {}
+    );
+    ensure!(!timelock_info.frozen, ContractError::Paused {});
+    // The claim right belongs to the receipt, which is transferred on the receipt contract, the
+    // parties of a hash lock or swap are part of its terms, and bundles and schedules have a
+    // single recipient
+    ensure!(
+        timelock_info.receipt.is_none()
+            && !timelock_info.is_escrow()
+            && !timelock_info.is_grouped(),
+        ContractError::UnsupportedOperation {}
+    );
+    let new_recipient_addr = new_recipient.address.get_raw_address(&deps.as_ref())?;
+    ADOContract::default().is_permissioned(
+        deps.storage,
+        env.clone(),
+        RECEIVE_LOCK_ACTION,
+        new_recipient_addr.clone(),
+    )?;
+    settle_rewards(deps.storage, &env.block, &mut timelock_info)?;
+
+    let previous_recipient = change_recipient(
+        deps.storage,
+        &env.block,
+        &mut timelock_info,
+        new_recipient_addr,
+        new_recipient.msg,
+    )?;
+    timelocks().save(deps.storage, lock_id, &timelock_info)?;
+
+    Ok(Response::new()
+        .add_event(beneficiary_event(
+            &cw721_contract,
+            &token_id,
+            &previous_recipient,
+            &timelock_info.recipient,
+        ))
+        .add_attribute("method", "transfer_beneficiary")
+        .add_attribute("token_id", token_id)
+        .add_attribute("previous_recipient", previous_recipient)
+        .add_attribute("recipient", timelock_info.recipient))
+}
+
+/// Points a lock at a new recipient, moving its voting power along, and returns the previous
+/// recipient. Rewards must be settled beforehand.
+fn change_recipient(
+    storage: &mut dyn Storage,
+    block: &BlockInfo,
+    timelock_info: &mut TimelockInfo,
+    new_recipient: Addr,
+    new_recipient_msg: Option<Binary>,
+) -> Result<Addr, ContractError> {
+    let voting_power = timelock_info.voting_power;
+    set_voting_power(storage, block, timelock_info, Uint128::zero())?;
+    let previous_recipient = std::mem::replace(&mut timelock_info.recipient, new_recipient);
+    timelock_info.recipient_msg = new_recipient_msg;
+    set_voting_power(storage, block, timelock_info, voting_power)?;
+
+    Ok(previous_recipient)
+}
+
+/// Voting power is the remaining lock time when the lock is created or extended. Like rewards,
+/// it only goes to locks that hold the NFT until their unlock time: receipt locks (whose claim
+/// can be sold), revocable and heartbeat locks (which the depositor can take back) and locks
+/// that can unlock at a block height before their time carry none.
+fn lock_voting_power(env: &Env, timelock_info: &TimelockInfo) -> Uint128 {
+    if timelock_info.receipt.is_some()
+        || timelock_info.revocable
+        || timelock_info.heartbeat_interval.is_some()
+    {
+        return Uint128::zero();
+    }
+    match timelock_info.unlock_condition {
+        UnlockCondition::Time(time) | UnlockCondition::TimeAndHeight { time, .. } => {
+            Uint128::from(time.seconds().saturating_sub(env.block.time.seconds()))
+        }
+        UnlockCondition::Height(_) | UnlockCondition::TimeOrHeight { .. } => Uint128::zero(),
+    }
+}
+
+/// Sets the voting power a lock gives its recipient, updating the recipient's and the total
+/// voting power snapshots at the current height.
+fn set_voting_power(
+    storage: &mut dyn Storage,
+    block: &BlockInfo,
+    timelock_info: &mut TimelockInfo,
+    voting_power: Uint128,
+) -> Result<(), ContractError> {
+    let previous = timelock_info.voting_power;
+    if previous == voting_power {
+        return Ok(());
+    }
+
+    VOTING_POWER.update(
+        storage,
+        &timelock_info.recipient,
+        block.height,
+        |power| -> StdResult<_> { Ok(power.unwrap_or_default() + voting_power - previous) },
+    )?;
+    TOTAL_VOTING_POWER.update(storage, block.height, |total| -> StdResult<_> {
+        Ok(total.unwrap_or_default() + voting_power - previous)
+    })?;
+    timelock_info.voting_power = voting_power;
+    Ok(())
+}
+
+/// Emitted whenever the recipient of a lock changes so indexers can follow the claim.
+fn beneficiary_event(
+    cw721_contract: &Addr,
+    token_id: &str,
+    previous_recipient: &Addr,
+    new_recipient: &Addr,
+) -> Event {
+    Event::new("beneficiary_transferred")
+        .add_attribute("cw721_contract", cw721_contract)
+        .add_attribute("token_id", token_id)
+        .add_attribute("previous_recipient", previous_recipient)
+        .add_attribute("new_recipient", new_recipient)
+}
+
+fn execute_update_lock_policy(
+    ctx: ExecuteContext,
+    cw721_contract: Option<AndrAddr>,
+    policy: Option<LockPolicy>,
+) -> Result<Response<Empty>, ContractError> {
+    let ExecuteContext { deps, info, .. } = ctx;
+
+    ensure!(
+        ADOContract::default().is_contract_owner(deps.storage, info.sender.as_str())?,
+        ContractError::Unauthorized {}
+    );
+
+    if let Some(policy) = &policy {
+        ensure!(
+            policy.min_duration <= policy.max_duration,
+            ContractError::InvalidTimestamp {
+                msg: "min_duration cannot exceed max_duration".to_string(),
+            }
+        );
+        ensure!(
+            policy.height_limits.min_blocks <= policy.height_limits.max_blocks,
+            ContractError::InvalidTimestamp {
+                msg: "min_blocks cannot exceed max_blocks".to_string(),
+            }
+        );
+        if let Some(forced_recipient) = &policy.forced_recipient {
+            forced_recipient.validate(&deps.as_ref())?;
+        }
+        if let Some(early_unlock) = &policy.early_unlock {
+            early_unlock.treasury.validate(&deps.as_ref())?;
+        }
+    }
+
+    match cw721_contract {
+        Some(cw721_contract) => {
+            let cw721_contract = cw721_contract.get_raw_address(&deps.as_ref())?;
+            match policy {
+                Some(policy) => LOCK_POLICIES.save(deps.storage, &cw721_contract, &policy)?,
+                None => LOCK_POLICIES.remove(deps.storage, &cw721_contract),
+            }
+            Ok(Response::new()
+                .add_attribute("method", "update_lock_policy")
+                .add_attribute("cw721_contract", cw721_contract))
+        }
+        None => {
+            CONFIG.save(
+                deps.storage,
+                &Config {
+                    default_lock_policy: policy.unwrap_or_else(default_lock_policy),
+                },
+            )?;
+            Ok(Response::new()
+                .add_attribute("method", "update_lock_policy")
+                .add_attribute("cw721_contract", "default"))
+        }
+    }
+}
+
+fn execute_add_authorized_token_address(
+    ctx: ExecuteContext,
+    address: AndrAddr,
+) -> Result<Response, ContractError> {
+    let ExecuteContext { deps, info, .. } = ctx;
+    let contract = ADOContract::default();
+
+    ensure!(
+        contract.is_owner_or_operator(deps.storage, info.sender.as_str())?,
+        ContractError::Unauthorized {}
+    );
+
+    let addr = address.get_raw_address(&deps.as_ref())?;
+    contract.permission_action(SEND_NFT_ACTION, deps.storage)?;
+    ADOContract::set_permission(
+        deps.storage,
+        SEND_NFT_ACTION,
+        addr.clone(),
+        Permission::Whitelisted(None),
+    )?;
+
+    Ok(Response::new()
+        .add_attribute("method", "add_authorized_token_address")
+        .add_attribute("address", addr))
+}
+
+/// Removing the last authorized address keeps `SEND_NFT_ACTION` permissioned, so no collection
+/// can deposit until a new one is added.
+fn execute_remove_authorized_token_address(
+    ctx: ExecuteContext,
+    address: AndrAddr,
+) -> Result<Response, ContractError> {
+    let ExecuteContext { deps, info, .. } = ctx;
+
+    ensure!(
+        ADOContract::default().is_owner_or_operator(deps.storage, info.sender.as_str())?,
+        ContractError::Unauthorized {}
+    );
+
+    let addr = address.get_raw_address(&deps.as_ref())?;
+    ensure!(
+        ADOContract::get_permission(deps.storage, SEND_NFT_ACTION, addr.clone())?.is_some(),
+        ContractError::InvalidAddress {}
+    );
+    ADOContract::remove_permission(deps.storage, SEND_NFT_ACTION, addr.clone())?;
+
+    Ok(Response::new()
+        .add_attribute("method", "remove_authorized_token_address")
+        .add_attribute("address", addr))
+}
+
+fn default_lock_policy() -> LockPolicy {
+    LockPolicy {
+        min_duration: Milliseconds::from_seconds(ONE_DAY),
+        max_duration: Milliseconds::from_seconds(ONE_YEAR),
+        allow_revocable: true,
+        forced_recipient: None,
+        early_unlock: None,
+        height_limits: HeightLimits::default(),
+    }
+}
+
+/// The unlock time `duration` from now. Durations too long to be represented are rejected
+/// like any other duration above the policy's maximum.
+fn unlock_time_after(
+    env: &Env,
+    duration: MillisecondsDuration,
+) -> Result<MillisecondsExpiration, ContractError> {
+    env.block
+        .time
+        .seconds()
+        .checked_add(duration.seconds())
+        .and_then(|seconds| seconds.checked_mul(1000))
+        .map(Milliseconds)
+        .ok_or(ContractError::LockTimeTooLong {})
+}
+
+/// Applies the policy's duration limits to the time component of a new lock and its height
+/// limits to any block height.
+fn validate_unlock_condition(
+    env: &Env,
+    policy: &LockPolicy,
+    unlock_condition: &UnlockCondition,
+) -> Result<(), ContractError> {
+    if let Some(unlock_time) = unlock_condition.time() {
+        let current_time = env.block.time.seconds();
+        ensure!(
+            unlock_time.seconds() >= current_time + policy.min_duration.seconds(),
+            ContractError::LockTimeTooShort {}
+        );
+        validate_max_lock_duration(policy, unlock_time.seconds() - current_time)?;
+    }
+    if let Some(unlock_height) = unlock_condition.height() {
+        ensure!(
+            unlock_height > env.block.height,
+            ContractError::ExpirationInPast {}
+        );
+        let blocks = unlock_height - env.block.height;
+        ensure!(
+            blocks >= policy.height_limits.min_blocks,
+            ContractError::LockTimeTooShort {}
+        );
+        ensure!(
+            blocks <= policy.height_limits.max_blocks,
+            ContractError::LockTimeTooLong {}
+        );
+    }
+    Ok(())
+}
+
+/// Resolves the arbiters of a new lock. Members listed more than once only count once.
+fn validate_arbiters(deps: &Deps, arbiters: Arbiters) -> Result<ArbiterSet, ContractError> {
+    let mut members = arbiters
+        .members
+        .iter()
+        .map(|member| member.get_raw_address(deps))
+        .collect::<Result<Vec<Addr>, ContractError>>()?;
+    members.sort();
+    members.dedup();
+    ensure!(
+        arbiters.threshold > 0 && arbiters.threshold as usize <= members.len(),
+        ContractError::InvalidWeight {}
+    );
+
+    Ok(ArbiterSet {
+        members,
+        threshold: arbiters.threshold,
+    })
+}
+
+/// Escrows send the NFT to a counterparty rather than a recipient, so they are not available
+/// for collections with a forced recipient, and the minimum lock duration does not apply.
+fn validate_escrow_expiration(
+    env: &Env,
+    policy: &LockPolicy,
+    expiration: MillisecondsExpiration,
+) -> Result<(), ContractError> {
+    ensure!(
+        policy.forced_recipient.is_none(),
+        ContractError::Unauthorized {}
+    );
+    let current_time = env.block.time.seconds();
+    ensure!(
+        expiration.seconds() > current_time,
+        ContractError::ExpirationInPast {}
+    );
+    validate_max_lock_duration(policy, expiration.seconds() - current_time)
+}
+
+/// Ensures a lock never ends more than the policy's maximum duration from now.
+fn validate_max_lock_duration(
+    policy: &LockPolicy,
+    duration_seconds: u64,
+) -> Result<(), ContractError> {
+    ensure!(
+        duration_seconds <= policy.max_duration.seconds(),
+        ContractError::LockTimeTooLong {}
+    );
+    Ok(())
+}
+
+/// Sends the NFT with the recipient's message so contract recipients receive their
+/// `ReceiveNft` hook, or transfers it directly if there is no message.
+fn generate_claim_msg(
+    cw721_contract: &Addr,
+    token_id: &str,
+    timelock_info: &TimelockInfo,
+) -> Result<CosmosMsg, ContractError> {
+    generate_delivery_msg(
+        cw721_contract,
+        token_id,
+        &timelock_info.recipient,
+        timelock_info.recipient_msg.as_ref(),
+    )
+}
+
+/// Sends the NFT with `SendNft` if the recipient gave a message, otherwise transfers it.
+fn generate_delivery_msg(
+    cw721_contract: &Addr,
+    token_id: &str,
+    recipient: &Addr,
+    recipient_msg: Option<&Binary>,
+) -> Result<CosmosMsg, ContractError> {
+    match recipient_msg {
+        Some(msg) => Ok(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: cw721_contract.to_string(),
+            msg: encode_binary(&Cw721ExecuteMsg::SendNft {
+                contract: AndrAddr::from_string(recipient.to_string()),
+                token_id: token_id.to_string(),
+                msg: msg.clone(),
+            })?,
+            funds: vec![],
+        })),
+        None => generate_transfer_msg(cw721_contract, token_id, recipient),
+    }
+}
+
+fn generate_transfer_msg(
+    cw721_contract: &Addr,
+    token_id: &str,
+    recipient: &Addr,
+) -> Result<CosmosMsg, ContractError> {
+    Ok(CosmosMsg::Wasm(WasmMsg::Execute {
+        contract_addr: cw721_contract.to_string(),
+        msg: encode_binary(&Cw721ExecuteMsg::TransferNft {
+            recipient: AndrAddr::from_string(recipient.to_string()),
+            token_id: token_id.to_string(),
+        })?,
+        funds: vec![],
+    }))
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn sudo(deps: DepsMut, env: Env, msg: SudoMsg) -> Result<Response, ContractError> {
+    match msg {
+        SudoMsg::ReleaseDue { limit } => sudo_release_due(deps, env, limit),
+    }
+}
+
+/// Unlike `ReleaseExpired` no bounty is paid, as there is no caller to reward. `limit` bounds
+/// the locks looked at in total: releases come first and voting power expires with the rest.
+fn sudo_release_due(
+    deps: DepsMut,
+    env: Env,
+    limit: Option<u32>,
+) -> Result<Response, ContractError> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let (resp, released, scanned) = release_expired(deps.storage, &env.block, limit)?;
+    let power_expired = expire_voting_power(deps.storage, &env.block, limit - scanned)?;
+
+    Ok(resp
+        .add_attribute("method", "release_due")
+        .add_attribute("released", released.to_string())
+        .add_attribute("power_expired", power_expired.to_string()))
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> Result<Binary, ContractError> {
+    match msg {
+        QueryMsg::UnlockTime {
+            cw721_contract,
+            token_id,
+        } => encode_binary(&query_unlock_time(deps, cw721_contract, token_id)?),
+        QueryMsg::NftDetails {
+            cw721_contract,
+            token_id,
+        } => encode_binary(&query_nft_details(deps, env, cw721_contract, token_id)?),
+        QueryMsg::LockHistory {
+            cw721_contract,
+            token_id,
+        } => encode_binary(&query_lock_history(deps, env, cw721_contract, token_id)?),
+        QueryMsg::IsLocked {
+            cw721_contract,
+            token_id,
+        } => encode_binary(&query_is_locked(deps, env, cw721_contract, token_id)?),
+        QueryMsg::AllLocks { start_after, limit } => {
+            encode_binary(&query_all_locks(deps, env, start_after, limit)?)
+        }
+        QueryMsg::LocksByRecipient {
+            recipient,
+            start_after,
+            limit,
+        } => encode_binary(&query_locks_by_recipient(
+            deps,
+            env,
+            recipient,
+            start_after,
+            limit,
+        )?),
+        QueryMsg::LocksByCollection {
+            cw721_contract,
+            start_after,
+            limit,
+        } => encode_binary(&query_locks_by_collection(
+            deps,
+            env,
+            cw721_contract,
+            start_after,
+            limit,
+        )?),
+        QueryMsg::LocksUnlockingBetween {
+            start,
+            end,
+            start_after,
+            limit,
+        } => encode_binary(&query_locks_unlocking_between(
+            deps,
+            env,
+            start,
+            end,
+            start_after,
+            limit,
+        )?),
+        QueryMsg::LockPolicy { cw721_contract } => {
+            encode_binary(&query_lock_policy(deps, cw721_contract)?)
+        }
+        QueryMsg::KeeperBounty {} => encode_binary(&query_keeper_bounty(deps)?),
+        QueryMsg::Rate { action } => encode_binary(&RateResponse {
+            rate: RATES.may_load(deps.storage, &action.to_string())?,
+        }),
+        QueryMsg::SimulateFee { action } => encode_binary(&query_simulate_fee(deps, action)?),
+        QueryMsg::FeeBalance { address } => encode_binary(&query_fee_balance(deps, address)?),
+        QueryMsg::RewardConfig { cw721_contract } => encode_binary(&RewardConfigResponse {
+            config: REWARD_CONFIGS
+                .may_load(deps.storage, &cw721_contract.get_raw_address(&deps)?)?,
+        }),
+        QueryMsg::RewardPools {} => encode_binary(&query_reward_pools(deps)?),
+        QueryMsg::PendingRewards {
+            recipient,
+            start_after,
+            limit,
+        } => encode_binary(&query_pending_rewards(
+            deps,
+            env,
+            recipient,
+            start_after,
+            limit,
+        )?),
+        QueryMsg::VotingPower { address, height } => {
+            encode_binary(&query_voting_power(deps, env, address, height)?)
+        }
+        QueryMsg::ArbiterVotes { lock_id } => encode_binary(&ArbiterVotesResponse {
+            votes: ARBITER_VOTES
+                .may_load(deps.storage, lock_id)?
+                .unwrap_or_default(),
+        }),
+        QueryMsg::SwapOffer { offer_id } => encode_binary(&query_swap_offer(deps, offer_id)?),
+        QueryMsg::Schedule { schedule_id } => {
+            encode_binary(&query_schedule(deps, env, schedule_id)?)
+        }
+        QueryMsg::Bundle { bundle_id } => encode_binary(&BundleResponse {
+            bundle_id,
+            bundle: BUNDLES
+                .may_load(deps.storage, bundle_id)?
+                .ok_or(ContractError::NFTNotFound {})?,
+        }),
+        QueryMsg::TotalVotingPower { height } => {
+            encode_binary(&query_total_voting_power(deps, env, height)?)
+        }
+        QueryMsg::ReceiptContract {} => encode_binary(&ReceiptContractResponse {
+            address: RECEIPT_CONTRACT.may_load(deps.storage)?,
+        }),
+        QueryMsg::AuthorizedTokenAddresses { start_after, limit } => {
+            encode_binary(&query_authorized_token_addresses(deps, start_after, limit)?)
+        }
+        _ => ADOContract::default().query(deps, env, msg),
+    }
+}
+
+fn query_unlock_time(
+    deps: Deps,
+    cw721_contract: AndrAddr,
+    token_id: String,
+) -> Result<UnlockTimeResponse, ContractError> {
+    let lock_id = (&cw721_contract.get_raw_address(&deps)?, token_id.as_str());
+    let timelock = timelocks().load(deps.storage, lock_id)?;
+
+    Ok(UnlockTimeResponse {
+        unlock_time: timelock.unlock_time_seconds(),
+    })
+}
+
+fn query_nft_details(
+    deps: Deps,
+    env: Env,
+    cw721_contract: AndrAddr,
+    token_id: String,
+) -> Result<NftDetailsResponse, ContractError> {
+    let cw721_contract = cw721_contract.get_raw_address(&deps)?;
+    let timelock = timelocks().load(deps.storage, (&cw721_contract, token_id.as_str()))?;
+    let early_unlock_price =
+        early_unlock_price(deps.storage, &env.block, &cw721_contract, &timelock)?
+            .map(|(_, price)| price);
+
+    let claim_deadline = timelock.claim_deadline();
+    Ok(NftDetailsResponse {
+        lock_id: timelock.lock_id,
+        unlock_time: timelock.unlock_time_seconds(),
+        is_locked: !timelock.unlock_condition.is_met(&env.block),
+        unlock_condition: timelock.unlock_condition,
+        recipient: timelock.recipient,
+        depositor: timelock.depositor,
+        revocable: timelock.revocable,
+        receipt: timelock.receipt,
+        hash_lock: timelock.hash_lock,
+        swap_offer: timelock.swap_offer,
+        arbiters: timelock.arbiters,
+        frozen: timelock.frozen,
+        bundle: timelock.bundle,
+        schedule: timelock.schedule,
+        claim_deadline,
+        fallback: timelock.fallback,
+        heartbeat_interval: timelock.heartbeat_interval,
+        early_unlock_price,
+    })
+}
+
+fn query_lock_history(
+    deps: Deps,
+    env: Env,
+    cw721_contract: AndrAddr,
+    token_id: String,
+) -> Result<LockHistoryResponse, ContractError> {
+    let cw721_contract = cw721_contract.get_raw_address(&deps)?;
+    let mut locks = LOCK_HISTORY
+        .prefix((&cw721_contract, token_id.as_str()))
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| item.map(|(_, record)| record))
+        .collect::<StdResult<Vec<LockRecord>>>()?;
+
+    if let Some(timelock_info) =
+        timelocks().may_load(deps.storage, (&cw721_contract, token_id.as_str()))?
+    {
+        let status = get_lock_status(deps, &env, &cw721_contract, &token_id, &timelock_info);
+        locks.push(LockRecord {
+            lock_id: timelock_info.lock_id,
+            unlock_condition: timelock_info.unlock_condition,
+            depositor: timelock_info.depositor,
+            recipient: timelock_info.recipient,
+            status,
+            claimer: None,
+            claimed_at: None,
+        });
+    }
+
+    Ok(LockHistoryResponse { locks })
+}
+
+fn query_is_locked(
+    deps: Deps,
+    env: Env,
+    cw721_contract: AndrAddr,
+    token_id: String,
+) -> Result<IsLockedResponse, ContractError> {
+    let cw721_contract = cw721_contract.get_raw_address(&deps)?;
+    let timelock = timelocks().load(deps.storage, (&cw721_contract, token_id.as_str()))?;
+    let is_locked = !timelock.unlock_condition.is_met(&env.block);
+    let early_unlock_price =
+        early_unlock_price(deps.storage, &env.block, &cw721_contract, &timelock)?
+            .map(|(_, price)| price);
+
+    Ok(IsLockedResponse {
+        is_locked,
+        unlock_condition: timelock.unlock_condition,
+        early_unlock_price,
+    })
+}
+
+fn query_lock_policy(
+    deps: Deps,
+    cw721_contract: AndrAddr,
+) -> Result<LockPolicyResponse, ContractError> {
+    let cw721_contract = cw721_contract.get_raw_address(&deps)?;
+    let is_override = LOCK_POLICIES.has(deps.storage, &cw721_contract);
+    let policy = get_lock_policy(deps.storage, &cw721_contract)?;
+
+    Ok(LockPolicyResponse {
+        policy,
+        is_override,
+    })
+}
+
+fn query_reward_pools(deps: Deps) -> Result<RewardPoolsResponse, ContractError> {
+    let pools = REWARD_POOLS
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| item.map(|(_, (asset, amount))| RewardAmount { asset, amount }))
+        .collect::<StdResult<Vec<_>>>()?;
+
+    Ok(RewardPoolsResponse { pools })
+}
+
+/// Owed rewards plus everything the recipient's locks accrued since they were last settled.
+/// Owed rewards are included in the first page only, so the pages add up to the total.
+fn query_pending_rewards(
+    deps: Deps,
+    env: Env,
+    recipient: AndrAddr,
+    start_after: Option<(String, String)>,
+    limit: Option<u32>,
+) -> Result<PendingRewardsResponse, ContractError> {
+    let recipient = recipient.get_raw_address(&deps)?;
+    let first_page = start_after.is_none();
+
+    let mut pending: BTreeMap<String, RewardAmount> = BTreeMap::new();
+    let mut add = |asset: Asset, amount: Uint128| {
+        pending
+            .entry(asset.key())
+            .or_insert_with(|| RewardAmount {
+                asset,
+                amount: Uint128::zero(),
+            })
+            .amount += amount;
+    };
+
+    if first_page {
+        for item in
+            OWED_REWARDS
+                .prefix(&recipient)
+                .range(deps.storage, None, None, Order::Ascending)
+        {
+            let (_, (asset, amount)) = item?;
+            add(asset, amount);
+        }
+    }
+    for (_, timelock_info) in recipient_locks(deps, &recipient, start_after, limit)? {
+        if let Some((asset, amount, _)) = timelock_info.unsettled_rewards(env.block.time.seconds())
+        {
+            add(asset, amount);
+        }
+    }
+
+    Ok(PendingRewardsResponse {
+        rewards: pending
+            .into_values()
+            .filter(|reward| !reward.amount.is_zero())
+            .collect(),
+    })
+}
+
+fn query_voting_power(
+    deps: Deps,
+    env: Env,
+    address: AndrAddr,
+    height: Option<u64>,
+) -> Result<VotingPowerResponse, ContractError> {
+    let address = address.get_raw_address(&deps)?;
+    let power = match height {
+        Some(height) => VOTING_POWER.may_load_at_height(deps.storage, &address, height)?,
+        None => VOTING_POWER.may_load(deps.storage, &address)?,
+    };
+    let height = height.unwrap_or(env.block.height);
+
+    Ok(VotingPowerResponse {
+        power: power.unwrap_or_default(),
+        height,
+    })
+}
+
+fn query_total_voting_power(
+    deps: Deps,
+    env: Env,
+    height: Option<u64>,
+) -> Result<VotingPowerResponse, ContractError> {
+    let power = match height {
+        Some(height) => TOTAL_VOTING_POWER.may_load_at_height(deps.storage, height)?,
+        None => TOTAL_VOTING_POWER.may_load(deps.storage)?,
+    };
+    let height = height.unwrap_or(env.block.height);
+
+    Ok(VotingPowerResponse {
+        power: power.unwrap_or_default(),
+        height,
+    })
+}
+
+fn query_swap_offer(deps: Deps, offer_id: u64) -> Result<SwapOfferResponse, ContractError> {
+    let offer = SWAP_OFFERS
+        .may_load(deps.storage, offer_id)?
+        .ok_or(ContractError::NFTNotFound {})?;
+    let timelock_info = timelocks().load(
+        deps.storage,
+        (
+            &offer.offered.cw721_contract,
+            offer.offered.token_id.as_str(),
+        ),
+    )?;
+
+    Ok(SwapOfferResponse {
+        offer_id,
+        offer,
+        deadline: timelock_info
+            .unlock_condition
+            .time()
+            .ok_or(ContractError::ExpirationNotSpecified {})?,
+    })
+}
+
+fn query_simulate_fee(
+    deps: Deps,
+    action: RateAction,
+) -> Result<SimulateFeeResponse, ContractError> {
+    Ok(match RATES.may_load(deps.storage, &action.to_string())? {
+        Some(rate) => SimulateFeeResponse {
+            payouts: rate.payouts(),
+            fee: Some(AssetAmount {
+                asset: rate.asset,
+                amount: rate.amount,
+            }),
+        },
+        None => SimulateFeeResponse {
+            fee: None,
+            payouts: vec![],
+        },
+    })
+}
+
+fn query_fee_balance(deps: Deps, address: AndrAddr) -> Result<FeeBalanceResponse, ContractError> {
+    let address = address.get_raw_address(&deps)?;
+    let balances = FEE_BALANCES
+        .prefix(&address)
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| item.map(|(_, (asset, amount))| AssetAmount { asset, amount }))
+        .collect::<StdResult<Vec<_>>>()?;
+    Ok(FeeBalanceResponse { balances })
+}
+
+fn query_schedule(
+    deps: Deps,
+    env: Env,
+    schedule_id: u64,
+) -> Result<ScheduleResponse, ContractError> {
+    let schedule = SCHEDULES
+        .may_load(deps.storage, schedule_id)?
+        .ok_or(ContractError::NFTNotFound {})?;
+    let next_tranche = schedule.unlocked_tranches(&env.block);
+    let next_unlock = (u64::from(next_tranche) * u64::from(schedule.per_period)
+        < u64::from(schedule.deposited))
+    .then(|| schedule.tranche_time(next_tranche));
+    let claimable = schedule
+        .unlocked(&env.block)
+        .saturating_sub(schedule.released);
+
+    Ok(ScheduleResponse {
+        schedule_id,
+        schedule,
+        next_unlock,
+        claimable,
+    })
+}
+
+fn query_keeper_bounty(deps: Deps) -> Result<KeeperBountyResponse, ContractError> {
+    let pool = BOUNTY_POOL
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| item.map(|(denom, amount)| coin(amount.u128(), denom)))
+        .collect::<StdResult<Vec<Coin>>>()?;
+
+    Ok(KeeperBountyResponse {
+        bounty: KEEPER_BOUNTY.may_load(deps.storage)?,
+        pool,
+    })
+}
+
+/// Reads the whitelisted `SEND_NFT_ACTION` actors straight from the permission store so the
+/// list can never drift from the addresses that are actually allowed to deposit.
+fn query_authorized_token_addresses(
+    deps: Deps,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> Result<AuthorizedTokenAddressesResponse, ContractError> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(|addr| Bound::exclusive(format!("{SEND_NFT_ACTION}{addr}")));
+
+    let addresses = permissions()
+        .idx
+        .action
+        .prefix(SEND_NFT_ACTION.to_string())
+        .range(deps.storage, start, None, Order::Ascending)
+        .filter(|item| {
+            !matches!(
+                item,
+                Ok((_, info)) if !matches!(info.permission, Permission::Whitelisted(_))
+            )
+        })
+        .take(limit)
+        .map(|item| item.map(|(_, info)| Addr::unchecked(info.actor)))
+        .collect::<StdResult<Vec<Addr>>>()?;
+
+    Ok(AuthorizedTokenAddressesResponse { addresses })
+}
+
+fn query_all_locks(
+    deps: Deps,
+    env: Env,
+    start_after: Option<(String, String)>,
+    limit: Option<u32>,
+) -> Result<LocksResponse, ContractError> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start_after = match start_after {
+        Some((cw721_contract, token_id)) => {
+            Some((deps.api.addr_validate(&cw721_contract)?, token_id))
+        }
+        None => None,
+    };
+    let start = start_after
+        .as_ref()
+        .map(|(cw721_contract, token_id)| Bound::exclusive((cw721_contract, token_id.as_str())));
+
+    let locks = timelocks()
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .collect::<StdResult<Vec<_>>>()?;
+
+    to_locks_response(deps, &env, locks)
+}
+
+fn query_locks_by_recipient(
+    deps: Deps,
+    env: Env,
+    recipient: AndrAddr,
+    start_after: Option<(String, String)>,
+    limit: Option<u32>,
+) -> Result<LocksResponse, ContractError> {
+    let recipient = recipient.get_raw_address(&deps)?;
+    let locks = recipient_locks(deps, &recipient, start_after, limit)?;
+
+    to_locks_response(deps, &env, locks)
+}
+
+fn query_locks_by_collection(
+    deps: Deps,
+    env: Env,
+    cw721_contract: AndrAddr,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> Result<LocksResponse, ContractError> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let cw721_contract = cw721_contract.get_raw_address(&deps)?;
+    let start = start_after.map(|token_id| Bound::exclusive((cw721_contract.clone(), token_id)));
+
+    let locks = timelocks()
+        .idx
+        .cw721_contract
+        .prefix(cw721_contract)
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .collect::<StdResult<Vec<_>>>()?;
+
+    to_locks_response(deps, &env, locks)
+}
+
+fn query_locks_unlocking_between(
+    deps: Deps,
+    env: Env,
+    start: MillisecondsExpiration,
+    end: MillisecondsExpiration,
+    start_after: Option<(MillisecondsExpiration, String, String)>,
+    limit: Option<u32>,
+) -> Result<LocksResponse, ContractError> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+
+    // The cursor carries the unlock time itself, so it stays valid if the lock is claimed
+    let min = match start_after {
+        Some((unlock_time, cw721_contract, token_id)) if unlock_time >= start => {
+            let cw721_contract = deps.api.addr_validate(&cw721_contract)?;
+            Bound::exclusive((unlock_time.milliseconds(), (cw721_contract, token_id)))
+        }
+        _ => Bound::inclusive((start.milliseconds(), (Addr::unchecked(""), String::new()))),
+    };
+    let max = Bound::exclusive((end.milliseconds(), (Addr::unchecked(""), String::new())));
+
+    let locks = timelocks()
+        .idx
+        .unlock_time
+        .range(deps.storage, Some(min), Some(max), Order::Ascending)
+        .take(limit)
+        .collect::<StdResult<Vec<_>>>()?;
+
+    to_locks_response(deps, &env, locks)
+}
+
+fn to_locks_response(
+    deps: Deps,
+    env: &Env,
+    locks: Vec<KeyedLock>,
+) -> Result<LocksResponse, ContractError> {
+    let locks = locks
+        .into_iter()
+        .map(|((cw721_contract, token_id), timelock_info)| {
+            let status = get_lock_status(deps, env, &cw721_contract, &token_id, &timelock_info);
+            LockInfoResponse {
+                lock_id: timelock_info.lock_id,
+                cw721_contract,
+                token_id,
+                unlock_time: timelock_info.unlock_time_seconds(),
+                unlock_condition: timelock_info.unlock_condition,
+                recipient: timelock_info.recipient,
+                depositor: timelock_info.depositor,
+                status,
+            }
+        })
+        .collect();
+
+    Ok(LocksResponse { locks })
+}
+
+/// Reports `Unknown` when the collection no longer shows this contract as the token owner,
+/// otherwise evaluates the unlock condition against the current block.
+fn get_lock_status(
+    deps: Deps,
+    env: &Env,
+    cw721_contract: &Addr,
+    token_id: &str,
+    timelock_info: &TimelockInfo,
+) -> LockStatus {
+    let owner_res: StdResult<OwnerOfResponse> = deps.querier.query_wasm_smart(
+        cw721_contract,
+        &Cw721QueryMsg::OwnerOf {
+            token_id: token_id.to_string(),
+            include_expired: None,
+        },
+    );
+    match owner_res {
+        Ok(owner_res) if owner_res.owner == env.contract.address.as_str() => {
+            if timelock_info.frozen {
+                LockStatus::Frozen
+            } else if timelock_info.unlock_condition.is_met(&env.block) {
+                LockStatus::Claimable
+            } else {
+                LockStatus::Locked
+            }
+        }
+        _ => LockStatus::Unknown,
+    }
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn migrate(deps: DepsMut, env: Env, _msg: MigrateMsg) -> Result<Response, ContractError> {
+    // Contracts instantiated before lock policies existed use the previous fixed limits
+    if CONFIG.may_load(deps.storage)?.is_none() {
+        CONFIG.save(
+            deps.storage,
+            &Config {
+                default_lock_policy: default_lock_policy(),
+            },
+        )?;
+    }
+    let migrated = migrate_legacy_locks(deps.storage, &env, None, MAX_LIMIT as usize)?;
+    let pending = LEGACY_MIGRATION_CURSOR.exists(deps.storage);
+
+    Ok(ADOContract::default()
+        .migrate(deps, CONTRACT_NAME, CONTRACT_VERSION)?
+        .add_attribute("migrated_locks", migrated.to_string())
+        .add_attribute("pending", pending.to_string()))
+}
+
+fn execute_migrate_legacy_locks(
+    ctx: ExecuteContext,
+    limit: Option<u32>,
+) -> Result<Response<Empty>, ContractError> {
+    let ExecuteContext { deps, env, .. } = ctx;
+
+    let start_after = LEGACY_MIGRATION_CURSOR
+        .may_load(deps.storage)?
+        .ok_or(ContractError::UnsupportedOperation {})?;
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let migrated = migrate_legacy_locks(deps.storage, &env, Some(start_after), limit)?;
+
+    Ok(Response::new()
+        .add_attribute("method", "migrate_legacy_locks")
+        .add_attribute("migrated_locks", migrated.to_string())
+        .add_attribute(
+            "pending",
+            LEGACY_MIGRATION_CURSOR.exists(deps.storage).to_string(),
+        ))
+}
+
+/// Looks at up to `limit` entries of the `timelocks` namespace after `start_after` and rewrites
+/// the ones stored before 1.1.0 as plain time locks of their recipient, with a new lock ID and
+/// voting power as if they were locked now, and adds them to the indexes.
+fn migrate_legacy_locks(
+    storage: &mut dyn Storage,
+    env: &Env,
+    start_after: Option<(Addr, String)>,
+    limit: usize,
+) -> Result<usize, ContractError> {
+    let start = start_after
+        .as_ref()
+        .map(|(cw721_contract, token_id)| Bound::exclusive((cw721_contract, token_id.as_str())));
+    // `keys` would parse the values too, which fails for locks written since 1.1.0
+    let keys = LEGACY_TIMELOCKS
+        .keys_raw(storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(<(&Addr, &str)>::from_vec)
+        .collect::<StdResult<Vec<_>>>()?;
+
+    let mut migrated = 0;
+    for (cw721_contract, token_id) in &keys {
+        let legacy_info = match LEGACY_TIMELOCKS.load(storage, (cw721_contract, token_id)) {
+            Ok(legacy_info) => legacy_info,
+            // Locks written since 1.1.0 do not parse as legacy locks
+            Err(StdError::ParseErr { .. }) => continue,
+            Err(err) => return Err(err.into()),
+        };
+        let mut timelock_info = TimelockInfo {
+            lock_id: next_lock_id(storage)?,
+            locked_at: Milliseconds::from_seconds(env.block.time.seconds()),
+            unlock_condition: UnlockCondition::Time(legacy_info.unlock_time),
+            recipient: legacy_info.recipient.clone(),
+            recipient_msg: None,
+            depositor: legacy_info.recipient.clone(),
+            revocable: false,
+            receipt: None,
+            reward: None,
+            voting_power: Uint128::zero(),
+            hash_lock: None,
+            swap_offer: None,
+            arbiters: None,
+            frozen: false,
+            bundle: None,
+            schedule: None,
+            fallback: None,
+            heartbeat_interval: None,
+            claim_fee: None,
+            set_aside: false,
+        };
+        let voting_power = lock_voting_power(env, &timelock_info);
+        set_voting_power(storage, &env.block, &mut timelock_info, voting_power)?;
+        // The legacy entry cannot be read as a `TimelockInfo` and has no index entries to remove
+        timelocks().replace(
+            storage,
+            (cw721_contract, token_id),
+            Some(&timelock_info),
+            None,
+        )?;
+        migrated += 1;
+    }
+
+    // A full page may be followed by more entries
+    match keys.last() {
+        Some(last) if keys.len() == limit => LEGACY_MIGRATION_CURSOR.save(storage, last)?,
+        _ => LEGACY_MIGRATION_CURSOR.remove(storage),
+    }
+    Ok(migrated)
 }
@@ -1,11 +1,13 @@
 use andromeda_std::{
     amp::{AndrAddr, Recipient},
     andr_exec, andr_instantiate, andr_query,
-    common::milliseconds::MillisecondsDuration,
+    common::milliseconds::{Milliseconds, MillisecondsDuration, MillisecondsExpiration},
 };
 use cosmwasm_schema::{cw_serde, QueryResponses};
-use cosmwasm_std::Addr;
+use cosmwasm_std::{Addr, Binary, BlockInfo, Coin, Decimal, HexBinary, Uint128};
+use cw20::Cw20ReceiveMsg;
 use cw721::Cw721ReceiveMsg;
+use std::fmt;
 
 #[andr_instantiate]
 #[cw_serde]
@@ -17,10 +19,305 @@ pub struct InstantiateMsg {
 #[cw_serde]
 pub enum ExecuteMsg {
     ReceiveNft(Cw721ReceiveMsg),
+    /// Receives cw20 tokens, see `Cw20HookMsg`.
+    Receive(Cw20ReceiveMsg),
     ClaimNft {
         cw721_contract: AndrAddr,
         token_id: String,
     },
+    /// Sends a hash locked NFT to its counterparty. Callable by anyone who knows the preimage.
+    ClaimHashLockedNft {
+        cw721_contract: AndrAddr,
+        token_id: String,
+        preimage: Binary,
+    },
+    /// Returns a hash locked NFT to its depositor once the refund time has passed. Depositor only.
+    RefundHashLockedNft {
+        cw721_contract: AndrAddr,
+        token_id: String,
+    },
+    /// Returns the NFT of a swap offer that was not accepted to its maker once the deadline has
+    /// passed. Callable by anyone.
+    ReclaimSwap {
+        offer_id: u64,
+    },
+    /// Releases a lock to its recipient before the unlock time. Only available if the lock
+    /// policy of the collection allows it, to the caller it names, and the penalty has to be
+    /// attached. Anything paid above the penalty is refunded.
+    EarlyUnlock {
+        cw721_contract: AndrAddr,
+        token_id: String,
+    },
+    /// Votes on a decision for a lock with arbiters. The decision is carried out as soon as
+    /// `threshold` arbiters agree on it. Arbiters only.
+    ArbitrateLock {
+        cw721_contract: AndrAddr,
+        token_id: String,
+        decision: ArbiterDecision,
+        reason: String,
+    },
+    /// Opens an empty bundle for `recipient`. NFTs are added with the `AddToBundle` hook
+    /// message and are released together by `ClaimBundle` once `unlock_condition` is met.
+    OpenBundle {
+        recipient: Recipient,
+        unlock_condition: UnlockCondition,
+    },
+    /// Sends every NFT in an unlocked bundle to its recipient.
+    ClaimBundle {
+        bundle_id: u64,
+    },
+    /// Creates a vesting schedule for `recipient`, starting now. NFTs are added with the
+    /// `AddToSchedule` hook message: the first `per_period` unlock after `cliff`, and each
+    /// following `per_period` one `period` later.
+    CreateSchedule {
+        recipient: Recipient,
+        cliff: MillisecondsDuration,
+        period: MillisecondsDuration,
+        per_period: u32,
+    },
+    /// Sends the NFT of a lock whose claim window has passed to its fallback recipient.
+    /// Callable by anyone.
+    ReleaseToFallback {
+        cw721_contract: AndrAddr,
+        token_id: String,
+    },
+    /// Claims up to 30 locks at once. Locks that cannot be claimed are reported in a
+    /// `claim_failed` event rather than failing the whole message.
+    ClaimNfts {
+        items: Vec<(AndrAddr, String)>,
+    },
+    /// Looks at up to `limit` locks held for `recipient` after `start_after`, in the order of
+    /// `LocksByRecipient`, and claims those that have unlocked.
+    ClaimAllUnlocked {
+        recipient: AndrAddr,
+        start_after: Option<(String, String)>,
+        limit: Option<u32>,
+    },
+    /// Looks at up to `limit` expired locks in unlock time order and releases them to their
+    /// recipients, paying the caller the keeper bounty for each one while the bounty pool lasts.
+    ReleaseExpired {
+        limit: Option<u32>,
+    },
+    /// Carries on the migration of locks stored before 1.1.0, looking at up to `limit` more
+    /// entries. Only available while the migration has entries left to look at.
+    MigrateLegacyLocks {
+        limit: Option<u32>,
+    },
+    /// Sends an NFT the release cranks could not deliver to its recipient with `TransferNft`,
+    /// charging the claim fee. Recipient only.
+    ClaimUndelivered {
+        cw721_contract: AndrAddr,
+        token_id: String,
+    },
+    /// Adds the attached native funds to the keeper bounty pool.
+    FundBountyPool {},
+    /// Sets the fee charged for `action`, or removes it. Owner only.
+    UpdateRate {
+        action: RateAction,
+        rate: Option<Rate>,
+    },
+    /// Adds the attached native funds to the fee balance of `recipient`, or of the sender.
+    DepositFeeBalance {
+        recipient: Option<AndrAddr>,
+    },
+    /// Withdraws `amount`, or all, of the sender's fee balance in `asset`.
+    WithdrawFeeBalance {
+        asset: Asset,
+        amount: Option<Uint128>,
+    },
+    /// Sets the bounty paid per released NFT, or disables it. Owner only.
+    UpdateKeeperBounty {
+        bounty: Option<Coin>,
+    },
+    /// Sets the rewards emitted to recipients of new locks from `cw721_contract`, or stops
+    /// them. Locks keep the rate they were created with. Owner only.
+    UpdateRewardConfig {
+        cw721_contract: AndrAddr,
+        config: Option<RewardConfig>,
+    },
+    /// Adds the attached native funds to the reward pools. Owner only.
+    FundRewardPool {},
+    /// Settles the rewards of up to `limit` of the sender's locks after `start_after`, in the
+    /// order of `LocksByRecipient`, and pays out the sender's accrued rewards, as far as the
+    /// reward pools allow.
+    ClaimRewards {
+        start_after: Option<(String, String)>,
+        limit: Option<u32>,
+    },
+    /// Sets the cw721 contract receipts are minted on for locks that ask for one, or disables
+    /// receipts. This contract must be the minter of the receipt contract. Owner only.
+    UpdateReceiptContract {
+        address: Option<AndrAddr>,
+    },
+    /// Lets the depositor of a revocable lock take the NFT back, or redirect it to
+    /// `new_recipient`, before the unlock time. Heartbeat locks can be revoked until claimed.
+    RevokeLock {
+        cw721_contract: AndrAddr,
+        token_id: String,
+        new_recipient: Option<Recipient>,
+    },
+    /// Pushes back the unlock time of an existing lock. Callable by the recipient or the depositor.
+    ExtendLock {
+        cw721_contract: AndrAddr,
+        token_id: String,
+        additional_duration: MillisecondsDuration,
+    },
+    /// Moves the unlock time of a heartbeat lock to its interval from now. Callable by the
+    /// depositor before the lock expires.
+    Heartbeat {
+        cw721_contract: AndrAddr,
+        token_id: String,
+    },
+    /// Hands the claim on a lock over to `new_recipient`. Callable by the current recipient.
+    TransferBeneficiary {
+        cw721_contract: AndrAddr,
+        token_id: String,
+        new_recipient: Recipient,
+    },
+    /// Sets the lock policy for `cw721_contract`, or the default policy if no collection is
+    /// given. Passing no policy for a collection removes its override. Owner only.
+    UpdateLockPolicy {
+        cw721_contract: Option<AndrAddr>,
+        policy: Option<LockPolicy>,
+    },
+    /// Authorizes a cw721 contract to send NFTs to this contract. Owner or operator only.
+    AddAuthorizedTokenAddress {
+        address: AndrAddr,
+    },
+    /// Revokes a cw721 contract's authorization. Owner or operator only.
+    RemoveAuthorizedTokenAddress {
+        address: AndrAddr,
+    },
+}
+
+#[cw_serde]
+pub struct LockPolicy {
+    pub min_duration: MillisecondsDuration,
+    pub max_duration: MillisecondsDuration,
+    /// Whether depositors may create revocable locks.
+    pub allow_revocable: bool,
+    /// If set, every lock is created for this recipient regardless of the one provided.
+    pub forced_recipient: Option<Recipient>,
+    /// If set, locks can be released before the unlock time for a penalty.
+    #[serde(default)]
+    pub early_unlock: Option<EarlyUnlockPolicy>,
+    #[serde(default)]
+    pub height_limits: HeightLimits,
+}
+
+/// The block counterpart of the duration limits: how many blocks ahead of the current height
+/// the unlock height of a new lock may be.
+#[cw_serde]
+pub struct HeightLimits {
+    pub min_blocks: u64,
+    pub max_blocks: u64,
+}
+
+impl Default for HeightLimits {
+    /// A day and a year of six second blocks, in line with the default durations.
+    fn default() -> Self {
+        HeightLimits {
+            min_blocks: 14_400,
+            max_blocks: 5_256_000,
+        }
+    }
+}
+
+#[cw_serde]
+pub struct EarlyUnlockPolicy {
+    pub caller: EarlyUnlockCaller,
+    /// Receives the penalties.
+    pub treasury: Recipient,
+    pub penalty: PenaltyCurve,
+}
+
+#[cw_serde]
+pub enum EarlyUnlockCaller {
+    Recipient,
+    Depositor,
+}
+
+/// The penalty is `base + per_second * seconds left until the unlock time`, in `denom`.
+#[cw_serde]
+pub struct PenaltyCurve {
+    pub denom: String,
+    pub base: Uint128,
+    pub per_second: Uint128,
+}
+
+impl PenaltyCurve {
+    pub fn penalty(&self, remaining_seconds: u64) -> Coin {
+        let amount = self
+            .per_second
+            .saturating_mul(Uint128::from(remaining_seconds))
+            .saturating_add(self.base);
+        Coin::new(amount.u128(), self.denom.clone())
+    }
+}
+
+#[cw_serde]
+pub struct ClaimWindow {
+    /// How long after the unlock time the recipient has to claim.
+    pub duration: MillisecondsDuration,
+    pub fallback: Recipient,
+}
+
+#[cw_serde]
+pub struct Fallback {
+    pub claim_window: MillisecondsDuration,
+    pub recipient: Addr,
+    pub recipient_msg: Option<Binary>,
+}
+
+#[cw_serde]
+pub struct Arbiters {
+    pub members: Vec<AndrAddr>,
+    /// The number of members that have to agree on a decision.
+    pub threshold: u32,
+}
+
+#[cw_serde]
+pub struct ArbiterSet {
+    pub members: Vec<Addr>,
+    pub threshold: u32,
+}
+
+#[cw_serde]
+pub enum ArbiterDecision {
+    /// Keeps the NFT locked, even past the unlock time, until it is unfrozen.
+    Freeze,
+    Unfreeze,
+    /// Sends the NFT to the recipient right away.
+    Release,
+    /// Sends the NFT back to the depositor.
+    Refund,
+}
+
+impl fmt::Display for ArbiterDecision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArbiterDecision::Freeze => write!(f, "freeze"),
+            ArbiterDecision::Unfreeze => write!(f, "unfreeze"),
+            ArbiterDecision::Release => write!(f, "release"),
+            ArbiterDecision::Refund => write!(f, "refund"),
+        }
+    }
+}
+
+#[cw_serde]
+pub struct ArbiterVote {
+    pub arbiter: Addr,
+    pub decision: ArbiterDecision,
+    pub reason: String,
+    /// When the vote was cast, in seconds.
+    pub time: u64,
+    /// Whether this vote reached the threshold and the decision was carried out.
+    pub executed: bool,
+}
+
+#[cw_serde]
+pub struct ArbiterVotesResponse {
+    pub votes: Vec<ArbiterVote>,
 }
 
 #[cw_serde]
@@ -28,9 +325,263 @@ pub enum Cw721HookMsg {
     TimelockNft {
         lock_duration: MillisecondsDuration,
         recipient: Recipient,
+        #[serde(default)]
+        revocable: bool,
+        /// Arbiters that can freeze the lock, release it early or refund the depositor.
+        #[serde(default)]
+        arbiters: Option<Arbiters>,
+        /// If set, the recipient has to claim within the window, or the NFT can be sent to the
+        /// fallback recipient.
+        #[serde(default)]
+        claim_window: Option<ClaimWindow>,
+        /// Mints a receipt NFT carrying the claim on the lock to the recipient. Requires a
+        /// receipt contract to be set.
+        #[serde(default)]
+        receipt: bool,
+    },
+    /// Locks the NFT until the given condition is met instead of for a relative duration.
+    TimelockNftUntil {
+        unlock_condition: UnlockCondition,
+        recipient: Recipient,
+        #[serde(default)]
+        revocable: bool,
+        /// Arbiters that can freeze the lock, release it early or refund the depositor.
+        #[serde(default)]
+        arbiters: Option<Arbiters>,
+        /// If set, the recipient has to claim within the window, or the NFT can be sent to the
+        /// fallback recipient.
+        #[serde(default)]
+        claim_window: Option<ClaimWindow>,
+        /// Mints a receipt NFT carrying the claim on the lock to the recipient. Requires a
+        /// receipt contract to be set.
+        #[serde(default)]
+        receipt: bool,
+    },
+    /// Locks the NFT as a dead man's switch: it unlocks `interval` after the depositor's last
+    /// `Heartbeat`, and the depositor can take it back with `RevokeLock` until it is claimed.
+    HeartbeatLockNft {
+        interval: MillisecondsDuration,
+        recipient: Recipient,
+    },
+    /// Sent with a receipt NFT to claim the unlocked NFT it represents. The receipt is burned.
+    RedeemReceipt {},
+    /// Locks the NFT for `counterparty`, who can claim it by revealing the preimage of the
+    /// sha256 `hash` before `refund_after`. After that only the depositor can reclaim it.
+    HashLockNft {
+        hash: HexBinary,
+        counterparty: AndrAddr,
+        refund_after: MillisecondsExpiration,
+    },
+    /// Offers the sent NFT in exchange for `ask_token_id` from `ask_cw721_contract`. If the
+    /// asked NFT is not sent with `AcceptSwap` before `deadline`, the sent NFT can be returned
+    /// to its depositor with `ReclaimSwap`.
+    OpenSwap {
+        ask_cw721_contract: AndrAddr,
+        ask_token_id: String,
+        /// Restricts who can accept the offer.
+        counterparty: Option<AndrAddr>,
+        deadline: MillisecondsExpiration,
+    },
+    /// Sent with the NFT asked for by `offer_id`. Both NFTs are swapped immediately.
+    AcceptSwap { offer_id: u64 },
+    /// Adds the sent NFT to a bundle, under the bundle's recipient and unlock condition. Only
+    /// the owner of the bundle can add to it.
+    AddToBundle { bundle_id: u64 },
+    /// Adds the sent NFT to a vesting schedule, in the next open slot. Only the owner of the
+    /// schedule can add to it.
+    AddToSchedule { schedule_id: u64 },
+}
+
+#[cw_serde]
+pub enum Cw20HookMsg {
+    /// Adds the sent cw20 tokens to the reward pools. Owner only.
+    FundRewardPool {},
+    /// Adds the sent cw20 tokens to the fee balance of `recipient`, or of the sender.
+    DepositFeeBalance { recipient: Option<AndrAddr> },
+}
+
+/// The actions a fee can be charged for.
+#[cw_serde]
+pub enum RateAction {
+    /// Charged to the depositor of each new lock.
+    TimelockNft,
+    /// Charged to the recipient each time a lock is released to them, whether by `ClaimNft`,
+    /// `ClaimNfts`, `ClaimAllUnlocked`, an early unlock, an arbiter release, a receipt or a
+    /// keeper. Each lock is charged the rate in
+    /// effect when it was created.
+    ClaimNft,
+}
+
+impl fmt::Display for RateAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RateAction::TimelockNft => write!(f, "timelock_nft"),
+            RateAction::ClaimNft => write!(f, "claim_nft"),
+        }
+    }
+}
+
+/// A flat fee, split between `recipients` by share. The shares have to add up to one.
+#[cw_serde]
+pub struct Rate {
+    pub asset: Asset,
+    pub amount: Uint128,
+    pub recipients: Vec<RateRecipient>,
+}
+
+impl Rate {
+    /// Splits the fee between the recipients. Rounding leftovers go to the last recipient.
+    pub fn payouts(&self) -> Vec<(Addr, Uint128)> {
+        let mut remaining = self.amount;
+        let mut payouts = vec![];
+        for (i, recipient) in self.recipients.iter().enumerate() {
+            let amount = if i + 1 == self.recipients.len() {
+                remaining
+            } else {
+                self.amount.mul_floor(recipient.share)
+            };
+            remaining -= amount;
+            if !amount.is_zero() {
+                payouts.push((recipient.address.clone(), amount));
+            }
+        }
+        payouts
+    }
+}
+
+#[cw_serde]
+pub struct RateRecipient {
+    pub address: Addr,
+    pub share: Decimal,
+}
+
+/// An asset fees are charged and rewards are paid in.
+#[cw_serde]
+pub enum Asset {
+    Native(String),
+    Cw20(Addr),
+}
+
+impl Asset {
+    /// The key the asset's fee balances, reward pools and owed rewards are stored under.
+    pub fn key(&self) -> String {
+        match self {
+            Asset::Native(denom) => format!("native:{denom}"),
+            Asset::Cw20(address) => format!("cw20:{address}"),
+        }
+    }
+}
+
+#[cw_serde]
+pub struct AssetAmount {
+    pub asset: Asset,
+    pub amount: Uint128,
+}
+
+#[cw_serde]
+pub struct RewardConfig {
+    pub asset: Asset,
+    /// Emitted per second to the recipient of each lock until its unlock time. Locks that can
+    /// unlock at a block height earlier earn nothing.
+    pub rate_per_second: Uint128,
+    /// Boosts the rate of locks committed for at least `min_duration`.
+    pub multiplier: Option<LongLockMultiplier>,
+}
+
+#[cw_serde]
+pub struct LongLockMultiplier {
+    pub min_duration: MillisecondsDuration,
+    pub multiplier: Decimal,
+}
+
+#[cw_serde]
+pub struct RewardAmount {
+    pub asset: Asset,
+    pub amount: Uint128,
+}
+
+/// The receipt NFT minted to the recipient of a lock, which carries the right to claim it.
+#[cw_serde]
+pub struct Receipt {
+    pub cw721_contract: Addr,
+    pub token_id: String,
+}
+
+#[cw_serde]
+pub enum UnlockCondition {
+    /// Unlocks once the block time reaches the given time.
+    Time(MillisecondsExpiration),
+    /// Unlocks once the chain reaches the given block height.
+    Height(u64),
+    /// Unlocks once both the time and the block height have been reached.
+    TimeAndHeight {
+        time: MillisecondsExpiration,
+        height: u64,
+    },
+    /// Unlocks once either the time or the block height has been reached.
+    TimeOrHeight {
+        time: MillisecondsExpiration,
+        height: u64,
     },
 }
 
+impl UnlockCondition {
+    pub fn is_met(&self, block: &BlockInfo) -> bool {
+        match self {
+            UnlockCondition::Time(time) => time.is_expired(block),
+            UnlockCondition::Height(height) => block.height >= *height,
+            UnlockCondition::TimeAndHeight { time, height } => {
+                time.is_expired(block) && block.height >= *height
+            }
+            UnlockCondition::TimeOrHeight { time, height } => {
+                time.is_expired(block) || block.height >= *height
+            }
+        }
+    }
+
+    pub fn time(&self) -> Option<MillisecondsExpiration> {
+        match self {
+            UnlockCondition::Time(time)
+            | UnlockCondition::TimeAndHeight { time, .. }
+            | UnlockCondition::TimeOrHeight { time, .. } => Some(*time),
+            UnlockCondition::Height(_) => None,
+        }
+    }
+
+    pub fn height(&self) -> Option<u64> {
+        match self {
+            UnlockCondition::Height(height)
+            | UnlockCondition::TimeAndHeight { height, .. }
+            | UnlockCondition::TimeOrHeight { height, .. } => Some(*height),
+            UnlockCondition::Time(_) => None,
+        }
+    }
+
+    /// Returns the condition with its time component replaced, leaving any height untouched.
+    pub fn with_time(&self, new_time: MillisecondsExpiration) -> Option<UnlockCondition> {
+        match self {
+            UnlockCondition::Time(_) => Some(UnlockCondition::Time(new_time)),
+            UnlockCondition::TimeAndHeight { height, .. } => Some(UnlockCondition::TimeAndHeight {
+                time: new_time,
+                height: *height,
+            }),
+            UnlockCondition::TimeOrHeight { height, .. } => Some(UnlockCondition::TimeOrHeight {
+                time: new_time,
+                height: *height,
+            }),
+            UnlockCondition::Height(_) => None,
+        }
+    }
+}
+
+/// Messages the chain itself can send, e.g. from a cron or clock module.
+#[cw_serde]
+pub enum SudoMsg {
+    /// Looks at up to `limit` expired locks in unlock time order and releases them to their
+    /// recipients.
+    ReleaseDue { limit: Option<u32> },
+}
+
 #[andr_query]
 #[cw_serde]
 #[derive(QueryResponses)]
@@ -45,25 +596,351 @@ pub enum QueryMsg {
         cw721_contract: AndrAddr,
         token_id: String,
     },
+    /// Returns every lock of an NFT, oldest first, including claimed and revoked ones.
+    #[returns(LockHistoryResponse)]
+    LockHistory {
+        cw721_contract: AndrAddr,
+        token_id: String,
+    },
     #[returns(IsLockedResponse)]
     IsLocked {
         cw721_contract: AndrAddr,
         token_id: String,
     },
+    #[returns(LocksResponse)]
+    AllLocks {
+        start_after: Option<(String, String)>,
+        limit: Option<u32>,
+    },
+    #[returns(LocksResponse)]
+    LocksByRecipient {
+        recipient: AndrAddr,
+        start_after: Option<(String, String)>,
+        limit: Option<u32>,
+    },
+    #[returns(LocksResponse)]
+    LocksByCollection {
+        cw721_contract: AndrAddr,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    /// Lists locks whose unlock time falls within `[start, end)`, ordered by unlock time.
+    /// Locks that only depend on the block height are never included.
+    #[returns(LocksResponse)]
+    LocksUnlockingBetween {
+        start: MillisecondsExpiration,
+        end: MillisecondsExpiration,
+        /// The `(unlock_time, cw721_contract, token_id)` of the last lock from the previous page.
+        start_after: Option<(MillisecondsExpiration, String, String)>,
+        limit: Option<u32>,
+    },
+    /// Returns the policy applied to new locks from the given collection.
+    #[returns(LockPolicyResponse)]
+    LockPolicy { cw721_contract: AndrAddr },
+    #[returns(KeeperBountyResponse)]
+    KeeperBounty {},
+    #[returns(RateResponse)]
+    Rate { action: RateAction },
+    /// Returns the fee `action` costs right now and how it would be split.
+    #[returns(SimulateFeeResponse)]
+    SimulateFee { action: RateAction },
+    /// Returns the prepaid balances `address` pays fees from.
+    #[returns(FeeBalanceResponse)]
+    FeeBalance { address: AndrAddr },
+    #[returns(ReceiptContractResponse)]
+    ReceiptContract {},
+    /// Returns the voting power of `address` at the start of `height`, or its current voting
+    /// power if no height is given.
+    #[returns(VotingPowerResponse)]
+    VotingPower {
+        address: AndrAddr,
+        height: Option<u64>,
+    },
+    /// Returns every arbiter vote cast on a lock, oldest first.
+    #[returns(ArbiterVotesResponse)]
+    ArbiterVotes { lock_id: u64 },
+    /// Returns an open swap offer.
+    #[returns(SwapOfferResponse)]
+    SwapOffer { offer_id: u64 },
+    /// Returns a bundle that has not been claimed yet.
+    #[returns(BundleResponse)]
+    Bundle { bundle_id: u64 },
+    #[returns(ScheduleResponse)]
+    Schedule { schedule_id: u64 },
+    #[returns(VotingPowerResponse)]
+    TotalVotingPower { height: Option<u64> },
+    #[returns(RewardConfigResponse)]
+    RewardConfig { cw721_contract: AndrAddr },
+    /// Returns the funds left in each reward pool.
+    #[returns(RewardPoolsResponse)]
+    RewardPools {},
+    /// Returns the rewards `recipient` could claim with `ClaimRewards` at the current block, for
+    /// up to `limit` of its locks after `start_after`. Rewards already owed are included in the
+    /// first page.
+    #[returns(PendingRewardsResponse)]
+    PendingRewards {
+        recipient: AndrAddr,
+        start_after: Option<(String, String)>,
+        limit: Option<u32>,
+    },
+    /// Lists the cw721 contracts authorized to send NFTs to this contract.
+    #[returns(AuthorizedTokenAddressesResponse)]
+    AuthorizedTokenAddresses {
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
 }
 
 #[cw_serde]
 pub struct UnlockTimeResponse {
+    /// The unlock time in seconds, or 0 if the lock only depends on the block height.
     pub unlock_time: u64,
 }
 
 #[cw_serde]
 pub struct NftDetailsResponse {
+    pub lock_id: u64,
+    /// The unlock time in seconds, or 0 if the lock only depends on the block height.
     pub unlock_time: u64,
+    pub unlock_condition: UnlockCondition,
+    pub is_locked: bool,
     pub recipient: Addr,
+    pub depositor: Addr,
+    pub revocable: bool,
+    pub receipt: Option<Receipt>,
+    pub hash_lock: Option<HexBinary>,
+    pub swap_offer: Option<u64>,
+    pub arbiters: Option<ArbiterSet>,
+    pub frozen: bool,
+    pub bundle: Option<u64>,
+    pub schedule: Option<u64>,
+    pub fallback: Option<Fallback>,
+    /// The time after which the NFT can only go to the fallback recipient.
+    pub claim_deadline: Option<MillisecondsExpiration>,
+    pub heartbeat_interval: Option<MillisecondsDuration>,
+    /// The penalty for unlocking now, if the lock can be unlocked early.
+    pub early_unlock_price: Option<Coin>,
 }
 
 #[cw_serde]
 pub struct IsLockedResponse {
     pub is_locked: bool,
+    pub unlock_condition: UnlockCondition,
+    pub early_unlock_price: Option<Coin>,
+}
+
+#[cw_serde]
+pub enum LockStatus {
+    /// The unlock condition has not been met yet.
+    Locked,
+    /// The unlock condition has been met and the NFT can be claimed.
+    Claimable,
+    /// The contract could not confirm it still holds the NFT.
+    Unknown,
+    /// The NFT was sent to its recipient.
+    Claimed,
+    /// Arbiters froze the lock.
+    Frozen,
+    /// The recipient did not claim within the claim window and the NFT was sent to the
+    /// fallback recipient.
+    Forfeited,
+    /// The NFT went back to the depositor, who revoked the lock or refunded an expired hash lock.
+    Revoked,
+    /// A release crank closed the lock but could not deliver the NFT, which waits for its
+    /// recipient to take it with `ClaimUndelivered`.
+    Undelivered,
+}
+
+#[cw_serde]
+pub struct SwapNft {
+    pub cw721_contract: Addr,
+    pub token_id: String,
+}
+
+#[cw_serde]
+pub struct SwapOffer {
+    pub maker: Addr,
+    pub offered: SwapNft,
+    pub ask: SwapNft,
+    pub counterparty: Option<Addr>,
+}
+
+#[cw_serde]
+pub struct SwapOfferResponse {
+    pub offer_id: u64,
+    pub offer: SwapOffer,
+    pub deadline: MillisecondsExpiration,
+}
+
+#[cw_serde]
+pub struct BundleItem {
+    pub cw721_contract: Addr,
+    pub token_id: String,
+}
+
+#[cw_serde]
+pub struct Bundle {
+    /// The opener of the bundle, the only one who can add NFTs to it.
+    pub owner: Addr,
+    pub recipient: Addr,
+    pub recipient_msg: Option<Binary>,
+    pub unlock_condition: UnlockCondition,
+    pub items: Vec<BundleItem>,
+}
+
+#[cw_serde]
+pub struct BundleResponse {
+    pub bundle_id: u64,
+    pub bundle: Bundle,
+}
+
+#[cw_serde]
+pub struct Schedule {
+    /// The creator of the schedule, the only one who can add NFTs to it.
+    pub owner: Addr,
+    pub recipient: Addr,
+    pub recipient_msg: Option<Binary>,
+    pub start: MillisecondsExpiration,
+    pub cliff: MillisecondsDuration,
+    pub period: MillisecondsDuration,
+    pub per_period: u32,
+    /// The number of NFTs added so far. The n-th NFT added unlocks with tranche
+    /// `n / per_period`.
+    pub deposited: u32,
+    /// The number of NFTs sent to the recipient so far.
+    pub released: u32,
+}
+
+impl Schedule {
+    /// When the given tranche unlocks, counting from zero.
+    pub fn tranche_time(&self, tranche: u32) -> MillisecondsExpiration {
+        self.start
+            .plus_milliseconds(self.cliff)
+            .plus_milliseconds(Milliseconds(
+                self.period.milliseconds().saturating_mul(tranche.into()),
+            ))
+    }
+
+    /// The number of tranches that have unlocked at the given block.
+    pub fn unlocked_tranches(&self, block: &BlockInfo) -> u32 {
+        let now = Milliseconds::from_nanos(block.time.nanos()).milliseconds();
+        let first = self.tranche_time(0).milliseconds();
+        if now < first {
+            return 0;
+        }
+        let tranches = (now - first) / self.period.milliseconds() + 1;
+        tranches.try_into().unwrap_or(u32::MAX)
+    }
+
+    /// The number of NFTs that have unlocked at the given block, claimed or not.
+    pub fn unlocked(&self, block: &BlockInfo) -> u32 {
+        self.unlocked_tranches(block)
+            .saturating_mul(self.per_period)
+            .min(self.deposited)
+    }
+}
+
+#[cw_serde]
+pub struct ScheduleResponse {
+    pub schedule_id: u64,
+    pub schedule: Schedule,
+    /// When the next NFTs unlock, if any are still locked.
+    pub next_unlock: Option<MillisecondsExpiration>,
+    /// The number of unlocked NFTs that have not been claimed yet.
+    pub claimable: u32,
+}
+
+#[cw_serde]
+pub struct LockRecord {
+    pub lock_id: u64,
+    pub unlock_condition: UnlockCondition,
+    pub depositor: Addr,
+    pub recipient: Addr,
+    pub status: LockStatus,
+    /// The address the NFT was sent to when the lock was closed.
+    pub claimer: Option<Addr>,
+    /// When the lock was closed, in seconds.
+    pub claimed_at: Option<u64>,
+}
+
+#[cw_serde]
+pub struct LockHistoryResponse {
+    pub locks: Vec<LockRecord>,
+}
+
+#[cw_serde]
+pub struct LockInfoResponse {
+    pub lock_id: u64,
+    pub cw721_contract: Addr,
+    pub token_id: String,
+    pub unlock_time: u64,
+    pub unlock_condition: UnlockCondition,
+    pub recipient: Addr,
+    pub depositor: Addr,
+    pub status: LockStatus,
+}
+
+#[cw_serde]
+pub struct LocksResponse {
+    pub locks: Vec<LockInfoResponse>,
+}
+
+#[cw_serde]
+pub struct LockPolicyResponse {
+    pub policy: LockPolicy,
+    /// Whether the policy is an override for this collection rather than the default.
+    pub is_override: bool,
+}
+
+#[cw_serde]
+pub struct AuthorizedTokenAddressesResponse {
+    pub addresses: Vec<Addr>,
+}
+
+#[cw_serde]
+pub struct KeeperBountyResponse {
+    pub bounty: Option<Coin>,
+    pub pool: Vec<Coin>,
+}
+
+#[cw_serde]
+pub struct RateResponse {
+    pub rate: Option<Rate>,
+}
+
+#[cw_serde]
+pub struct SimulateFeeResponse {
+    pub fee: Option<AssetAmount>,
+    pub payouts: Vec<(Addr, Uint128)>,
+}
+
+#[cw_serde]
+pub struct FeeBalanceResponse {
+    pub balances: Vec<AssetAmount>,
+}
+
+#[cw_serde]
+pub struct ReceiptContractResponse {
+    pub address: Option<Addr>,
+}
+
+#[cw_serde]
+pub struct RewardConfigResponse {
+    pub config: Option<RewardConfig>,
+}
+
+#[cw_serde]
+pub struct RewardPoolsResponse {
+    pub pools: Vec<RewardAmount>,
+}
+
+#[cw_serde]
+pub struct PendingRewardsResponse {
+    pub rewards: Vec<RewardAmount>,
+}
+
+#[cw_serde]
+pub struct VotingPowerResponse {
+    pub power: Uint128,
+    pub height: u64,
 }
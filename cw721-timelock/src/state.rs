@@ -1,12 +1,309 @@
-use andromeda_std::common::milliseconds::MillisecondsExpiration;
+use crate::msg::{
+    ArbiterSet, ArbiterVote, Asset, Bundle, Fallback, LockPolicy, LockRecord, Rate, Receipt,
+    RewardConfig, Schedule, SwapOffer, UnlockCondition,
+};
+use andromeda_std::common::milliseconds::{
+    Milliseconds, MillisecondsDuration, MillisecondsExpiration,
+};
 use cosmwasm_schema::cw_serde;
-use cosmwasm_std::Addr;
-use cw_storage_plus::Map;
+use cosmwasm_std::{Addr, Binary, BlockInfo, Coin, HexBinary, StdResult, Storage, Uint128};
+use cw_storage_plus::{
+    Index, IndexList, IndexedMap, Item, KeyDeserialize, Map, MultiIndex, SnapshotItem, SnapshotMap,
+    Strategy,
+};
 
-pub const TIMELOCKS: Map<(&Addr, &str), TimelockInfo> = Map::new("timelocks");
+pub const CONFIG: Item<Config> = Item::new("config");
+pub const LOCK_POLICIES: Map<&Addr, LockPolicy> = Map::new("lock_policies");
+/// Paid to the caller of `ReleaseExpired` for every NFT released.
+pub const KEEPER_BOUNTY: Item<Coin> = Item::new("keeper_bounty");
+/// Native funds available for keeper bounties, by denom.
+pub const BOUNTY_POOL: Map<&str, Uint128> = Map::new("bounty_pool");
+/// The cw721 contract receipts are minted on for new locks, if receipts are enabled.
+pub const RECEIPT_CONTRACT: Item<Addr> = Item::new("receipt_contract");
+/// Used to generate receipt token IDs, as burned IDs cannot be minted again.
+pub const RECEIPT_COUNT: Item<u64> = Item::new("receipt_count");
+/// Maps `(receipt_contract, receipt_token_id)` to the `(cw721_contract, token_id)` of its lock.
+pub const RECEIPTS: Map<(&Addr, &str), (Addr, String)> = Map::new("receipts");
+pub const REWARD_CONFIGS: Map<&Addr, RewardConfig> = Map::new("reward_configs");
+/// Funds available for rewards, keyed by `Asset::key`.
+pub const REWARD_POOLS: Map<&str, (Asset, Uint128)> = Map::new("reward_pools");
+/// The last lock ID handed out. IDs are never reused.
+pub const LOCK_COUNT: Item<u64> = Item::new("lock_count");
+/// Claimed and revoked locks, keyed by `(cw721_contract, token_id, lock_id)`.
+pub const LOCK_HISTORY: Map<(&Addr, &str, u64), LockRecord> = Map::new("lock_history");
+/// Arbiter votes on each lock, keyed by lock ID.
+pub const ARBITER_VOTES: Map<u64, Vec<ArbiterVote>> = Map::new("arbiter_votes");
+/// The last swap offer ID handed out.
+pub const SWAP_COUNT: Item<u64> = Item::new("swap_count");
+pub const SWAP_OFFERS: Map<u64, SwapOffer> = Map::new("swap_offers");
+/// The last bundle ID handed out.
+pub const BUNDLE_COUNT: Item<u64> = Item::new("bundle_count");
+/// Bundles that have not been claimed yet.
+pub const BUNDLES: Map<u64, Bundle> = Map::new("bundles");
+/// The last vesting schedule ID handed out.
+pub const SCHEDULE_COUNT: Item<u64> = Item::new("schedule_count");
+pub const SCHEDULES: Map<u64, Schedule> = Map::new("schedules");
+/// Voting power of each recipient, snapshotted at every height it changes.
+pub const VOTING_POWER: SnapshotMap<&Addr, Uint128> = SnapshotMap::new(
+    "voting_power",
+    "voting_power__checkpoints",
+    "voting_power__changelog",
+    Strategy::EveryBlock,
+);
+pub const TOTAL_VOTING_POWER: SnapshotItem<Uint128> = SnapshotItem::new(
+    "total_voting_power",
+    "total_voting_power__checkpoints",
+    "total_voting_power__changelog",
+    Strategy::EveryBlock,
+);
+/// Rewards settled to a recipient but not paid out yet, keyed by recipient and asset key.
+pub const OWED_REWARDS: Map<(&Addr, &str), (Asset, Uint128)> = Map::new("owed_rewards");
+/// NFTs sent by the release cranks in the current transaction, keyed by lock ID, so the reply to
+/// each delivery can pay out its claim fee or record the failure. Cleared at the start of every
+/// crank.
+pub const CRANK_DELIVERIES: Map<u64, Delivery> = Map::new("crank_deliveries");
+/// NFTs the release cranks could not deliver, keyed by `(cw721_contract, token_id)`, for their
+/// recipient to take with `ClaimUndelivered`.
+pub const UNDELIVERED: Map<(&Addr, &str), Delivery> = Map::new("undelivered");
+/// Fees charged for each `RateAction`, keyed by its display name. The `ClaimNft` rate is copied
+/// into each lock when it is created.
+pub const RATES: Map<&str, Rate> = Map::new("rates");
+/// Prepaid funds fees are taken from, keyed by payer and asset key.
+pub const FEE_BALANCES: Map<(&Addr, &str), (Asset, Uint128)> = Map::new("fee_balances");
+
+/// Locks as stored before 1.1.0, which only kept the unlock time and recipient. They share the
+/// `timelocks` namespace and are rewritten when the contract is migrated.
+pub const LEGACY_TIMELOCKS: Map<(&Addr, &str), LegacyTimelockInfo> = Map::new("timelocks");
+
+/// The last entry looked at by the legacy lock migration, kept while entries are left to look at.
+pub const LEGACY_MIGRATION_CURSOR: Item<(Addr, String)> = Item::new("legacy_migration_cursor");
 
 #[cw_serde]
-pub struct TimelockInfo {
+pub struct LegacyTimelockInfo {
     pub unlock_time: MillisecondsExpiration,
     pub recipient: Addr,
 }
+
+/// An NFT sent out by a release crank. The claim fee taken for it is only paid out once the NFT
+/// has arrived.
+#[cw_serde]
+pub struct Delivery {
+    pub lock_id: u64,
+    pub cw721_contract: Addr,
+    pub token_id: String,
+    pub recipient: Addr,
+    pub claim_fee: Option<Rate>,
+}
+
+#[cw_serde]
+pub struct Config {
+    pub default_lock_policy: LockPolicy,
+}
+
+#[cw_serde]
+pub struct TimelockInfo {
+    pub lock_id: u64,
+    /// When the NFT was locked. `ExtendLock` cannot push the unlock time further than the
+    /// policy's maximum duration from it.
+    pub locked_at: MillisecondsExpiration,
+    pub unlock_condition: UnlockCondition,
+    pub recipient: Addr,
+    /// The recipient's message, delivered with `SendNft` when the NFT is claimed.
+    pub recipient_msg: Option<Binary>,
+    pub depositor: Addr,
+    pub revocable: bool,
+    /// Set if a receipt was minted for the lock, in which case only its holder can claim.
+    pub receipt: Option<Receipt>,
+    pub reward: Option<LockReward>,
+    /// The voting power the lock gives its recipient: the remaining lock time in seconds when
+    /// it was created or last extended.
+    pub voting_power: Uint128,
+    /// Set for hash locks: the sha256 hash of the preimage the recipient must reveal to claim
+    /// before the unlock time. After it, only the depositor can take the NFT back.
+    pub hash_lock: Option<HexBinary>,
+    /// Set while the NFT is offered in a swap. It goes back to the depositor after the deadline.
+    pub swap_offer: Option<u64>,
+    pub arbiters: Option<ArbiterSet>,
+    /// Set by the arbiters to keep the NFT in the contract past the unlock time.
+    pub frozen: bool,
+    /// Set if the NFT is part of a bundle, which is only released as a whole.
+    pub bundle: Option<u64>,
+    /// Set if the NFT vests as part of a schedule.
+    pub schedule: Option<u64>,
+    /// Set if the recipient has to claim within a window, after which the NFT goes to the
+    /// fallback recipient instead.
+    pub fallback: Option<Fallback>,
+    /// Set for heartbeat locks, whose unlock time each `Heartbeat` moves to this long from now.
+    pub heartbeat_interval: Option<MillisecondsDuration>,
+    /// The `ClaimNft` rate when the lock was created, charged when it is released.
+    pub claim_fee: Option<Rate>,
+    /// Set when a release crank reached the lock but its recipient could not cover the claim
+    /// fee. The cranks skip it from then on and the recipient has to claim it.
+    pub set_aside: bool,
+}
+
+/// The reward emission of a lock, fixed when the lock is created.
+#[cw_serde]
+pub struct LockReward {
+    pub asset: Asset,
+    /// The collection's rate with any multiplier applied.
+    pub rate_per_second: Uint128,
+    /// Rewards up to this time (in seconds) have been settled to the recipient.
+    pub accrued_until: u64,
+}
+
+impl TimelockInfo {
+    /// The unlock time in seconds, or 0 if the lock only depends on the block height.
+    pub fn unlock_time_seconds(&self) -> u64 {
+        self.unlock_condition
+            .time()
+            .map_or(0, |unlock_time| unlock_time.seconds())
+    }
+
+    /// Whether the NFT can be sent to its recipient without any further input, as
+    /// `ClaimAllUnlocked` does. Escrows and bundled NFTs have their own messages, and locks
+    /// with a claim window have to be claimed by their recipient.
+    pub fn can_release(&self, block: &BlockInfo) -> bool {
+        self.receipt.is_none()
+            && !self.is_escrow()
+            && self.bundle.is_none()
+            && self.fallback.is_none()
+            && !self.frozen
+            && self.unlock_condition.is_met(block)
+    }
+
+    /// The time from which the release cranks send the NFT to its recipient, or `None` if they
+    /// never do. Every lock released by then is unlocked, so the cranks never have to pass over
+    /// one. Locks that wait for a block height are left to their recipient, as are frozen locks,
+    /// locks that were set aside and those `can_release` always rules out.
+    pub fn release_time(&self) -> Option<MillisecondsExpiration> {
+        if self.receipt.is_some()
+            || self.is_escrow()
+            || self.bundle.is_some()
+            || self.fallback.is_some()
+            || self.frozen
+            || self.set_aside
+        {
+            return None;
+        }
+        match self.unlock_condition {
+            UnlockCondition::Time(time) | UnlockCondition::TimeOrHeight { time, .. } => Some(time),
+            UnlockCondition::Height(_) | UnlockCondition::TimeAndHeight { .. } => None,
+        }
+    }
+
+    /// The end of the claim window, if the lock has one.
+    pub fn claim_deadline(&self) -> Option<MillisecondsExpiration> {
+        let fallback = self.fallback.as_ref()?;
+        let unlock_time = self.unlock_condition.time()?;
+        Some(Milliseconds(
+            unlock_time
+                .milliseconds()
+                .saturating_add(fallback.claim_window.milliseconds()),
+        ))
+    }
+
+    /// Whether the lock holds one side of a hash lock or swap, whose terms cannot change.
+    pub fn is_escrow(&self) -> bool {
+        self.hash_lock.is_some() || self.swap_offer.is_some()
+    }
+
+    /// Whether the lock is part of a bundle or schedule, whose NFTs share a recipient and
+    /// unlock on the group's terms.
+    pub fn is_grouped(&self) -> bool {
+        self.bundle.is_some() || self.schedule.is_some()
+    }
+
+    /// Rewards accrued since they were last settled. Emissions stop at the unlock time; only
+    /// locks that cannot unlock before it earn rewards.
+    pub fn unsettled_rewards(&self, now_seconds: u64) -> Option<(Asset, Uint128, u64)> {
+        let reward = self.reward.as_ref()?;
+        let end = self.unlock_time_seconds().min(now_seconds);
+        let elapsed = end.saturating_sub(reward.accrued_until);
+        Some((
+            reward.asset.clone(),
+            reward
+                .rate_per_second
+                .saturating_mul(Uint128::from(elapsed)),
+            end.max(reward.accrued_until),
+        ))
+    }
+}
+
+pub struct TimelockIndexes<'a> {
+    pub recipient: MultiIndex<'a, Addr, TimelockInfo, (Addr, String)>,
+    pub cw721_contract: MultiIndex<'a, Addr, TimelockInfo, (Addr, String)>,
+    pub unlock_time: MultiIndex<'a, u64, TimelockInfo, (Addr, String)>,
+    pub power_expiry: MultiIndex<'a, u64, TimelockInfo, (Addr, String)>,
+    pub release_time: MultiIndex<'a, u64, TimelockInfo, (Addr, String)>,
+}
+
+impl<'a> IndexList<TimelockInfo> for TimelockIndexes<'a> {
+    fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<TimelockInfo>> + '_> {
+        let v: Vec<&dyn Index<TimelockInfo>> = vec![
+            &self.recipient,
+            &self.cw721_contract,
+            &self.unlock_time,
+            &self.power_expiry,
+            &self.release_time,
+        ];
+        Box::new(v.into_iter())
+    }
+}
+
+/// Locks are keyed by `(cw721_contract, token_id)` and indexed by recipient, collection and
+/// unlock time so they can be listed without knowing the exact key. Locks without a time
+/// component are indexed at `u64::MAX`. `power_expiry` only indexes locks that still carry
+/// voting power by their unlock time and `release_time` only the locks the release cranks can
+/// send out, the others are at `u64::MAX`.
+pub fn timelocks<'a>() -> IndexedMap<'a, (&'a Addr, &'a str), TimelockInfo, TimelockIndexes<'a>> {
+    let indexes = TimelockIndexes {
+        recipient: MultiIndex::new(
+            |_pk, info| info.recipient.clone(),
+            "timelocks",
+            "timelocks__recipient",
+        ),
+        cw721_contract: MultiIndex::new(
+            |pk, _info| {
+                let (cw721_contract, _) = <(Addr, String)>::from_slice(pk).unwrap();
+                cw721_contract
+            },
+            "timelocks",
+            "timelocks__cw721_contract",
+        ),
+        unlock_time: MultiIndex::new(
+            |_pk, info| {
+                info.unlock_condition
+                    .time()
+                    .map_or(u64::MAX, |time| time.milliseconds())
+            },
+            "timelocks",
+            "timelocks__unlock_time",
+        ),
+        power_expiry: MultiIndex::new(
+            |_pk, info| match info.unlock_condition.time() {
+                Some(time) if !info.voting_power.is_zero() => time.milliseconds(),
+                _ => u64::MAX,
+            },
+            "timelocks",
+            "timelocks__power_expiry",
+        ),
+        release_time: MultiIndex::new(
+            |_pk, info| {
+                info.release_time()
+                    .map_or(u64::MAX, |time| time.milliseconds())
+            },
+            "timelocks",
+            "timelocks__release_time",
+        ),
+    };
+    IndexedMap::new("timelocks", indexes)
+}
+
+/// Returns the override for the given collection if one is set, otherwise the default policy.
+pub fn get_lock_policy(storage: &dyn Storage, cw721_contract: &Addr) -> StdResult<LockPolicy> {
+    match LOCK_POLICIES.may_load(storage, cw721_contract)? {
+        Some(policy) => Ok(policy),
+        None => Ok(CONFIG.load(storage)?.default_lock_policy),
+    }
+}
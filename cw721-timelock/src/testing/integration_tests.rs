@@ -1,12 +1,12 @@
-use crate::contract::{execute, instantiate, query};
+use crate::contract::{execute, instantiate, query, reply, sudo};
 use crate::msg::{
-    Cw721HookMsg::TimelockNft, ExecuteMsg, InstantiateMsg, NftDetailsResponse, QueryMsg,
-    UnlockTimeResponse,
+    Cw721HookMsg::TimelockNft, ExecuteMsg, InstantiateMsg, LockInfoResponse, LockStatus,
+    LocksResponse, NftDetailsResponse, QueryMsg, SudoMsg, UnlockCondition, UnlockTimeResponse,
 };
 use andromeda_std::ado_base::ownership::ContractOwnerResponse;
 use andromeda_std::testing::mock_querier::MOCK_KERNEL_CONTRACT;
 use anyhow::Error;
-use cosmwasm_std::{testing::mock_env, Addr, Empty};
+use cosmwasm_std::{testing::mock_env, to_json_binary, Addr, Empty};
 use cw721::OwnerOfResponse;
 use cw721_base::entry::{
     execute as cw721_execute, instantiate as cw721_instantiate, query as cw721_query,
@@ -16,7 +16,10 @@ use cw_multi_test::{App, Contract, ContractWrapper, Executor};
 
 use andromeda_std::{
     amp::{AndrAddr, Recipient},
-    common::{encode_binary, milliseconds::MillisecondsDuration},
+    common::{
+        encode_binary,
+        milliseconds::{Milliseconds, MillisecondsDuration},
+    },
 };
 
 const ONE_DAY: u64 = 24 * 60 * 60;
@@ -26,7 +29,9 @@ fn mock_app() -> App {
 }
 
 pub fn contract_cw721_timelock() -> Box<dyn Contract<Empty>> {
-    let contract = ContractWrapper::new(execute, instantiate, query);
+    let contract = ContractWrapper::new(execute, instantiate, query)
+        .with_sudo(sudo)
+        .with_reply(reply);
     Box::new(contract)
 }
 
@@ -135,6 +140,10 @@ fn cw721_timelock_test() {
     let hook_msg = TimelockNft {
         lock_duration: MillisecondsDuration::from_seconds(3 * ONE_DAY),
         recipient: Recipient::new(recipient.to_string(), None),
+        revocable: false,
+        arbiters: None,
+        claim_window: None,
+        receipt: false,
     };
 
     let send_cw721_msg: cw721_base::msg::ExecuteMsg<Empty, Empty> =
@@ -188,6 +197,33 @@ fn cw721_timelock_test() {
     // Assert that the recipient is as set
     assert_eq!(recipient, nft_details_res.recipient);
 
+    let query_locks_msg = QueryMsg::LocksByRecipient {
+        recipient: AndrAddr::from_string(recipient.to_string()),
+        start_after: None,
+        limit: None,
+    };
+    let locks_res: LocksResponse = router
+        .wrap()
+        .query_wasm_smart(&cw721_timelock_addr, &query_locks_msg)
+        .unwrap();
+
+    // Assert that the lock is listed for the recipient and still locked
+    assert_eq!(
+        locks_res.locks,
+        vec![LockInfoResponse {
+            lock_id: 1,
+            cw721_contract: cw721_addr.clone(),
+            token_id: "token1".to_string(),
+            unlock_time: expected_unlock_time,
+            unlock_condition: UnlockCondition::Time(Milliseconds::from_seconds(
+                expected_unlock_time
+            )),
+            recipient: recipient.clone(),
+            depositor: origin_cw721_owner.clone(),
+            status: LockStatus::Locked,
+        }]
+    );
+
     let execute_claim_msg = ExecuteMsg::ClaimNft {
         cw721_contract: AndrAddr::from_string(cw721_addr.to_string()),
         token_id: "token1".to_string(),
@@ -231,3 +267,244 @@ fn cw721_timelock_test() {
     // Assert that the owner of token1 is now the recipient
     assert_eq!(recipient, owner_res.owner);
 }
+
+#[test]
+fn cw721_timelock_sudo_release_test() {
+    let mut router: App = mock_app();
+
+    let owner = Addr::unchecked("owner");
+    let minter = Addr::unchecked("minter");
+    let depositor = Addr::unchecked("depositor");
+    let recipient = Addr::unchecked("recipient");
+
+    let cw721_timelock_id: u64 = router.store_code(contract_cw721_timelock());
+    let cw721_id: u64 = router.store_code(contract_cw721());
+
+    let cw721_timelock_addr: Addr = router
+        .instantiate_contract(
+            cw721_timelock_id,
+            owner.clone(),
+            &InstantiateMsg {
+                owner: None,
+                kernel_address: MOCK_KERNEL_CONTRACT.to_string(),
+                authorized_token_addresses: None,
+            },
+            &[],
+            "CW721 Timelock",
+            None,
+        )
+        .unwrap();
+    let cw721_addr: Addr = router
+        .instantiate_contract(
+            cw721_id,
+            minter.clone(),
+            &cw721_base::msg::InstantiateMsg {
+                name: "Test Timelock CW721".to_string(),
+                symbol: "TL-CW721".to_string(),
+                minter: minter.to_string(),
+            },
+            &[],
+            "CW721",
+            None,
+        )
+        .unwrap();
+
+    for (token_id, lock_days) in [("token1", 1), ("token2", 2), ("token3", 5)] {
+        let mint_msg: cw721_base::msg::ExecuteMsg<Empty, Empty> =
+            cw721_base::msg::ExecuteMsg::Mint {
+                token_id: token_id.to_string(),
+                owner: depositor.to_string(),
+                token_uri: None,
+                extension: Empty::default(),
+            };
+        router
+            .execute_contract(minter.clone(), cw721_addr.clone(), &mint_msg, &[])
+            .unwrap();
+
+        let send_msg: cw721_base::msg::ExecuteMsg<Empty, Empty> =
+            cw721_base::msg::ExecuteMsg::SendNft {
+                contract: cw721_timelock_addr.to_string(),
+                token_id: token_id.to_string(),
+                msg: encode_binary(&TimelockNft {
+                    lock_duration: MillisecondsDuration::from_seconds(lock_days * ONE_DAY),
+                    recipient: Recipient::new(recipient.to_string(), None),
+                    revocable: false,
+                    arbiters: None,
+                    claim_window: None,
+                    receipt: false,
+                })
+                .unwrap(),
+            };
+        router
+            .execute_contract(depositor.clone(), cw721_addr.clone(), &send_msg, &[])
+            .unwrap();
+    }
+
+    router.update_block(|block| {
+        block.time = block.time.plus_seconds(3 * ONE_DAY);
+    });
+
+    // The chain releases the expired locks without any user transaction
+    router
+        .wasm_sudo(
+            cw721_timelock_addr.clone(),
+            &SudoMsg::ReleaseDue { limit: None },
+        )
+        .unwrap();
+
+    for (token_id, expected_owner) in [
+        ("token1", &recipient),
+        ("token2", &recipient),
+        ("token3", &cw721_timelock_addr),
+    ] {
+        let owner_res: OwnerOfResponse = router
+            .wrap()
+            .query_wasm_smart(
+                &cw721_addr,
+                &cw721_base::msg::QueryMsg::<Empty>::OwnerOf {
+                    token_id: token_id.to_string(),
+                    include_expired: None,
+                },
+            )
+            .unwrap();
+        assert_eq!(expected_owner.to_string(), owner_res.owner);
+    }
+
+    let locks_res: LocksResponse = router
+        .wrap()
+        .query_wasm_smart(
+            &cw721_timelock_addr,
+            &QueryMsg::AllLocks {
+                start_after: None,
+                limit: None,
+            },
+        )
+        .unwrap();
+    assert_eq!(locks_res.locks.len(), 1);
+    assert_eq!(locks_res.locks[0].token_id, "token3");
+}
+
+#[test]
+fn cw721_timelock_failed_delivery_test() {
+    let mut router: App = mock_app();
+
+    let owner = Addr::unchecked("owner");
+    let minter = Addr::unchecked("minter");
+    let depositor = Addr::unchecked("depositor");
+    let recipient = Addr::unchecked("recipient");
+
+    let cw721_timelock_id: u64 = router.store_code(contract_cw721_timelock());
+    let cw721_id: u64 = router.store_code(contract_cw721());
+
+    let cw721_timelock_addr: Addr = router
+        .instantiate_contract(
+            cw721_timelock_id,
+            owner,
+            &InstantiateMsg {
+                owner: None,
+                kernel_address: MOCK_KERNEL_CONTRACT.to_string(),
+                authorized_token_addresses: None,
+            },
+            &[],
+            "CW721 Timelock",
+            None,
+        )
+        .unwrap();
+    let cw721_addr: Addr = router
+        .instantiate_contract(
+            cw721_id,
+            minter.clone(),
+            &cw721_base::msg::InstantiateMsg {
+                name: "Test Timelock CW721".to_string(),
+                symbol: "TL-CW721".to_string(),
+                minter: minter.to_string(),
+            },
+            &[],
+            "CW721",
+            None,
+        )
+        .unwrap();
+
+    // token1 is sent with `SendNft`, which fails as the recipient is not a contract
+    for (token_id, recipient_msg) in [
+        ("token1", Some(to_json_binary("hook").unwrap())),
+        ("token2", None),
+    ] {
+        let mint_msg: cw721_base::msg::ExecuteMsg<Empty, Empty> =
+            cw721_base::msg::ExecuteMsg::Mint {
+                token_id: token_id.to_string(),
+                owner: depositor.to_string(),
+                token_uri: None,
+                extension: Empty::default(),
+            };
+        router
+            .execute_contract(minter.clone(), cw721_addr.clone(), &mint_msg, &[])
+            .unwrap();
+
+        let send_msg: cw721_base::msg::ExecuteMsg<Empty, Empty> =
+            cw721_base::msg::ExecuteMsg::SendNft {
+                contract: cw721_timelock_addr.to_string(),
+                token_id: token_id.to_string(),
+                msg: encode_binary(&TimelockNft {
+                    lock_duration: MillisecondsDuration::from_seconds(ONE_DAY),
+                    recipient: Recipient::new(recipient.to_string(), recipient_msg),
+                    revocable: false,
+                    arbiters: None,
+                    claim_window: None,
+                    receipt: false,
+                })
+                .unwrap(),
+            };
+        router
+            .execute_contract(depositor.clone(), cw721_addr.clone(), &send_msg, &[])
+            .unwrap();
+    }
+
+    router.update_block(|block| {
+        block.time = block.time.plus_seconds(ONE_DAY);
+    });
+
+    let res = router
+        .wasm_sudo(
+            cw721_timelock_addr.clone(),
+            &SudoMsg::ReleaseDue { limit: None },
+        )
+        .unwrap();
+    assert!(res
+        .events
+        .iter()
+        .any(|event| event.ty == "wasm-delivery_failed"
+            && event
+                .attributes
+                .iter()
+                .any(|attr| attr.key == "token_id" && attr.value == "token1")));
+
+    let owner_of = |router: &App, token_id: &str| -> String {
+        let owner_res: OwnerOfResponse = router
+            .wrap()
+            .query_wasm_smart(
+                &cw721_addr,
+                &cw721_base::msg::QueryMsg::<Empty>::OwnerOf {
+                    token_id: token_id.to_string(),
+                    include_expired: None,
+                },
+            )
+            .unwrap();
+        owner_res.owner
+    };
+    assert_eq!(owner_of(&router, "token1"), cw721_timelock_addr.to_string());
+    assert_eq!(owner_of(&router, "token2"), recipient.to_string());
+
+    router
+        .execute_contract(
+            recipient.clone(),
+            cw721_timelock_addr.clone(),
+            &ExecuteMsg::ClaimUndelivered {
+                cw721_contract: AndrAddr::from_string(cw721_addr.to_string()),
+                token_id: "token1".to_string(),
+            },
+            &[],
+        )
+        .unwrap();
+    assert_eq!(owner_of(&router, "token1"), recipient.to_string());
+}
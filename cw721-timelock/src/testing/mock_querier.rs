@@ -53,7 +53,6 @@ pub fn mock_dependencies_custom(
 pub struct WasmMockQuerier {
     pub base: MockQuerier,
     pub contract_address: String,
-    pub tokens_left_to_burn: usize,
 }
 
 impl Querier for WasmMockQuerier {
@@ -110,9 +109,7 @@ impl WasmMockQuerier {
                 }
                 _ => panic!("Unsupported Query"),
             },
-            _ => SystemResult::Err(SystemError::UnsupportedRequest {
-                kind: format!("{:?}", request),
-            }),
+            _ => self.base.handle_query(request),
         }
     }
 
@@ -132,7 +129,7 @@ impl WasmMockQuerier {
             Cw721QueryMsg::OwnerOf { token_id, .. } => {
                 let res = if token_id == MOCK_UNCLAIMED_TOKEN {
                     OwnerOfResponse {
-                        owner: mock_env().contract.address.to_string(),
+                        owner: self.contract_address.clone(),
                         approvals: vec![],
                     }
                 } else {
@@ -154,7 +151,6 @@ impl WasmMockQuerier {
         WasmMockQuerier {
             base,
             contract_address: mock_env().contract.address.to_string(),
-            tokens_left_to_burn: 2,
         }
     }
 }
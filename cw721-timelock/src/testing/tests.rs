@@ -1,27 +1,48 @@
 use crate::{
-    contract::{execute, instantiate, query},
+    contract::{execute, instantiate, migrate, query, reply, sudo},
     msg::{
-        Cw721HookMsg, ExecuteMsg, InstantiateMsg, IsLockedResponse, NftDetailsResponse, QueryMsg,
-        UnlockTimeResponse,
+        ArbiterDecision, ArbiterVotesResponse, Arbiters, Asset, AssetAmount,
+        AuthorizedTokenAddressesResponse, Bundle, BundleItem, BundleResponse, ClaimWindow,
+        Cw20HookMsg, Cw721HookMsg, EarlyUnlockCaller, EarlyUnlockPolicy, ExecuteMsg,
+        FeeBalanceResponse, HeightLimits, InstantiateMsg, IsLockedResponse, KeeperBountyResponse,
+        LockHistoryResponse, LockInfoResponse, LockPolicy, LockPolicyResponse, LockRecord,
+        LockStatus, LocksResponse, LongLockMultiplier, NftDetailsResponse, PenaltyCurve,
+        PendingRewardsResponse, QueryMsg, Rate, RateAction, RateRecipient, Receipt, RewardAmount,
+        RewardConfig, RewardConfigResponse, RewardPoolsResponse, ScheduleResponse,
+        SimulateFeeResponse, SudoMsg, SwapNft, SwapOffer, SwapOfferResponse, UnlockCondition,
+        UnlockTimeResponse, VotingPowerResponse,
+    },
+    state::{timelocks, LegacyTimelockInfo, LEGACY_TIMELOCKS, SCHEDULES},
+    testing::mock_querier::{
+        mock_dependencies_custom, MOCK_CW721_CONTRACT, MOCK_TOKEN_OWNER, MOCK_UNCLAIMED_TOKEN,
     },
-    testing::mock_querier::{mock_dependencies_custom, MOCK_CW721_CONTRACT, MOCK_TOKEN_OWNER},
 };
+use andromeda_non_fungible_tokens::cw721::{ExecuteMsg as Cw721ExecuteMsg, TokenExtension};
 use andromeda_std::{
+    ado_base::{
+        permissioning::{Permission, PermissioningMessage},
+        MigrateMsg,
+    },
     amp::{AndrAddr, Recipient},
     common::encode_binary,
-    common::milliseconds::MillisecondsDuration,
+    common::milliseconds::{Milliseconds, MillisecondsDuration},
     error::ContractError,
     testing::mock_querier::MOCK_KERNEL_CONTRACT,
 };
 use cosmwasm_std::{
-    from_json,
+    coin, from_json,
     testing::{mock_env, mock_info},
-    to_json_binary, Addr, Attribute, Querier, QueryRequest, WasmQuery,
+    to_json_binary, Addr, Attribute, BankMsg, Binary, Decimal, Deps, DepsMut, Env, Event,
+    HexBinary, Querier, QueryRequest, Reply, Response, SubMsg, SubMsgResponse, SubMsgResult,
+    Uint128, WasmMsg, WasmQuery,
 };
+use cw20::{Cw20ExecuteMsg, Cw20ReceiveMsg};
 use cw721::{Cw721QueryMsg, Cw721ReceiveMsg, OwnerOfResponse};
+use sha2::{Digest, Sha256};
 
 const ONE_DAY: u64 = 24 * 60 * 60;
 const ONE_YEAR: u64 = 365 * 24 * 60 * 60;
+const ONE_DAY_BLOCKS: u64 = 14_400;
 
 #[test]
 fn test_instantiate() {
@@ -57,6 +78,10 @@ fn test_timelock_cw721() {
         msg: encode_binary(&Cw721HookMsg::TimelockNft {
             lock_duration: MillisecondsDuration::from_seconds(3 * ONE_DAY),
             recipient: Recipient::new("recipient", None),
+            revocable: false,
+            arbiters: None,
+            claim_window: None,
+            receipt: false,
         })
         .unwrap(),
     });
@@ -78,11 +103,19 @@ fn test_timelock_cw721() {
                 key: "token_id".to_string(),
                 value: "token1".to_string()
             },
+            Attribute {
+                key: "depositor".to_string(),
+                value: "cw721_contract".to_string()
+            },
+            Attribute {
+                key: "lock_id".to_string(),
+                value: "1".to_string()
+            },
         ]
     );
     // Verify the timelock has been set
     let query_res: UnlockTimeResponse = from_json(
-        &query(
+        query(
             deps.as_ref(),
             env.clone(),
             QueryMsg::UnlockTime {
@@ -118,6 +151,10 @@ fn test_claim_cw721() {
         msg: encode_binary(&Cw721HookMsg::TimelockNft {
             lock_duration: MillisecondsDuration::from_seconds(3 * ONE_DAY),
             recipient: Recipient::new("recipient", None),
+            revocable: false,
+            arbiters: None,
+            claim_window: None,
+            receipt: false,
         })
         .unwrap(),
     });
@@ -139,6 +176,14 @@ fn test_claim_cw721() {
                 key: "token_id".to_string(),
                 value: "token1".to_string()
             },
+            Attribute {
+                key: "depositor".to_string(),
+                value: "owner".to_string()
+            },
+            Attribute {
+                key: "lock_id".to_string(),
+                value: "1".to_string()
+            },
         ]
     );
 
@@ -185,7 +230,7 @@ fn test_claim_cw721() {
 
     let raw_query_res = deps.querier.raw_query(&owner_query_msg);
 
-    let owner_response: OwnerOfResponse = from_json(&(raw_query_res.unwrap()).unwrap()).unwrap();
+    let owner_response: OwnerOfResponse = from_json((raw_query_res.unwrap()).unwrap()).unwrap();
 
     assert_eq!(owner_response.owner, "owner".to_string());
 }
@@ -209,6 +254,10 @@ fn test_too_short_lock_duration() {
         msg: encode_binary(&Cw721HookMsg::TimelockNft {
             lock_duration: MillisecondsDuration::from_seconds(ONE_DAY / 2),
             recipient: Recipient::new("recipient", None),
+            revocable: false,
+            arbiters: None,
+            claim_window: None,
+            receipt: false,
         })
         .unwrap(),
     });
@@ -237,6 +286,10 @@ fn test_too_long_lock_duration() {
         msg: encode_binary(&Cw721HookMsg::TimelockNft {
             lock_duration: MillisecondsDuration::from_seconds(2 * ONE_YEAR),
             recipient: Recipient::new("recipient", None),
+            revocable: false,
+            arbiters: None,
+            claim_window: None,
+            receipt: false,
         })
         .unwrap(),
     });
@@ -244,6 +297,27 @@ fn test_too_long_lock_duration() {
     let execute_res =
         execute(deps.as_mut(), env.clone(), info.clone(), timelock_cw721_msg).unwrap_err();
     assert_eq!(execute_res, ContractError::LockTimeTooLong {});
+
+    // Durations that do not fit in a timestamp are rejected rather than overflowing
+    let overflow = Milliseconds(u64::MAX);
+    for hook in [
+        Cw721HookMsg::TimelockNft {
+            lock_duration: overflow,
+            recipient: Recipient::new("recipient", None),
+            revocable: false,
+            arbiters: None,
+            claim_window: None,
+            receipt: false,
+        },
+        Cw721HookMsg::HeartbeatLockNft {
+            interval: overflow,
+            recipient: Recipient::new("recipient", None),
+        },
+    ] {
+        let err =
+            send_nft(deps.as_mut(), env.clone(), MOCK_TOKEN_OWNER, "token1", hook).unwrap_err();
+        assert_eq!(err, ContractError::LockTimeTooLong {});
+    }
 }
 
 #[test]
@@ -265,6 +339,10 @@ fn test_locked_nft() {
         msg: encode_binary(&Cw721HookMsg::TimelockNft {
             lock_duration: MillisecondsDuration::from_seconds(3 * ONE_DAY),
             recipient: Recipient::new("recipient", None),
+            revocable: false,
+            arbiters: None,
+            claim_window: None,
+            receipt: false,
         })
         .unwrap(),
     });
@@ -326,6 +404,10 @@ fn test_query_nft_details() {
         msg: encode_binary(&Cw721HookMsg::TimelockNft {
             lock_duration: MillisecondsDuration::from_seconds(3 * ONE_DAY),
             recipient: Recipient::new("recipient", None),
+            revocable: false,
+            arbiters: None,
+            claim_window: None,
+            receipt: false,
         })
         .unwrap(),
     });
@@ -338,10 +420,12 @@ fn test_query_nft_details() {
     };
 
     let res: NftDetailsResponse =
-        from_json(&query(deps.as_ref(), env.clone(), query_msg).unwrap()).unwrap();
+        from_json(query(deps.as_ref(), env.clone(), query_msg).unwrap()).unwrap();
 
     assert_eq!(res.unlock_time, env.block.time.seconds() + 3 * ONE_DAY);
     assert_eq!(res.recipient, Addr::unchecked("recipient"));
+    assert_eq!(res.depositor, Addr::unchecked(MOCK_TOKEN_OWNER));
+    assert!(!res.revocable);
 }
 
 #[test]
@@ -363,6 +447,10 @@ fn test_query_unlocktime() {
         msg: encode_binary(&Cw721HookMsg::TimelockNft {
             lock_duration: MillisecondsDuration::from_seconds(3 * ONE_DAY),
             recipient: Recipient::new("recipient", None),
+            revocable: false,
+            arbiters: None,
+            claim_window: None,
+            receipt: false,
         })
         .unwrap(),
     });
@@ -374,7 +462,7 @@ fn test_query_unlocktime() {
     };
 
     let res: UnlockTimeResponse =
-        from_json(&query(deps.as_ref(), env.clone(), query_msg).unwrap()).unwrap();
+        from_json(query(deps.as_ref(), env.clone(), query_msg).unwrap()).unwrap();
 
     assert_eq!(res.unlock_time, env.block.time.seconds() + 3 * ONE_DAY);
 }
@@ -398,6 +486,10 @@ fn test_query_is_locked() {
         msg: encode_binary(&Cw721HookMsg::TimelockNft {
             lock_duration: MillisecondsDuration::from_seconds(3 * ONE_DAY),
             recipient: Recipient::new("recipient", None),
+            revocable: false,
+            arbiters: None,
+            claim_window: None,
+            receipt: false,
         })
         .unwrap(),
     });
@@ -413,7 +505,5373 @@ fn test_query_is_locked() {
     };
 
     let res: IsLockedResponse =
-        from_json(&query(deps.as_ref(), env.clone(), query_msg).unwrap()).unwrap();
+        from_json(query(deps.as_ref(), env.clone(), query_msg).unwrap()).unwrap();
+
+    assert!(res.is_locked);
+}
+
+fn send_nft(
+    deps: DepsMut,
+    env: Env,
+    sender: &str,
+    token_id: &str,
+    hook_msg: Cw721HookMsg,
+) -> Result<Response, ContractError> {
+    let msg = ExecuteMsg::ReceiveNft(Cw721ReceiveMsg {
+        sender: sender.to_string(),
+        token_id: token_id.to_string(),
+        msg: encode_binary(&hook_msg).unwrap(),
+    });
+    execute(deps, env, mock_info(MOCK_CW721_CONTRACT, &[]), msg)
+}
+
+fn timelock_nft(
+    deps: DepsMut,
+    env: Env,
+    token_id: &str,
+    lock_duration: u64,
+    recipient: &str,
+) -> Result<Response, ContractError> {
+    send_nft(
+        deps,
+        env,
+        MOCK_TOKEN_OWNER,
+        token_id,
+        Cw721HookMsg::TimelockNft {
+            lock_duration: MillisecondsDuration::from_seconds(lock_duration),
+            recipient: Recipient::new(recipient, None),
+            revocable: false,
+            arbiters: None,
+            claim_window: None,
+            receipt: false,
+        },
+    )
+}
+
+fn init(deps: DepsMut) {
+    let msg = InstantiateMsg {
+        kernel_address: MOCK_KERNEL_CONTRACT.to_string(),
+        owner: Some("creator".to_owned()),
+        authorized_token_addresses: None,
+    };
+    instantiate(deps, mock_env(), mock_info("creator", &[]), msg).unwrap();
+}
+
+#[test]
+fn test_query_all_locks() {
+    let mut deps = mock_dependencies_custom(&[]);
+    let env = mock_env();
+    init(deps.as_mut());
+
+    timelock_nft(
+        deps.as_mut(),
+        env.clone(),
+        "token1",
+        3 * ONE_DAY,
+        "recipient",
+    )
+    .unwrap();
+    timelock_nft(
+        deps.as_mut(),
+        env.clone(),
+        "token2",
+        2 * ONE_DAY,
+        "recipient",
+    )
+    .unwrap();
+    timelock_nft(
+        deps.as_mut(),
+        env.clone(),
+        MOCK_UNCLAIMED_TOKEN,
+        ONE_DAY,
+        "other_recipient",
+    )
+    .unwrap();
+
+    let res: LocksResponse = from_json(
+        query(
+            deps.as_ref(),
+            env.clone(),
+            QueryMsg::AllLocks {
+                start_after: None,
+                limit: Some(2),
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        res.locks,
+        vec![
+            LockInfoResponse {
+                lock_id: 1,
+                cw721_contract: Addr::unchecked(MOCK_CW721_CONTRACT),
+                token_id: "token1".to_string(),
+                unlock_time: env.block.time.seconds() + 3 * ONE_DAY,
+                unlock_condition: UnlockCondition::Time(Milliseconds::from_seconds(
+                    env.block.time.seconds() + 3 * ONE_DAY
+                )),
+                recipient: Addr::unchecked("recipient"),
+                depositor: Addr::unchecked(MOCK_TOKEN_OWNER),
+                // The mock collection reports another owner for this token
+                status: LockStatus::Unknown,
+            },
+            LockInfoResponse {
+                lock_id: 2,
+                cw721_contract: Addr::unchecked(MOCK_CW721_CONTRACT),
+                token_id: "token2".to_string(),
+                unlock_time: env.block.time.seconds() + 2 * ONE_DAY,
+                unlock_condition: UnlockCondition::Time(Milliseconds::from_seconds(
+                    env.block.time.seconds() + 2 * ONE_DAY
+                )),
+                recipient: Addr::unchecked("recipient"),
+                depositor: Addr::unchecked(MOCK_TOKEN_OWNER),
+                status: LockStatus::Unknown,
+            },
+        ]
+    );
+
+    let res: LocksResponse = from_json(
+        query(
+            deps.as_ref(),
+            env.clone(),
+            QueryMsg::AllLocks {
+                start_after: Some((MOCK_CW721_CONTRACT.to_string(), "token2".to_string())),
+                limit: None,
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        res.locks,
+        vec![LockInfoResponse {
+            lock_id: 3,
+            cw721_contract: Addr::unchecked(MOCK_CW721_CONTRACT),
+            token_id: MOCK_UNCLAIMED_TOKEN.to_string(),
+            unlock_time: env.block.time.seconds() + ONE_DAY,
+            unlock_condition: UnlockCondition::Time(Milliseconds::from_seconds(
+                env.block.time.seconds() + ONE_DAY
+            )),
+            recipient: Addr::unchecked("other_recipient"),
+            depositor: Addr::unchecked(MOCK_TOKEN_OWNER),
+            status: LockStatus::Locked,
+        }]
+    );
+}
+
+#[test]
+fn test_query_locks_by_recipient_and_collection() {
+    let mut deps = mock_dependencies_custom(&[]);
+    let env = mock_env();
+    init(deps.as_mut());
+
+    timelock_nft(
+        deps.as_mut(),
+        env.clone(),
+        "token1",
+        3 * ONE_DAY,
+        "recipient",
+    )
+    .unwrap();
+    timelock_nft(deps.as_mut(), env.clone(), "token2", 2 * ONE_DAY, "other").unwrap();
+    timelock_nft(deps.as_mut(), env.clone(), "token3", ONE_DAY, "recipient").unwrap();
+
+    let res: LocksResponse = from_json(
+        query(
+            deps.as_ref(),
+            env.clone(),
+            QueryMsg::LocksByRecipient {
+                recipient: AndrAddr::from_string("recipient"),
+                start_after: None,
+                limit: None,
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    let token_ids: Vec<String> = res.locks.into_iter().map(|l| l.token_id).collect();
+    assert_eq!(token_ids, vec!["token1", "token3"]);
+
+    let res: LocksResponse = from_json(
+        query(
+            deps.as_ref(),
+            env.clone(),
+            QueryMsg::LocksByRecipient {
+                recipient: AndrAddr::from_string("recipient"),
+                start_after: Some((MOCK_CW721_CONTRACT.to_string(), "token1".to_string())),
+                limit: None,
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    let token_ids: Vec<String> = res.locks.into_iter().map(|l| l.token_id).collect();
+    assert_eq!(token_ids, vec!["token3"]);
+
+    let res: LocksResponse = from_json(
+        query(
+            deps.as_ref(),
+            env.clone(),
+            QueryMsg::LocksByCollection {
+                cw721_contract: AndrAddr::from_string(MOCK_CW721_CONTRACT),
+                start_after: Some("token1".to_string()),
+                limit: Some(1),
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    let token_ids: Vec<String> = res.locks.into_iter().map(|l| l.token_id).collect();
+    assert_eq!(token_ids, vec!["token2"]);
+}
+
+#[test]
+fn test_query_locks_unlocking_between() {
+    let mut deps = mock_dependencies_custom(&[]);
+    let env = mock_env();
+    init(deps.as_mut());
+
+    timelock_nft(
+        deps.as_mut(),
+        env.clone(),
+        "token1",
+        3 * ONE_DAY,
+        "recipient",
+    )
+    .unwrap();
+    timelock_nft(
+        deps.as_mut(),
+        env.clone(),
+        "token2",
+        5 * ONE_DAY,
+        "recipient",
+    )
+    .unwrap();
+    timelock_nft(
+        deps.as_mut(),
+        env.clone(),
+        MOCK_UNCLAIMED_TOKEN,
+        ONE_DAY,
+        "recipient",
+    )
+    .unwrap();
+
+    let now = env.block.time.seconds();
+    let query_msg = |start_after: Option<(u64, &str)>| QueryMsg::LocksUnlockingBetween {
+        start: Milliseconds::from_seconds(now + ONE_DAY),
+        end: Milliseconds::from_seconds(now + 5 * ONE_DAY),
+        start_after: start_after.map(|(unlock_time, token_id)| {
+            (
+                Milliseconds::from_seconds(unlock_time),
+                MOCK_CW721_CONTRACT.to_string(),
+                token_id.to_string(),
+            )
+        }),
+        limit: Some(1),
+    };
+
+    let res: LocksResponse =
+        from_json(query(deps.as_ref(), env.clone(), query_msg(None)).unwrap()).unwrap();
+    assert_eq!(res.locks.len(), 1);
+    assert_eq!(res.locks[0].token_id, MOCK_UNCLAIMED_TOKEN);
+    assert_eq!(res.locks[0].status, LockStatus::Locked);
+
+    let res: LocksResponse = from_json(
+        query(
+            deps.as_ref(),
+            env.clone(),
+            query_msg(Some((now + ONE_DAY, MOCK_UNCLAIMED_TOKEN))),
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(res.locks.len(), 1);
+    assert_eq!(res.locks[0].token_id, "token1");
+
+    // The end of the range is exclusive
+    let res: LocksResponse = from_json(
+        query(
+            deps.as_ref(),
+            env.clone(),
+            query_msg(Some((now + 3 * ONE_DAY, "token1"))),
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert!(res.locks.is_empty());
+
+    // Once the unlock time passes the lock reports as claimable
+    let mut later_env = mock_env();
+    later_env.block.time = env.block.time.plus_seconds(ONE_DAY);
+    let res: LocksResponse =
+        from_json(query(deps.as_ref(), later_env.clone(), query_msg(None)).unwrap()).unwrap();
+    assert_eq!(res.locks[0].status, LockStatus::Claimable);
+
+    // A cursor still works after the lock it points at has been claimed
+    execute(
+        deps.as_mut(),
+        later_env.clone(),
+        mock_info("recipient", &[]),
+        ExecuteMsg::ClaimNft {
+            cw721_contract: AndrAddr::from_string(MOCK_CW721_CONTRACT),
+            token_id: MOCK_UNCLAIMED_TOKEN.to_string(),
+        },
+    )
+    .unwrap();
+    let res: LocksResponse = from_json(
+        query(
+            deps.as_ref(),
+            later_env,
+            query_msg(Some((now + ONE_DAY, MOCK_UNCLAIMED_TOKEN))),
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(res.locks.len(), 1);
+    assert_eq!(res.locks[0].token_id, "token1");
+}
+
+fn revocable_lock(deps: DepsMut, env: Env, token_id: &str) {
+    send_nft(
+        deps,
+        env,
+        MOCK_TOKEN_OWNER,
+        token_id,
+        Cw721HookMsg::TimelockNft {
+            lock_duration: MillisecondsDuration::from_seconds(3 * ONE_DAY),
+            recipient: Recipient::new("recipient", None),
+            revocable: true,
+            arbiters: None,
+            claim_window: None,
+            receipt: false,
+        },
+    )
+    .unwrap();
+}
+
+#[test]
+fn test_revoke_lock_returns_nft_to_depositor() {
+    let mut deps = mock_dependencies_custom(&[]);
+    let env = mock_env();
+    init(deps.as_mut());
+    revocable_lock(deps.as_mut(), env.clone(), "token1");
+
+    let revoke_msg = ExecuteMsg::RevokeLock {
+        cw721_contract: AndrAddr::from_string(MOCK_CW721_CONTRACT),
+        token_id: "token1".to_string(),
+        new_recipient: None,
+    };
+
+    // Only the depositor can revoke
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("recipient", &[]),
+        revoke_msg.clone(),
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(MOCK_TOKEN_OWNER, &[]),
+        revoke_msg,
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(WasmMsg::Execute {
+            contract_addr: MOCK_CW721_CONTRACT.to_string(),
+            msg: encode_binary(&Cw721ExecuteMsg::TransferNft {
+                recipient: AndrAddr::from_string(MOCK_TOKEN_OWNER),
+                token_id: "token1".to_string(),
+            })
+            .unwrap(),
+            funds: vec![],
+        })]
+    );
+
+    let err = query(
+        deps.as_ref(),
+        env,
+        QueryMsg::NftDetails {
+            cw721_contract: AndrAddr::from_string(MOCK_CW721_CONTRACT),
+            token_id: "token1".to_string(),
+        },
+    );
+    assert!(err.is_err());
+}
+
+#[test]
+fn test_revoke_lock_new_recipient() {
+    let mut deps = mock_dependencies_custom(&[]);
+    let env = mock_env();
+    init(deps.as_mut());
+    revocable_lock(deps.as_mut(), env.clone(), "token1");
+
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(MOCK_TOKEN_OWNER, &[]),
+        ExecuteMsg::RevokeLock {
+            cw721_contract: AndrAddr::from_string(MOCK_CW721_CONTRACT),
+            token_id: "token1".to_string(),
+            new_recipient: Some(Recipient::from_string("new_recipient")),
+        },
+    )
+    .unwrap();
+    assert!(res.messages.is_empty());
+
+    let res: NftDetailsResponse = from_json(
+        query(
+            deps.as_ref(),
+            env.clone(),
+            QueryMsg::NftDetails {
+                cw721_contract: AndrAddr::from_string(MOCK_CW721_CONTRACT),
+                token_id: "token1".to_string(),
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(res.recipient, Addr::unchecked("new_recipient"));
+
+    let res: LocksResponse = from_json(
+        query(
+            deps.as_ref(),
+            env,
+            QueryMsg::LocksByRecipient {
+                recipient: AndrAddr::from_string("recipient"),
+                start_after: None,
+                limit: None,
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert!(res.locks.is_empty());
+}
+
+#[test]
+fn test_revoke_lock_not_allowed() {
+    let mut deps = mock_dependencies_custom(&[]);
+    let env = mock_env();
+    init(deps.as_mut());
+    timelock_nft(
+        deps.as_mut(),
+        env.clone(),
+        "token1",
+        3 * ONE_DAY,
+        "recipient",
+    )
+    .unwrap();
+    revocable_lock(deps.as_mut(), env.clone(), "token2");
+
+    let revoke_msg = |token_id: &str| ExecuteMsg::RevokeLock {
+        cw721_contract: AndrAddr::from_string(MOCK_CW721_CONTRACT),
+        token_id: token_id.to_string(),
+        new_recipient: None,
+    };
+
+    // Non-revocable locks cannot be revoked, even by the depositor
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(MOCK_TOKEN_OWNER, &[]),
+        revoke_msg("token1"),
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+
+    // Revocable locks cannot be revoked after the unlock time
+    let mut later_env = mock_env();
+    later_env.block.time = env.block.time.plus_seconds(3 * ONE_DAY);
+    let err = execute(
+        deps.as_mut(),
+        later_env,
+        mock_info(MOCK_TOKEN_OWNER, &[]),
+        revoke_msg("token2"),
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::Expired {});
+}
+
+#[test]
+fn test_extend_lock() {
+    let mut deps = mock_dependencies_custom(&[]);
+    let env = mock_env();
+    init(deps.as_mut());
+    timelock_nft(
+        deps.as_mut(),
+        env.clone(),
+        "token1",
+        3 * ONE_DAY,
+        "recipient",
+    )
+    .unwrap();
+
+    let extend_msg = |days: u64| ExecuteMsg::ExtendLock {
+        cw721_contract: AndrAddr::from_string(MOCK_CW721_CONTRACT),
+        token_id: "token1".to_string(),
+        additional_duration: MillisecondsDuration::from_seconds(days * ONE_DAY),
+    };
+
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("anyone", &[]),
+        extend_msg(1),
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+
+    // Both the recipient and the depositor can extend
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("recipient", &[]),
+        extend_msg(2),
+    )
+    .unwrap();
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(MOCK_TOKEN_OWNER, &[]),
+        extend_msg(5),
+    )
+    .unwrap();
+
+    let res: UnlockTimeResponse = from_json(
+        query(
+            deps.as_ref(),
+            env.clone(),
+            QueryMsg::UnlockTime {
+                cw721_contract: AndrAddr::from_string(MOCK_CW721_CONTRACT),
+                token_id: "token1".to_string(),
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(res.unlock_time, env.block.time.seconds() + 10 * ONE_DAY);
+
+    // The extended lock cannot end more than a year after it was created
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("recipient", &[]),
+        extend_msg(360),
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::LockTimeTooLong {});
+
+    // Measured from the lock's start, so waiting does not allow a longer extension, whoever
+    // asks for it
+    let mut later_env = mock_env();
+    later_env.block.time = env.block.time.plus_seconds(5 * ONE_DAY);
+    let err = execute(
+        deps.as_mut(),
+        later_env.clone(),
+        mock_info(MOCK_TOKEN_OWNER, &[]),
+        extend_msg(360),
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::LockTimeTooLong {});
+    execute(
+        deps.as_mut(),
+        later_env,
+        mock_info("recipient", &[]),
+        extend_msg(355),
+    )
+    .unwrap();
+}
+
+#[test]
+fn test_extend_unlocked_lock() {
+    let mut deps = mock_dependencies_custom(&[]);
+    let env = mock_env();
+    init(deps.as_mut());
+    timelock_nft(
+        deps.as_mut(),
+        env.clone(),
+        "token1",
+        3 * ONE_DAY,
+        "recipient",
+    )
+    .unwrap();
+
+    let mut later_env = mock_env();
+    later_env.block.time = env.block.time.plus_seconds(3 * ONE_DAY);
+    let err = execute(
+        deps.as_mut(),
+        later_env,
+        mock_info("recipient", &[]),
+        ExecuteMsg::ExtendLock {
+            cw721_contract: AndrAddr::from_string(MOCK_CW721_CONTRACT),
+            token_id: "token1".to_string(),
+            additional_duration: MillisecondsDuration::from_seconds(ONE_DAY),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::Expired {});
+}
+
+#[test]
+fn test_transfer_beneficiary() {
+    let mut deps = mock_dependencies_custom(&[]);
+    let env = mock_env();
+    init(deps.as_mut());
+    timelock_nft(
+        deps.as_mut(),
+        env.clone(),
+        "token1",
+        3 * ONE_DAY,
+        "recipient",
+    )
+    .unwrap();
 
-    assert_eq!(res.is_locked, true);
+    let transfer_msg = ExecuteMsg::TransferBeneficiary {
+        cw721_contract: AndrAddr::from_string(MOCK_CW721_CONTRACT),
+        token_id: "token1".to_string(),
+        new_recipient: Recipient::from_string("buyer"),
+    };
+
+    // The depositor cannot hand over the claim
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(MOCK_TOKEN_OWNER, &[]),
+        transfer_msg.clone(),
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("recipient", &[]),
+        transfer_msg.clone(),
+    )
+    .unwrap();
+    assert_eq!(
+        res.events,
+        vec![Event::new("beneficiary_transferred")
+            .add_attribute("cw721_contract", MOCK_CW721_CONTRACT)
+            .add_attribute("token_id", "token1")
+            .add_attribute("previous_recipient", "recipient")
+            .add_attribute("new_recipient", "buyer")]
+    );
+
+    // The previous recipient no longer holds the claim
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("recipient", &[]),
+        transfer_msg,
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+
+    let res: NftDetailsResponse = from_json(
+        query(
+            deps.as_ref(),
+            env,
+            QueryMsg::NftDetails {
+                cw721_contract: AndrAddr::from_string(MOCK_CW721_CONTRACT),
+                token_id: "token1".to_string(),
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(res.recipient, Addr::unchecked("buyer"));
+}
+
+fn timelock_nft_until(
+    deps: DepsMut,
+    env: Env,
+    token_id: &str,
+    unlock_condition: UnlockCondition,
+) -> Result<Response, ContractError> {
+    send_nft(
+        deps,
+        env,
+        MOCK_TOKEN_OWNER,
+        token_id,
+        Cw721HookMsg::TimelockNftUntil {
+            unlock_condition,
+            recipient: Recipient::new("recipient", None),
+            revocable: false,
+            arbiters: None,
+            claim_window: None,
+            receipt: false,
+        },
+    )
+}
+
+fn query_is_locked(deps: Deps, env: Env, token_id: &str) -> IsLockedResponse {
+    from_json(
+        query(
+            deps,
+            env,
+            QueryMsg::IsLocked {
+                cw721_contract: AndrAddr::from_string(MOCK_CW721_CONTRACT),
+                token_id: token_id.to_string(),
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap()
+}
+
+#[test]
+fn test_timelock_until_time_and_height() {
+    let mut deps = mock_dependencies_custom(&[]);
+    let env = mock_env();
+    init(deps.as_mut());
+
+    let unlock_time = Milliseconds::from_seconds(env.block.time.seconds() + 2 * ONE_DAY);
+    let unlock_height = env.block.height + ONE_DAY_BLOCKS;
+    timelock_nft_until(
+        deps.as_mut(),
+        env.clone(),
+        "time",
+        UnlockCondition::Time(unlock_time),
+    )
+    .unwrap();
+    timelock_nft_until(
+        deps.as_mut(),
+        env.clone(),
+        "height",
+        UnlockCondition::Height(unlock_height),
+    )
+    .unwrap();
+    timelock_nft_until(
+        deps.as_mut(),
+        env.clone(),
+        "and",
+        UnlockCondition::TimeAndHeight {
+            time: unlock_time,
+            height: unlock_height,
+        },
+    )
+    .unwrap();
+    timelock_nft_until(
+        deps.as_mut(),
+        env.clone(),
+        "or",
+        UnlockCondition::TimeOrHeight {
+            time: unlock_time,
+            height: unlock_height,
+        },
+    )
+    .unwrap();
+
+    let res = query_is_locked(deps.as_ref(), env.clone(), "height");
+    assert!(res.is_locked);
+    assert_eq!(res.unlock_condition, UnlockCondition::Height(unlock_height));
+
+    // Only the time has passed
+    let mut time_env = mock_env();
+    time_env.block.time = unlock_time.into();
+    assert!(!query_is_locked(deps.as_ref(), time_env.clone(), "time").is_locked);
+    assert!(query_is_locked(deps.as_ref(), time_env.clone(), "height").is_locked);
+    assert!(query_is_locked(deps.as_ref(), time_env.clone(), "and").is_locked);
+    assert!(!query_is_locked(deps.as_ref(), time_env.clone(), "or").is_locked);
+
+    // Only the height has been reached
+    let mut height_env = mock_env();
+    height_env.block.height = unlock_height;
+    assert!(query_is_locked(deps.as_ref(), height_env.clone(), "time").is_locked);
+    assert!(!query_is_locked(deps.as_ref(), height_env.clone(), "height").is_locked);
+    assert!(query_is_locked(deps.as_ref(), height_env.clone(), "and").is_locked);
+    assert!(!query_is_locked(deps.as_ref(), height_env.clone(), "or").is_locked);
+
+    let err = execute(
+        deps.as_mut(),
+        time_env.clone(),
+        mock_info("recipient", &[]),
+        ExecuteMsg::ClaimNft {
+            cw721_contract: AndrAddr::from_string(MOCK_CW721_CONTRACT),
+            token_id: "and".to_string(),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::LockedNFT {});
+
+    let mut both_env = time_env;
+    both_env.block.height = unlock_height;
+    execute(
+        deps.as_mut(),
+        both_env,
+        mock_info("recipient", &[]),
+        ExecuteMsg::ClaimNft {
+            cw721_contract: AndrAddr::from_string(MOCK_CW721_CONTRACT),
+            token_id: "and".to_string(),
+        },
+    )
+    .unwrap();
+
+    let res: NftDetailsResponse = from_json(
+        query(
+            deps.as_ref(),
+            height_env,
+            QueryMsg::NftDetails {
+                cw721_contract: AndrAddr::from_string(MOCK_CW721_CONTRACT),
+                token_id: "height".to_string(),
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(res.unlock_time, 0);
+    assert!(!res.is_locked);
+}
+
+#[test]
+fn test_extend_time_and_height_lock_after_time() {
+    let mut deps = mock_dependencies_custom(&[]);
+    let env = mock_env();
+    init(deps.as_mut());
+
+    let unlock_time = Milliseconds::from_seconds(env.block.time.seconds() + 2 * ONE_DAY);
+    timelock_nft_until(
+        deps.as_mut(),
+        env.clone(),
+        "token1",
+        UnlockCondition::TimeAndHeight {
+            time: unlock_time,
+            height: env.block.height + ONE_DAY_BLOCKS,
+        },
+    )
+    .unwrap();
+
+    // The time has passed but the height has not been reached, so the lock can still be extended
+    let mut time_env = env;
+    time_env.block.time = unlock_time
+        .plus_milliseconds(Milliseconds::from_seconds(2 * ONE_DAY))
+        .into();
+    let res = execute(
+        deps.as_mut(),
+        time_env,
+        mock_info("recipient", &[]),
+        ExecuteMsg::ExtendLock {
+            cw721_contract: AndrAddr::from_string(MOCK_CW721_CONTRACT),
+            token_id: "token1".to_string(),
+            additional_duration: MillisecondsDuration::from_seconds(ONE_DAY),
+        },
+    )
+    .unwrap();
+    assert!(res.attributes.contains(&Attribute::new(
+        "unlock_time",
+        (unlock_time.seconds() + ONE_DAY).to_string()
+    )));
+}
+
+#[test]
+fn test_timelock_until_invalid_condition() {
+    let mut deps = mock_dependencies_custom(&[]);
+    let env = mock_env();
+    init(deps.as_mut());
+
+    let err = timelock_nft_until(
+        deps.as_mut(),
+        env.clone(),
+        "token1",
+        UnlockCondition::Height(env.block.height),
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::ExpirationInPast {});
+
+    // Heights are held to the policy's height limits, whatever the time component
+    let unlock_time = Milliseconds::from_seconds(env.block.time.seconds() + 2 * ONE_DAY);
+    for (unlock_condition, expected) in [
+        (
+            UnlockCondition::Height(env.block.height + 1),
+            ContractError::LockTimeTooShort {},
+        ),
+        (
+            UnlockCondition::TimeOrHeight {
+                time: unlock_time,
+                height: env.block.height + 1,
+            },
+            ContractError::LockTimeTooShort {},
+        ),
+        (
+            UnlockCondition::Height(u64::MAX),
+            ContractError::LockTimeTooLong {},
+        ),
+        (
+            UnlockCondition::TimeAndHeight {
+                time: unlock_time,
+                height: u64::MAX,
+            },
+            ContractError::LockTimeTooLong {},
+        ),
+    ] {
+        let err =
+            timelock_nft_until(deps.as_mut(), env.clone(), "token1", unlock_condition).unwrap_err();
+        assert_eq!(err, expected);
+    }
+
+    let err = timelock_nft_until(
+        deps.as_mut(),
+        env.clone(),
+        "token1",
+        UnlockCondition::TimeOrHeight {
+            time: Milliseconds::from_seconds(env.block.time.seconds() + ONE_DAY / 2),
+            height: env.block.height + 1,
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::LockTimeTooShort {});
+
+    let err = timelock_nft_until(
+        deps.as_mut(),
+        env.clone(),
+        "token1",
+        UnlockCondition::Time(Milliseconds::from_seconds(
+            env.block.time.seconds() + 2 * ONE_YEAR,
+        )),
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::LockTimeTooLong {});
+
+    // Height only locks have no time to extend
+    timelock_nft_until(
+        deps.as_mut(),
+        env.clone(),
+        "token1",
+        UnlockCondition::Height(env.block.height + ONE_DAY_BLOCKS),
+    )
+    .unwrap();
+    let err = execute(
+        deps.as_mut(),
+        env,
+        mock_info("recipient", &[]),
+        ExecuteMsg::ExtendLock {
+            cw721_contract: AndrAddr::from_string(MOCK_CW721_CONTRACT),
+            token_id: "token1".to_string(),
+            additional_duration: MillisecondsDuration::from_seconds(ONE_DAY),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::ExpirationNotSpecified {});
+}
+
+fn query_lock_policy(deps: Deps, env: Env) -> LockPolicyResponse {
+    from_json(
+        query(
+            deps,
+            env,
+            QueryMsg::LockPolicy {
+                cw721_contract: AndrAddr::from_string(MOCK_CW721_CONTRACT),
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap()
+}
+
+#[test]
+fn test_update_lock_policy() {
+    let mut deps = mock_dependencies_custom(&[]);
+    let env = mock_env();
+    init(deps.as_mut());
+
+    let default_policy = LockPolicy {
+        min_duration: MillisecondsDuration::from_seconds(ONE_DAY),
+        max_duration: MillisecondsDuration::from_seconds(ONE_YEAR),
+        allow_revocable: true,
+        forced_recipient: None,
+        early_unlock: None,
+        height_limits: HeightLimits::default(),
+    };
+    assert_eq!(
+        query_lock_policy(deps.as_ref(), env.clone()),
+        LockPolicyResponse {
+            policy: default_policy.clone(),
+            is_override: false,
+        }
+    );
+
+    let policy = LockPolicy {
+        min_duration: MillisecondsDuration::from_seconds(ONE_DAY / 2),
+        max_duration: MillisecondsDuration::from_seconds(2 * ONE_DAY),
+        allow_revocable: false,
+        forced_recipient: Some(Recipient::new("treasury", None)),
+        early_unlock: None,
+        height_limits: HeightLimits::default(),
+    };
+    let update_msg = ExecuteMsg::UpdateLockPolicy {
+        cw721_contract: Some(AndrAddr::from_string(MOCK_CW721_CONTRACT)),
+        policy: Some(policy.clone()),
+    };
+
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("anyone", &[]),
+        update_msg.clone(),
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("creator", &[]),
+        update_msg,
+    )
+    .unwrap();
+    assert_eq!(
+        query_lock_policy(deps.as_ref(), env.clone()),
+        LockPolicyResponse {
+            policy,
+            is_override: true,
+        }
+    );
+
+    // The collection's limits replace the defaults
+    let err = timelock_nft(
+        deps.as_mut(),
+        env.clone(),
+        "token1",
+        3 * ONE_DAY,
+        "recipient",
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::LockTimeTooLong {});
+
+    let err = send_nft(
+        deps.as_mut(),
+        env.clone(),
+        MOCK_TOKEN_OWNER,
+        "token1",
+        Cw721HookMsg::TimelockNft {
+            lock_duration: MillisecondsDuration::from_seconds(ONE_DAY),
+            recipient: Recipient::new("recipient", None),
+            revocable: true,
+            arbiters: None,
+            claim_window: None,
+            receipt: false,
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+
+    timelock_nft(
+        deps.as_mut(),
+        env.clone(),
+        "token1",
+        ONE_DAY / 2,
+        "recipient",
+    )
+    .unwrap();
+    let details: NftDetailsResponse = from_json(
+        query(
+            deps.as_ref(),
+            env.clone(),
+            QueryMsg::NftDetails {
+                cw721_contract: AndrAddr::from_string(MOCK_CW721_CONTRACT),
+                token_id: "token1".to_string(),
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(details.recipient, Addr::unchecked("treasury"));
+
+    // Removing the override falls back to the default policy
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("creator", &[]),
+        ExecuteMsg::UpdateLockPolicy {
+            cw721_contract: Some(AndrAddr::from_string(MOCK_CW721_CONTRACT)),
+            policy: None,
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        query_lock_policy(deps.as_ref(), env),
+        LockPolicyResponse {
+            policy: default_policy,
+            is_override: false,
+        }
+    );
+}
+
+#[test]
+fn test_update_default_lock_policy() {
+    let mut deps = mock_dependencies_custom(&[]);
+    let env = mock_env();
+    init(deps.as_mut());
+
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("creator", &[]),
+        ExecuteMsg::UpdateLockPolicy {
+            cw721_contract: None,
+            policy: Some(LockPolicy {
+                min_duration: MillisecondsDuration::from_seconds(2 * ONE_DAY),
+                max_duration: MillisecondsDuration::from_seconds(ONE_DAY),
+                allow_revocable: true,
+                forced_recipient: None,
+                early_unlock: None,
+                height_limits: HeightLimits::default(),
+            }),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidTimestamp {
+            msg: "min_duration cannot exceed max_duration".to_string(),
+        }
+    );
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("creator", &[]),
+        ExecuteMsg::UpdateLockPolicy {
+            cw721_contract: None,
+            policy: Some(LockPolicy {
+                min_duration: MillisecondsDuration::from_seconds(ONE_DAY),
+                max_duration: MillisecondsDuration::from_seconds(ONE_YEAR),
+                allow_revocable: true,
+                forced_recipient: None,
+                early_unlock: None,
+                height_limits: HeightLimits {
+                    min_blocks: 2,
+                    max_blocks: 1,
+                },
+            }),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidTimestamp {
+            msg: "min_blocks cannot exceed max_blocks".to_string(),
+        }
+    );
+
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("creator", &[]),
+        ExecuteMsg::UpdateLockPolicy {
+            cw721_contract: None,
+            policy: Some(LockPolicy {
+                min_duration: MillisecondsDuration::from_seconds(ONE_DAY),
+                max_duration: MillisecondsDuration::from_seconds(2 * ONE_YEAR),
+                allow_revocable: true,
+                forced_recipient: None,
+                early_unlock: None,
+                height_limits: HeightLimits::default(),
+            }),
+        },
+    )
+    .unwrap();
+
+    timelock_nft(
+        deps.as_mut(),
+        env,
+        "token1",
+        ONE_YEAR + ONE_DAY,
+        "recipient",
+    )
+    .unwrap();
+}
+
+fn query_authorized_token_addresses(
+    deps: Deps,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> Vec<Addr> {
+    let res: AuthorizedTokenAddressesResponse = from_json(
+        query(
+            deps,
+            mock_env(),
+            QueryMsg::AuthorizedTokenAddresses { start_after, limit },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    res.addresses
+}
+
+#[test]
+fn test_manage_authorized_token_addresses() {
+    let mut deps = mock_dependencies_custom(&[]);
+    let env = mock_env();
+    init(deps.as_mut());
+    assert!(query_authorized_token_addresses(deps.as_ref(), None, None).is_empty());
+
+    let add_msg = ExecuteMsg::AddAuthorizedTokenAddress {
+        address: AndrAddr::from_string("other_cw721"),
+    };
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("anyone", &[]),
+        add_msg.clone(),
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("creator", &[]),
+        add_msg,
+    )
+    .unwrap();
+    assert_eq!(
+        res.attributes,
+        vec![
+            Attribute::new("method", "add_authorized_token_address"),
+            Attribute::new("address", "other_cw721"),
+        ]
+    );
+
+    // Only authorized collections can deposit once the list is in use
+    let err = timelock_nft(
+        deps.as_mut(),
+        env.clone(),
+        "token1",
+        3 * ONE_DAY,
+        "recipient",
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("creator", &[]),
+        ExecuteMsg::AddAuthorizedTokenAddress {
+            address: AndrAddr::from_string(MOCK_CW721_CONTRACT),
+        },
+    )
+    .unwrap();
+    timelock_nft(
+        deps.as_mut(),
+        env.clone(),
+        "token1",
+        3 * ONE_DAY,
+        "recipient",
+    )
+    .unwrap();
+
+    assert_eq!(
+        query_authorized_token_addresses(deps.as_ref(), None, None),
+        vec![
+            Addr::unchecked(MOCK_CW721_CONTRACT),
+            Addr::unchecked("other_cw721"),
+        ]
+    );
+    assert_eq!(
+        query_authorized_token_addresses(deps.as_ref(), None, Some(1)),
+        vec![Addr::unchecked(MOCK_CW721_CONTRACT)]
+    );
+    assert_eq!(
+        query_authorized_token_addresses(
+            deps.as_ref(),
+            Some(MOCK_CW721_CONTRACT.to_string()),
+            None
+        ),
+        vec![Addr::unchecked("other_cw721")]
+    );
+
+    let remove_msg = ExecuteMsg::RemoveAuthorizedTokenAddress {
+        address: AndrAddr::from_string(MOCK_CW721_CONTRACT),
+    };
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("creator", &[]),
+        remove_msg.clone(),
+    )
+    .unwrap();
+    assert_eq!(
+        query_authorized_token_addresses(deps.as_ref(), None, None),
+        vec![Addr::unchecked("other_cw721")]
+    );
+    let err = timelock_nft(
+        deps.as_mut(),
+        env.clone(),
+        "token2",
+        3 * ONE_DAY,
+        "recipient",
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+
+    let err = execute(deps.as_mut(), env, mock_info("creator", &[]), remove_msg).unwrap_err();
+    assert_eq!(err, ContractError::InvalidAddress {});
+}
+
+#[test]
+fn test_claim_sends_nft_with_recipient_msg() {
+    let mut deps = mock_dependencies_custom(&[]);
+    let env = mock_env();
+    init(deps.as_mut());
+
+    let hook = to_json_binary(&"stake").unwrap();
+    send_nft(
+        deps.as_mut(),
+        env.clone(),
+        MOCK_TOKEN_OWNER,
+        "token1",
+        Cw721HookMsg::TimelockNft {
+            lock_duration: MillisecondsDuration::from_seconds(3 * ONE_DAY),
+            recipient: Recipient::new("staking_ado", Some(hook.clone())),
+            revocable: false,
+            arbiters: None,
+            claim_window: None,
+            receipt: false,
+        },
+    )
+    .unwrap();
+
+    let mut claim_env = env;
+    claim_env.block.time = claim_env.block.time.plus_seconds(3 * ONE_DAY);
+    let res = execute(
+        deps.as_mut(),
+        claim_env,
+        mock_info("anyone", &[]),
+        ExecuteMsg::ClaimNft {
+            cw721_contract: AndrAddr::from_string(MOCK_CW721_CONTRACT),
+            token_id: "token1".to_string(),
+        },
+    )
+    .unwrap();
+
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(WasmMsg::Execute {
+            contract_addr: MOCK_CW721_CONTRACT.to_string(),
+            msg: encode_binary(&Cw721ExecuteMsg::SendNft {
+                contract: AndrAddr::from_string("staking_ado"),
+                token_id: "token1".to_string(),
+                msg: hook,
+            })
+            .unwrap(),
+            funds: vec![],
+        })]
+    );
+}
+
+#[test]
+fn test_transfer_beneficiary_replaces_recipient_msg() {
+    let mut deps = mock_dependencies_custom(&[]);
+    let env = mock_env();
+    init(deps.as_mut());
+
+    send_nft(
+        deps.as_mut(),
+        env.clone(),
+        MOCK_TOKEN_OWNER,
+        "token1",
+        Cw721HookMsg::TimelockNft {
+            lock_duration: MillisecondsDuration::from_seconds(3 * ONE_DAY),
+            recipient: Recipient::new("staking_ado", Some(to_json_binary(&"stake").unwrap())),
+            revocable: false,
+            arbiters: None,
+            claim_window: None,
+            receipt: false,
+        },
+    )
+    .unwrap();
+
+    // The new recipient's (absent) message replaces the previous one
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("staking_ado", &[]),
+        ExecuteMsg::TransferBeneficiary {
+            cw721_contract: AndrAddr::from_string(MOCK_CW721_CONTRACT),
+            token_id: "token1".to_string(),
+            new_recipient: Recipient::from_string("new_recipient"),
+        },
+    )
+    .unwrap();
+
+    let mut claim_env = env;
+    claim_env.block.time = claim_env.block.time.plus_seconds(3 * ONE_DAY);
+    let res = execute(
+        deps.as_mut(),
+        claim_env,
+        mock_info("anyone", &[]),
+        ExecuteMsg::ClaimNft {
+            cw721_contract: AndrAddr::from_string(MOCK_CW721_CONTRACT),
+            token_id: "token1".to_string(),
+        },
+    )
+    .unwrap();
+
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(WasmMsg::Execute {
+            contract_addr: MOCK_CW721_CONTRACT.to_string(),
+            msg: encode_binary(&Cw721ExecuteMsg::TransferNft {
+                recipient: AndrAddr::from_string("new_recipient"),
+                token_id: "token1".to_string(),
+            })
+            .unwrap(),
+            funds: vec![],
+        })]
+    );
+}
+
+#[test]
+fn test_claim_nfts_reports_failures() {
+    let mut deps = mock_dependencies_custom(&[]);
+    let env = mock_env();
+    init(deps.as_mut());
+
+    timelock_nft(deps.as_mut(), env.clone(), "token1", ONE_DAY, "recipient").unwrap();
+    timelock_nft(
+        deps.as_mut(),
+        env.clone(),
+        "token2",
+        3 * ONE_DAY,
+        "recipient",
+    )
+    .unwrap();
+
+    let mut claim_env = env;
+    claim_env.block.time = claim_env.block.time.plus_seconds(ONE_DAY);
+    let items = vec![
+        (
+            AndrAddr::from_string(MOCK_CW721_CONTRACT),
+            "token1".to_string(),
+        ),
+        (
+            AndrAddr::from_string(MOCK_CW721_CONTRACT),
+            "token2".to_string(),
+        ),
+        (
+            AndrAddr::from_string(MOCK_CW721_CONTRACT),
+            "missing".to_string(),
+        ),
+    ];
+    let res = execute(
+        deps.as_mut(),
+        claim_env.clone(),
+        mock_info("anyone", &[]),
+        ExecuteMsg::ClaimNfts {
+            items: items.clone(),
+        },
+    )
+    .unwrap();
+
+    assert_eq!(res.messages.len(), 1);
+    assert_eq!(
+        res.attributes,
+        vec![
+            Attribute::new("method", "claim_nfts"),
+            Attribute::new("claimed", "1"),
+            Attribute::new("failed", "2"),
+        ]
+    );
+    assert_eq!(
+        res.events,
+        vec![
+            Event::new("claim_failed")
+                .add_attribute("cw721_contract", MOCK_CW721_CONTRACT)
+                .add_attribute("token_id", "token2")
+                .add_attribute("reason", ContractError::LockedNFT {}.to_string()),
+            Event::new("claim_failed")
+                .add_attribute("cw721_contract", MOCK_CW721_CONTRACT)
+                .add_attribute("token_id", "missing")
+                .add_attribute("reason", ContractError::NFTNotFound {}.to_string()),
+        ]
+    );
+
+    // Nothing left to claim, so the first error is returned
+    let err = execute(
+        deps.as_mut(),
+        claim_env.clone(),
+        mock_info("anyone", &[]),
+        ExecuteMsg::ClaimNfts { items },
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::NFTNotFound {});
+
+    let items = (0..31)
+        .map(|i| {
+            (
+                AndrAddr::from_string(MOCK_CW721_CONTRACT),
+                format!("token{i}"),
+            )
+        })
+        .collect();
+    let err = execute(
+        deps.as_mut(),
+        claim_env,
+        mock_info("anyone", &[]),
+        ExecuteMsg::ClaimNfts { items },
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::UnsupportedOperation {});
+}
+
+#[test]
+fn test_claim_all_unlocked() {
+    let mut deps = mock_dependencies_custom(&[]);
+    let env = mock_env();
+    init(deps.as_mut());
+
+    timelock_nft(
+        deps.as_mut(),
+        env.clone(),
+        "token0",
+        3 * ONE_DAY,
+        "recipient",
+    )
+    .unwrap();
+    timelock_nft(deps.as_mut(), env.clone(), "token1", ONE_DAY, "recipient").unwrap();
+    timelock_nft(deps.as_mut(), env.clone(), "token2", ONE_DAY, "recipient").unwrap();
+    timelock_nft(deps.as_mut(), env.clone(), "token3", ONE_DAY, "other").unwrap();
+    // The recipient cannot pay the claim fee of token4
+    update_rate(
+        deps.as_mut(),
+        RateAction::ClaimNft,
+        Rate {
+            asset: Asset::Native("uandr".to_string()),
+            amount: Uint128::new(5),
+            recipients: vec![RateRecipient {
+                address: Addr::unchecked("treasury"),
+                share: Decimal::one(),
+            }],
+        },
+    )
+    .unwrap();
+    timelock_nft(deps.as_mut(), env.clone(), "token4", ONE_DAY, "recipient").unwrap();
+
+    let mut claim_env = env;
+    claim_env.block.time = claim_env.block.time.plus_seconds(ONE_DAY);
+    let claim_msg = |start_after: Option<&str>| ExecuteMsg::ClaimAllUnlocked {
+        recipient: AndrAddr::from_string("recipient"),
+        start_after: start_after
+            .map(|token_id| (MOCK_CW721_CONTRACT.to_string(), token_id.to_string())),
+        limit: Some(2),
+    };
+    let transfer = |token_id: &str| {
+        SubMsg::new(WasmMsg::Execute {
+            contract_addr: MOCK_CW721_CONTRACT.to_string(),
+            msg: encode_binary(&Cw721ExecuteMsg::TransferNft {
+                recipient: AndrAddr::from_string("recipient"),
+                token_id: token_id.to_string(),
+            })
+            .unwrap(),
+            funds: vec![],
+        })
+    };
+
+    // Only `limit` locks are looked at, so the still locked token0 uses up part of the page
+    let res = execute(
+        deps.as_mut(),
+        claim_env.clone(),
+        mock_info("anyone", &[]),
+        claim_msg(None),
+    )
+    .unwrap();
+    assert_eq!(res.messages, vec![transfer("token1")]);
+    assert_eq!(
+        res.attributes,
+        vec![
+            Attribute::new("method", "claim_all_unlocked"),
+            Attribute::new("recipient", "recipient"),
+            Attribute::new("claimed", "1"),
+            Attribute::new("failed", "0"),
+            Attribute::new("last_cw721_contract", MOCK_CW721_CONTRACT),
+            Attribute::new("last_token_id", "token1"),
+        ]
+    );
+
+    // The next page goes on from there, over the recipient's locks only. token4 is reported as
+    // it cannot be paid for.
+    let res = execute(
+        deps.as_mut(),
+        claim_env.clone(),
+        mock_info("anyone", &[]),
+        claim_msg(Some("token1")),
+    )
+    .unwrap();
+    assert_eq!(res.messages, vec![transfer("token2")]);
+    assert_eq!(
+        res.attributes,
+        vec![
+            Attribute::new("method", "claim_all_unlocked"),
+            Attribute::new("recipient", "recipient"),
+            Attribute::new("claimed", "1"),
+            Attribute::new("failed", "1"),
+            Attribute::new("last_cw721_contract", MOCK_CW721_CONTRACT),
+            Attribute::new("last_token_id", "token4"),
+        ]
+    );
+    assert_eq!(res.events.len(), 1);
+    assert_eq!(res.events[0].ty, "claim_failed");
+    assert!(res.events[0]
+        .attributes
+        .contains(&Attribute::new("token_id", "token4")));
+
+    // A page that is not full is the last one
+    let res = execute(
+        deps.as_mut(),
+        claim_env,
+        mock_info("anyone", &[]),
+        claim_msg(Some("token4")),
+    )
+    .unwrap();
+    assert!(res.messages.is_empty());
+    assert_eq!(
+        res.attributes,
+        vec![
+            Attribute::new("method", "claim_all_unlocked"),
+            Attribute::new("recipient", "recipient"),
+            Attribute::new("claimed", "0"),
+            Attribute::new("failed", "0"),
+        ]
+    );
+
+    let locks: LocksResponse = from_json(
+        query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::AllLocks {
+                start_after: None,
+                limit: None,
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    let remaining: Vec<String> = locks.locks.into_iter().map(|lock| lock.token_id).collect();
+    assert_eq!(remaining, vec!["token0", "token3", "token4"]);
+}
+
+#[test]
+fn test_release_expired_pays_keeper_bounty() {
+    let mut deps = mock_dependencies_custom(&[]);
+    let env = mock_env();
+    init(deps.as_mut());
+
+    timelock_nft(
+        deps.as_mut(),
+        env.clone(),
+        "token1",
+        2 * ONE_DAY,
+        "recipient",
+    )
+    .unwrap();
+    timelock_nft(deps.as_mut(), env.clone(), "token2", ONE_DAY, "recipient").unwrap();
+    timelock_nft(
+        deps.as_mut(),
+        env.clone(),
+        "token3",
+        3 * ONE_DAY,
+        "recipient",
+    )
+    .unwrap();
+    timelock_nft_until(
+        deps.as_mut(),
+        env.clone(),
+        "token4",
+        UnlockCondition::Height(env.block.height + ONE_DAY_BLOCKS),
+    )
+    .unwrap();
+
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("anyone", &[]),
+        ExecuteMsg::UpdateKeeperBounty {
+            bounty: Some(coin(10, "uandr")),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("creator", &[]),
+        ExecuteMsg::UpdateKeeperBounty {
+            bounty: Some(coin(10, "uandr")),
+        },
+    )
+    .unwrap();
+
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(MOCK_TOKEN_OWNER, &[]),
+        ExecuteMsg::FundBountyPool {},
+    )
+    .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidFunds {
+            msg: "Must send funds to fund the bounty pool".to_string(),
+        }
+    );
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(MOCK_TOKEN_OWNER, &[coin(15, "uandr")]),
+        ExecuteMsg::FundBountyPool {},
+    )
+    .unwrap();
+
+    let mut release_env = env.clone();
+    release_env.block.time = release_env.block.time.plus_seconds(2 * ONE_DAY);
+    release_env.block.height += ONE_DAY_BLOCKS;
+    let res = execute(
+        deps.as_mut(),
+        release_env.clone(),
+        mock_info("keeper", &[]),
+        ExecuteMsg::ReleaseExpired { limit: None },
+    )
+    .unwrap();
+
+    // token2 unlocks first, then token1. The height only lock is left to its recipient.
+    let transfer = |token_id: &str, lock_id: u64| {
+        SubMsg::reply_always(
+            WasmMsg::Execute {
+                contract_addr: MOCK_CW721_CONTRACT.to_string(),
+                msg: encode_binary(&Cw721ExecuteMsg::TransferNft {
+                    recipient: AndrAddr::from_string("recipient"),
+                    token_id: token_id.to_string(),
+                })
+                .unwrap(),
+                funds: vec![],
+            },
+            lock_id,
+        )
+    };
+    assert_eq!(
+        res.messages,
+        vec![
+            transfer("token2", 2),
+            transfer("token1", 1),
+            SubMsg::new(BankMsg::Send {
+                to_address: "keeper".to_string(),
+                amount: vec![coin(15, "uandr")],
+            }),
+        ]
+    );
+    assert_eq!(
+        res.attributes,
+        vec![
+            Attribute::new("method", "release_expired"),
+            Attribute::new("released", "2"),
+            Attribute::new("power_expired", "0"),
+            Attribute::new("bounty", "15uandr"),
+        ]
+    );
+    assert!(timelocks().has(
+        deps.as_ref().storage,
+        (&Addr::unchecked(MOCK_CW721_CONTRACT), "token4")
+    ));
+
+    let bounty: KeeperBountyResponse =
+        from_json(query(deps.as_ref(), env, QueryMsg::KeeperBounty {}).unwrap()).unwrap();
+    assert_eq!(
+        bounty,
+        KeeperBountyResponse {
+            bounty: Some(coin(10, "uandr")),
+            pool: vec![],
+        }
+    );
+
+    // token3 is still locked so nothing is released and no bounty is paid
+    let res = execute(
+        deps.as_mut(),
+        release_env,
+        mock_info("keeper", &[]),
+        ExecuteMsg::ReleaseExpired { limit: None },
+    )
+    .unwrap();
+    assert!(res.messages.is_empty());
+}
+
+fn receipt_lock(deps: DepsMut, env: Env, token_id: &str) -> Result<Response, ContractError> {
+    send_nft(
+        deps,
+        env,
+        MOCK_TOKEN_OWNER,
+        token_id,
+        Cw721HookMsg::TimelockNft {
+            lock_duration: MillisecondsDuration::from_seconds(ONE_DAY),
+            recipient: Recipient::new("recipient", None),
+            revocable: false,
+            arbiters: None,
+            claim_window: None,
+            receipt: true,
+        },
+    )
+}
+
+#[test]
+fn test_receipt_nft_claim() {
+    let mut deps = mock_dependencies_custom(&[]);
+    let env = mock_env();
+    init(deps.as_mut());
+
+    // Receipts have to be enabled first
+    let err = receipt_lock(deps.as_mut(), env.clone(), "token1").unwrap_err();
+    assert_eq!(err, ContractError::UnsupportedOperation {});
+
+    let update_msg = ExecuteMsg::UpdateReceiptContract {
+        address: Some(AndrAddr::from_string("receipt_cw721")),
+    };
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("anyone", &[]),
+        update_msg.clone(),
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("creator", &[]),
+        update_msg,
+    )
+    .unwrap();
+
+    // Receipt locks cannot be revocable
+    let err = send_nft(
+        deps.as_mut(),
+        env.clone(),
+        MOCK_TOKEN_OWNER,
+        "token1",
+        Cw721HookMsg::TimelockNft {
+            lock_duration: MillisecondsDuration::from_seconds(ONE_DAY),
+            recipient: Recipient::new("recipient", None),
+            revocable: true,
+            arbiters: None,
+            claim_window: None,
+            receipt: true,
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::UnsupportedOperation {});
+
+    // Receipts are opt-in, so other locks work as usual while they are enabled
+    let res = timelock_nft(deps.as_mut(), env.clone(), "token2", ONE_DAY, "recipient").unwrap();
+    assert!(res.messages.is_empty());
+    send_nft(
+        deps.as_mut(),
+        env.clone(),
+        MOCK_TOKEN_OWNER,
+        "token3",
+        Cw721HookMsg::HeartbeatLockNft {
+            interval: MillisecondsDuration::from_seconds(ONE_DAY),
+            recipient: Recipient::from_string("recipient"),
+        },
+    )
+    .unwrap();
+    let power = query_voting_power(deps.as_ref(), env.clone(), "recipient", None);
+
+    let res = receipt_lock(deps.as_mut(), env.clone(), "token1").unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(WasmMsg::Execute {
+            contract_addr: "receipt_cw721".to_string(),
+            msg: encode_binary(&Cw721ExecuteMsg::Mint {
+                token_id: "1".to_string(),
+                owner: "recipient".to_string(),
+                token_uri: None,
+                extension: TokenExtension {
+                    publisher: env.contract.address.to_string(),
+                },
+            })
+            .unwrap(),
+            funds: vec![],
+        })]
+    );
+
+    let details: NftDetailsResponse = from_json(
+        query(
+            deps.as_ref(),
+            env.clone(),
+            QueryMsg::NftDetails {
+                cw721_contract: AndrAddr::from_string(MOCK_CW721_CONTRACT),
+                token_id: "token1".to_string(),
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        details.receipt,
+        Some(Receipt {
+            cw721_contract: Addr::unchecked("receipt_cw721"),
+            token_id: "1".to_string(),
+        })
+    );
+
+    // The receipt may be sold, so the recipient gets no voting power for it and cannot extend
+    assert_eq!(
+        query_voting_power(deps.as_ref(), env.clone(), "recipient", None),
+        power
+    );
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("recipient", &[]),
+        ExecuteMsg::ExtendLock {
+            cw721_contract: AndrAddr::from_string(MOCK_CW721_CONTRACT),
+            token_id: "token1".to_string(),
+            additional_duration: MillisecondsDuration::from_seconds(ONE_DAY),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::UnsupportedOperation {});
+
+    let redeem_msg = ExecuteMsg::ReceiveNft(Cw721ReceiveMsg {
+        sender: "receipt_buyer".to_string(),
+        token_id: "1".to_string(),
+        msg: encode_binary(&Cw721HookMsg::RedeemReceipt {}).unwrap(),
+    });
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("receipt_cw721", &[]),
+        redeem_msg.clone(),
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::LockedNFT {});
+
+    let mut claim_env = env;
+    claim_env.block.time = claim_env.block.time.plus_seconds(ONE_DAY);
+
+    // The NFT can only be claimed through the receipt
+    let err = execute(
+        deps.as_mut(),
+        claim_env.clone(),
+        mock_info("recipient", &[]),
+        ExecuteMsg::ClaimNft {
+            cw721_contract: AndrAddr::from_string(MOCK_CW721_CONTRACT),
+            token_id: "token1".to_string(),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::UnsupportedOperation {});
+
+    // Receipts from any other contract are not recognised
+    let err = execute(
+        deps.as_mut(),
+        claim_env.clone(),
+        mock_info("other_cw721", &[]),
+        redeem_msg.clone(),
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::NFTNotFound {});
+
+    let res = execute(
+        deps.as_mut(),
+        claim_env,
+        mock_info("receipt_cw721", &[]),
+        redeem_msg,
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![
+            SubMsg::new(WasmMsg::Execute {
+                contract_addr: MOCK_CW721_CONTRACT.to_string(),
+                msg: encode_binary(&Cw721ExecuteMsg::TransferNft {
+                    recipient: AndrAddr::from_string("receipt_buyer"),
+                    token_id: "token1".to_string(),
+                })
+                .unwrap(),
+                funds: vec![],
+            }),
+            SubMsg::new(WasmMsg::Execute {
+                contract_addr: "receipt_cw721".to_string(),
+                msg: encode_binary(&Cw721ExecuteMsg::Burn {
+                    token_id: "1".to_string(),
+                })
+                .unwrap(),
+                funds: vec![],
+            }),
+        ]
+    );
+}
+
+#[test]
+fn test_receipt_lock_earns_no_rewards() {
+    let mut deps = mock_dependencies_custom(&[]);
+    let env = mock_env();
+    init(deps.as_mut());
+
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("creator", &[]),
+        ExecuteMsg::UpdateReceiptContract {
+            address: Some(AndrAddr::from_string("receipt_cw721")),
+        },
+    )
+    .unwrap();
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("creator", &[]),
+        ExecuteMsg::UpdateRewardConfig {
+            cw721_contract: AndrAddr::from_string(MOCK_CW721_CONTRACT),
+            config: Some(RewardConfig {
+                asset: Asset::Native("uandr".to_string()),
+                rate_per_second: Uint128::new(2),
+                multiplier: None,
+            }),
+        },
+    )
+    .unwrap();
+    receipt_lock(deps.as_mut(), env.clone(), "token1").unwrap();
+
+    let mut later = env;
+    later.block.time = later.block.time.plus_seconds(ONE_DAY / 2);
+    assert!(query_pending_rewards(deps.as_ref(), later, "recipient").is_empty());
+}
+
+#[test]
+fn test_height_locks_earn_no_rewards() {
+    let mut deps = mock_dependencies_custom(&[]);
+    let env = mock_env();
+    init(deps.as_mut());
+
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("creator", &[]),
+        ExecuteMsg::UpdateRewardConfig {
+            cw721_contract: AndrAddr::from_string(MOCK_CW721_CONTRACT),
+            config: Some(RewardConfig {
+                asset: Asset::Native("uandr".to_string()),
+                rate_per_second: Uint128::new(2),
+                multiplier: None,
+            }),
+        },
+    )
+    .unwrap();
+
+    // Only the time and height lock is sure to stay locked until its unlock time
+    let unlock_time = Milliseconds::from_seconds(env.block.time.seconds() + ONE_DAY);
+    let unlock_height = env.block.height + ONE_DAY_BLOCKS;
+    for (token_id, unlock_condition) in [
+        ("height", UnlockCondition::Height(unlock_height)),
+        (
+            "or",
+            UnlockCondition::TimeOrHeight {
+                time: unlock_time,
+                height: unlock_height,
+            },
+        ),
+        (
+            "and",
+            UnlockCondition::TimeAndHeight {
+                time: unlock_time,
+                height: unlock_height,
+            },
+        ),
+    ] {
+        timelock_nft_until(deps.as_mut(), env.clone(), token_id, unlock_condition).unwrap();
+    }
+
+    let mut later = env;
+    later.block.time = later.block.time.plus_seconds(2 * ONE_DAY);
+    later.block.height = unlock_height;
+    assert_eq!(
+        query_pending_rewards(deps.as_ref(), later, "recipient"),
+        vec![RewardAmount {
+            asset: Asset::Native("uandr".to_string()),
+            amount: Uint128::from(2 * ONE_DAY),
+        }]
+    );
+}
+
+fn query_pending_rewards(deps: Deps, env: Env, recipient: &str) -> Vec<RewardAmount> {
+    let res: PendingRewardsResponse = from_json(
+        query(
+            deps,
+            env,
+            QueryMsg::PendingRewards {
+                recipient: AndrAddr::from_string(recipient),
+                start_after: None,
+                limit: None,
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    res.rewards
+}
+
+#[test]
+fn test_reward_emissions() {
+    let mut deps = mock_dependencies_custom(&[]);
+    let env = mock_env();
+    init(deps.as_mut());
+
+    let config = RewardConfig {
+        asset: Asset::Native("uandr".to_string()),
+        rate_per_second: Uint128::new(2),
+        multiplier: Some(LongLockMultiplier {
+            min_duration: MillisecondsDuration::from_seconds(2 * ONE_DAY),
+            multiplier: Decimal::percent(150),
+        }),
+    };
+    let update_msg = ExecuteMsg::UpdateRewardConfig {
+        cw721_contract: AndrAddr::from_string(MOCK_CW721_CONTRACT),
+        config: Some(config.clone()),
+    };
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("anyone", &[]),
+        update_msg.clone(),
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("creator", &[]),
+        update_msg,
+    )
+    .unwrap();
+
+    let res: RewardConfigResponse = from_json(
+        query(
+            deps.as_ref(),
+            env.clone(),
+            QueryMsg::RewardConfig {
+                cw721_contract: AndrAddr::from_string(MOCK_CW721_CONTRACT),
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(res.config, Some(config));
+
+    // token1 earns 2 per second for one day, token2 earns 3 per second for two days
+    timelock_nft(deps.as_mut(), env.clone(), "token1", ONE_DAY, "recipient").unwrap();
+    timelock_nft(
+        deps.as_mut(),
+        env.clone(),
+        "token2",
+        2 * ONE_DAY,
+        "recipient",
+    )
+    .unwrap();
+
+    let mut later = env.clone();
+    later.block.time = later.block.time.plus_seconds(ONE_DAY / 2);
+    assert_eq!(
+        query_pending_rewards(deps.as_ref(), later.clone(), "recipient"),
+        vec![RewardAmount {
+            asset: Asset::Native("uandr".to_string()),
+            amount: Uint128::from(5 * ONE_DAY / 2),
+        }]
+    );
+
+    // The query can be paged through the recipient's locks
+    for (start_after, amount) in [
+        (None, ONE_DAY),
+        (
+            Some((MOCK_CW721_CONTRACT.to_string(), "token1".to_string())),
+            3 * ONE_DAY / 2,
+        ),
+    ] {
+        let res: PendingRewardsResponse = from_json(
+            query(
+                deps.as_ref(),
+                later.clone(),
+                QueryMsg::PendingRewards {
+                    recipient: AndrAddr::from_string("recipient"),
+                    start_after,
+                    limit: Some(1),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            res.rewards,
+            vec![RewardAmount {
+                asset: Asset::Native("uandr".to_string()),
+                amount: Uint128::from(amount),
+            }]
+        );
+    }
+
+    // Nothing has been funded yet
+    let err = execute(
+        deps.as_mut(),
+        later.clone(),
+        mock_info("recipient", &[]),
+        ExecuteMsg::ClaimRewards {
+            start_after: None,
+            limit: None,
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::NoPendingPayments {});
+
+    let err = execute(
+        deps.as_mut(),
+        later.clone(),
+        mock_info("anyone", &[coin(1_000_000, "uandr")]),
+        ExecuteMsg::FundRewardPool {},
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+    execute(
+        deps.as_mut(),
+        later.clone(),
+        mock_info("creator", &[coin(1_000_000, "uandr")]),
+        ExecuteMsg::FundRewardPool {},
+    )
+    .unwrap();
+
+    let res = execute(
+        deps.as_mut(),
+        later,
+        mock_info("recipient", &[]),
+        ExecuteMsg::ClaimRewards {
+            start_after: None,
+            limit: None,
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(BankMsg::Send {
+            to_address: "recipient".to_string(),
+            amount: vec![coin((5 * ONE_DAY / 2).into(), "uandr")],
+        })]
+    );
+
+    // Emissions stop at the unlock time, even before the NFTs are claimed
+    let mut after_unlock = env;
+    after_unlock.block.time = after_unlock.block.time.plus_seconds(3 * ONE_DAY);
+    assert_eq!(
+        query_pending_rewards(deps.as_ref(), after_unlock.clone(), "recipient"),
+        vec![RewardAmount {
+            asset: Asset::Native("uandr".to_string()),
+            amount: Uint128::from(2 * ONE_DAY / 2 + 3 * 3 * ONE_DAY / 2),
+        }]
+    );
+
+    // Claiming the NFT settles its rewards, which can still be claimed afterwards
+    execute(
+        deps.as_mut(),
+        after_unlock.clone(),
+        mock_info("recipient", &[]),
+        ExecuteMsg::ClaimNft {
+            cw721_contract: AndrAddr::from_string(MOCK_CW721_CONTRACT),
+            token_id: "token1".to_string(),
+        },
+    )
+    .unwrap();
+    let res = execute(
+        deps.as_mut(),
+        after_unlock.clone(),
+        mock_info("recipient", &[]),
+        ExecuteMsg::ClaimRewards {
+            start_after: None,
+            limit: None,
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(BankMsg::Send {
+            to_address: "recipient".to_string(),
+            amount: vec![coin((ONE_DAY + 9 * ONE_DAY / 2).into(), "uandr")],
+        })]
+    );
+    assert!(query_pending_rewards(deps.as_ref(), after_unlock, "recipient").is_empty());
+}
+
+#[test]
+fn test_cw20_reward_pool_and_beneficiary_transfer() {
+    let mut deps = mock_dependencies_custom(&[]);
+    let env = mock_env();
+    init(deps.as_mut());
+
+    let asset = Asset::Cw20(Addr::unchecked("reward_cw20"));
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("creator", &[]),
+        ExecuteMsg::UpdateRewardConfig {
+            cw721_contract: AndrAddr::from_string(MOCK_CW721_CONTRACT),
+            config: Some(RewardConfig {
+                asset: asset.clone(),
+                rate_per_second: Uint128::one(),
+                multiplier: None,
+            }),
+        },
+    )
+    .unwrap();
+
+    let fund_msg = |sender: &str| {
+        ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: sender.to_string(),
+            amount: Uint128::new(1000),
+            msg: encode_binary(&Cw20HookMsg::FundRewardPool {}).unwrap(),
+        })
+    };
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("reward_cw20", &[]),
+        fund_msg("anyone"),
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("reward_cw20", &[]),
+        fund_msg("creator"),
+    )
+    .unwrap();
+
+    timelock_nft(
+        deps.as_mut(),
+        env.clone(),
+        "token1",
+        2 * ONE_DAY,
+        "recipient",
+    )
+    .unwrap();
+
+    // Rewards accrued before the transfer stay with the previous recipient
+    let mut later = env;
+    later.block.time = later.block.time.plus_seconds(600);
+    execute(
+        deps.as_mut(),
+        later.clone(),
+        mock_info("recipient", &[]),
+        ExecuteMsg::TransferBeneficiary {
+            cw721_contract: AndrAddr::from_string(MOCK_CW721_CONTRACT),
+            token_id: "token1".to_string(),
+            new_recipient: Recipient::from_string("new_recipient"),
+        },
+    )
+    .unwrap();
+    later.block.time = later.block.time.plus_seconds(900);
+    assert_eq!(
+        query_pending_rewards(deps.as_ref(), later.clone(), "new_recipient"),
+        vec![RewardAmount {
+            asset: asset.clone(),
+            amount: Uint128::new(900),
+        }]
+    );
+
+    // Only what is left in the pool is paid out, the rest stays owed
+    let res = execute(
+        deps.as_mut(),
+        later.clone(),
+        mock_info("recipient", &[]),
+        ExecuteMsg::ClaimRewards {
+            start_after: None,
+            limit: None,
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(WasmMsg::Execute {
+            contract_addr: "reward_cw20".to_string(),
+            msg: encode_binary(&Cw20ExecuteMsg::Transfer {
+                recipient: "recipient".to_string(),
+                amount: Uint128::new(600),
+            })
+            .unwrap(),
+            funds: vec![],
+        })]
+    );
+    let res = execute(
+        deps.as_mut(),
+        later.clone(),
+        mock_info("new_recipient", &[]),
+        ExecuteMsg::ClaimRewards {
+            start_after: None,
+            limit: None,
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(WasmMsg::Execute {
+            contract_addr: "reward_cw20".to_string(),
+            msg: encode_binary(&Cw20ExecuteMsg::Transfer {
+                recipient: "new_recipient".to_string(),
+                amount: Uint128::new(400),
+            })
+            .unwrap(),
+            funds: vec![],
+        })]
+    );
+    assert_eq!(
+        query_pending_rewards(deps.as_ref(), later.clone(), "new_recipient"),
+        vec![RewardAmount {
+            asset,
+            amount: Uint128::new(500),
+        }]
+    );
+
+    let res: RewardPoolsResponse =
+        from_json(query(deps.as_ref(), later, QueryMsg::RewardPools {}).unwrap()).unwrap();
+    assert!(res.pools.is_empty());
+}
+
+fn query_voting_power(deps: Deps, env: Env, address: &str, height: Option<u64>) -> Uint128 {
+    let res: VotingPowerResponse = from_json(
+        query(
+            deps,
+            env,
+            QueryMsg::VotingPower {
+                address: AndrAddr::from_string(address),
+                height,
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    res.power
+}
+
+fn query_total_voting_power(deps: Deps, env: Env, height: Option<u64>) -> Uint128 {
+    let res: VotingPowerResponse =
+        from_json(query(deps, env, QueryMsg::TotalVotingPower { height }).unwrap()).unwrap();
+    res.power
+}
+
+#[test]
+fn test_voting_power() {
+    let mut deps = mock_dependencies_custom(&[]);
+    let env = mock_env();
+    let start_height = env.block.height;
+    init(deps.as_mut());
+    timelock_nft(
+        deps.as_mut(),
+        env.clone(),
+        "token1",
+        3 * ONE_DAY,
+        "recipient",
+    )
+    .unwrap();
+    assert_eq!(
+        query_voting_power(deps.as_ref(), env.clone(), "recipient", None),
+        Uint128::from(3 * ONE_DAY)
+    );
+
+    // A second lock a block later adds to the total
+    let mut env = env;
+    env.block.height += 1;
+    timelock_nft(
+        deps.as_mut(),
+        env.clone(),
+        "token2",
+        5 * ONE_DAY,
+        "other_recipient",
+    )
+    .unwrap();
+    assert_eq!(
+        query_total_voting_power(deps.as_ref(), env.clone(), None),
+        Uint128::from(8 * ONE_DAY)
+    );
+
+    // Extending the lock a day later counts the remaining time from now
+    env.block.height += 1;
+    env.block.time = env.block.time.plus_seconds(ONE_DAY);
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("recipient", &[]),
+        ExecuteMsg::ExtendLock {
+            cw721_contract: AndrAddr::from_string(MOCK_CW721_CONTRACT),
+            token_id: "token1".to_string(),
+            additional_duration: MillisecondsDuration::from_seconds(2 * ONE_DAY),
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        query_voting_power(deps.as_ref(), env.clone(), "recipient", None),
+        Uint128::from(4 * ONE_DAY)
+    );
+
+    // The power follows the claim to its new recipient
+    env.block.height += 1;
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("recipient", &[]),
+        ExecuteMsg::TransferBeneficiary {
+            cw721_contract: AndrAddr::from_string(MOCK_CW721_CONTRACT),
+            token_id: "token1".to_string(),
+            new_recipient: Recipient::from_string("buyer"),
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        query_voting_power(deps.as_ref(), env.clone(), "recipient", None),
+        Uint128::zero()
+    );
+    assert_eq!(
+        query_voting_power(deps.as_ref(), env.clone(), "buyer", None),
+        Uint128::from(4 * ONE_DAY)
+    );
+
+    // Claiming gives up the power
+    env.block.height += 1;
+    env.block.time = env.block.time.plus_seconds(4 * ONE_DAY);
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("other_recipient", &[]),
+        ExecuteMsg::ClaimNft {
+            cw721_contract: AndrAddr::from_string(MOCK_CW721_CONTRACT),
+            token_id: "token2".to_string(),
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        query_total_voting_power(deps.as_ref(), env.clone(), None),
+        Uint128::from(4 * ONE_DAY)
+    );
+
+    // Historical heights see the power as it was at the start of that block
+    assert_eq!(
+        query_voting_power(deps.as_ref(), env.clone(), "recipient", Some(start_height)),
+        Uint128::zero()
+    );
+    assert_eq!(
+        query_voting_power(
+            deps.as_ref(),
+            env.clone(),
+            "recipient",
+            Some(start_height + 1)
+        ),
+        Uint128::from(3 * ONE_DAY)
+    );
+    assert_eq!(
+        query_voting_power(deps.as_ref(), env.clone(), "buyer", Some(start_height + 3)),
+        Uint128::zero()
+    );
+    assert_eq!(
+        query_total_voting_power(deps.as_ref(), env.clone(), Some(start_height + 2)),
+        Uint128::from(8 * ONE_DAY)
+    );
+    assert_eq!(
+        query_total_voting_power(deps.as_ref(), env, Some(start_height + 4)),
+        Uint128::from(9 * ONE_DAY)
+    );
+}
+
+#[test]
+fn test_voting_power_expires() {
+    let mut deps = mock_dependencies_custom(&[]);
+    let env = mock_env();
+    init(deps.as_mut());
+    claim_window_lock(deps.as_mut(), env.clone(), "token1").unwrap();
+
+    // The lock waits for its recipient to claim, so the crank does not release it, but it
+    // takes away the power once the unlock time has passed
+    let mut later_env = env.clone();
+    later_env.block.height += 1;
+    later_env.block.time = env.block.time.plus_seconds(3 * ONE_DAY);
+    let res = execute(
+        deps.as_mut(),
+        later_env.clone(),
+        mock_info("keeper", &[]),
+        ExecuteMsg::ReleaseExpired { limit: None },
+    )
+    .unwrap();
+    assert!(res.messages.is_empty());
+    assert!(res
+        .attributes
+        .contains(&Attribute::new("power_expired", "1")));
+    assert_eq!(
+        query_voting_power(deps.as_ref(), later_env.clone(), "recipient", None),
+        Uint128::zero()
+    );
+    assert_eq!(
+        query_total_voting_power(deps.as_ref(), later_env.clone(), None),
+        Uint128::zero()
+    );
+    assert_eq!(
+        query_voting_power(
+            deps.as_ref(),
+            later_env.clone(),
+            "recipient",
+            Some(later_env.block.height)
+        ),
+        Uint128::from(3 * ONE_DAY)
+    );
+
+    // Nothing is left to expire
+    let res = execute(
+        deps.as_mut(),
+        later_env,
+        mock_info("keeper", &[]),
+        ExecuteMsg::ReleaseExpired { limit: None },
+    )
+    .unwrap();
+    assert!(res
+        .attributes
+        .contains(&Attribute::new("power_expired", "0")));
+}
+
+#[test]
+fn test_voting_power_only_for_binding_locks() {
+    let mut deps = mock_dependencies_custom(&[]);
+    let env = mock_env();
+    init(deps.as_mut());
+
+    // Locks the depositor can take back, or that can unlock early at a height, carry no power
+    revocable_lock(deps.as_mut(), env.clone(), "revocable");
+    send_nft(
+        deps.as_mut(),
+        env.clone(),
+        MOCK_TOKEN_OWNER,
+        "heartbeat",
+        Cw721HookMsg::HeartbeatLockNft {
+            interval: MillisecondsDuration::from_seconds(ONE_DAY),
+            recipient: Recipient::from_string("recipient"),
+        },
+    )
+    .unwrap();
+    timelock_nft_until(
+        deps.as_mut(),
+        env.clone(),
+        "or",
+        UnlockCondition::TimeOrHeight {
+            time: Milliseconds::from_seconds(env.block.time.seconds() + 2 * ONE_DAY),
+            height: env.block.height + ONE_DAY_BLOCKS,
+        },
+    )
+    .unwrap();
+    timelock_nft_until(
+        deps.as_mut(),
+        env.clone(),
+        "height",
+        UnlockCondition::Height(env.block.height + ONE_DAY_BLOCKS),
+    )
+    .unwrap();
+    assert_eq!(
+        query_voting_power(deps.as_ref(), env.clone(), "recipient", None),
+        Uint128::zero()
+    );
+
+    // Beating the heartbeat does not give it any either
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(MOCK_TOKEN_OWNER, &[]),
+        ExecuteMsg::Heartbeat {
+            cw721_contract: AndrAddr::from_string(MOCK_CW721_CONTRACT),
+            token_id: "heartbeat".to_string(),
+        },
+    )
+    .unwrap();
+
+    // A lock that must run to its time counts, and only it does
+    timelock_nft_until(
+        deps.as_mut(),
+        env.clone(),
+        "and",
+        UnlockCondition::TimeAndHeight {
+            time: Milliseconds::from_seconds(env.block.time.seconds() + 2 * ONE_DAY),
+            height: env.block.height + ONE_DAY_BLOCKS,
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        query_voting_power(deps.as_ref(), env.clone(), "recipient", None),
+        Uint128::from(2 * ONE_DAY)
+    );
+    assert_eq!(
+        query_total_voting_power(deps.as_ref(), env, None),
+        Uint128::from(2 * ONE_DAY)
+    );
+}
+
+#[test]
+fn test_lock_history() {
+    let mut deps = mock_dependencies_custom(&[]);
+    let env = mock_env();
+    init(deps.as_mut());
+    let unlock_condition = |env: &Env, seconds: u64| {
+        UnlockCondition::Time(Milliseconds::from_seconds(
+            env.block.time.seconds() + seconds,
+        ))
+    };
+
+    // The first lock is revoked by the depositor
+    revocable_lock(deps.as_mut(), env.clone(), MOCK_UNCLAIMED_TOKEN);
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(MOCK_TOKEN_OWNER, &[]),
+        ExecuteMsg::RevokeLock {
+            cw721_contract: AndrAddr::from_string(MOCK_CW721_CONTRACT),
+            token_id: MOCK_UNCLAIMED_TOKEN.to_string(),
+            new_recipient: None,
+        },
+    )
+    .unwrap();
+
+    // The second lock is claimed by the recipient
+    let res = timelock_nft(
+        deps.as_mut(),
+        env.clone(),
+        MOCK_UNCLAIMED_TOKEN,
+        ONE_DAY,
+        "recipient",
+    )
+    .unwrap();
+    assert!(res.attributes.contains(&Attribute::new("lock_id", "2")));
+
+    // An active lock cannot be overwritten
+    let err = timelock_nft(
+        deps.as_mut(),
+        env.clone(),
+        MOCK_UNCLAIMED_TOKEN,
+        ONE_DAY,
+        "recipient",
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::LockedNFT {});
+
+    let mut claim_env = env.clone();
+    claim_env.block.time = env.block.time.plus_seconds(ONE_DAY);
+    execute(
+        deps.as_mut(),
+        claim_env.clone(),
+        mock_info("recipient", &[]),
+        ExecuteMsg::ClaimNft {
+            cw721_contract: AndrAddr::from_string(MOCK_CW721_CONTRACT),
+            token_id: MOCK_UNCLAIMED_TOKEN.to_string(),
+        },
+    )
+    .unwrap();
+
+    // The third lock is still active
+    timelock_nft(
+        deps.as_mut(),
+        claim_env.clone(),
+        MOCK_UNCLAIMED_TOKEN,
+        2 * ONE_DAY,
+        "other_recipient",
+    )
+    .unwrap();
+
+    let res: LockHistoryResponse = from_json(
+        query(
+            deps.as_ref(),
+            claim_env.clone(),
+            QueryMsg::LockHistory {
+                cw721_contract: AndrAddr::from_string(MOCK_CW721_CONTRACT),
+                token_id: MOCK_UNCLAIMED_TOKEN.to_string(),
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        res.locks,
+        vec![
+            LockRecord {
+                lock_id: 1,
+                unlock_condition: unlock_condition(&env, 3 * ONE_DAY),
+                depositor: Addr::unchecked(MOCK_TOKEN_OWNER),
+                recipient: Addr::unchecked("recipient"),
+                status: LockStatus::Revoked,
+                claimer: Some(Addr::unchecked(MOCK_TOKEN_OWNER)),
+                claimed_at: Some(env.block.time.seconds()),
+            },
+            LockRecord {
+                lock_id: 2,
+                unlock_condition: unlock_condition(&env, ONE_DAY),
+                depositor: Addr::unchecked(MOCK_TOKEN_OWNER),
+                recipient: Addr::unchecked("recipient"),
+                status: LockStatus::Claimed,
+                claimer: Some(Addr::unchecked("recipient")),
+                claimed_at: Some(claim_env.block.time.seconds()),
+            },
+            LockRecord {
+                lock_id: 3,
+                unlock_condition: unlock_condition(&claim_env, 2 * ONE_DAY),
+                depositor: Addr::unchecked(MOCK_TOKEN_OWNER),
+                recipient: Addr::unchecked("other_recipient"),
+                status: LockStatus::Locked,
+                claimer: None,
+                claimed_at: None,
+            },
+        ]
+    );
+}
+
+fn hash_lock_nft(deps: DepsMut, env: Env, token_id: &str) -> Result<Response, ContractError> {
+    send_nft(
+        deps,
+        env.clone(),
+        MOCK_TOKEN_OWNER,
+        token_id,
+        Cw721HookMsg::HashLockNft {
+            hash: HexBinary::from(Sha256::digest(b"secret").as_slice()),
+            counterparty: AndrAddr::from_string("counterparty"),
+            refund_after: Milliseconds::from_seconds(env.block.time.seconds() + ONE_DAY),
+        },
+    )
+}
+
+#[test]
+fn test_hash_lock_claim_with_preimage() {
+    let mut deps = mock_dependencies_custom(&[]);
+    let env = mock_env();
+    init(deps.as_mut());
+
+    let err = send_nft(
+        deps.as_mut(),
+        env.clone(),
+        MOCK_TOKEN_OWNER,
+        "token1",
+        Cw721HookMsg::HashLockNft {
+            hash: HexBinary::from(b"short".as_slice()),
+            counterparty: AndrAddr::from_string("counterparty"),
+            refund_after: Milliseconds::from_seconds(env.block.time.seconds() + ONE_DAY),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::UnexpectedNumberOfBytes {
+            expected: 32,
+            actual: 5
+        }
+    );
+
+    hash_lock_nft(deps.as_mut(), env.clone(), "token1").unwrap();
+
+    let claim_msg = |preimage: &[u8]| ExecuteMsg::ClaimHashLockedNft {
+        cw721_contract: AndrAddr::from_string(MOCK_CW721_CONTRACT),
+        token_id: "token1".to_string(),
+        preimage: Binary::from(preimage),
+    };
+
+    // Hash locks cannot be claimed or refunded like regular locks before the refund time
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("counterparty", &[]),
+        ExecuteMsg::ClaimNft {
+            cw721_contract: AndrAddr::from_string(MOCK_CW721_CONTRACT),
+            token_id: "token1".to_string(),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::UnsupportedOperation {});
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(MOCK_TOKEN_OWNER, &[]),
+        ExecuteMsg::RefundHashLockedNft {
+            cw721_contract: AndrAddr::from_string(MOCK_CW721_CONTRACT),
+            token_id: "token1".to_string(),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::LockedNFT {});
+
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("counterparty", &[]),
+        claim_msg(b"wrong"),
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+
+    // Anyone who knows the preimage can complete the swap for the counterparty
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("anyone", &[]),
+        claim_msg(b"secret"),
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(WasmMsg::Execute {
+            contract_addr: MOCK_CW721_CONTRACT.to_string(),
+            msg: encode_binary(&Cw721ExecuteMsg::TransferNft {
+                recipient: AndrAddr::from_string("counterparty"),
+                token_id: "token1".to_string(),
+            })
+            .unwrap(),
+            funds: vec![],
+        })]
+    );
+    assert!(res.attributes.contains(&Attribute::new(
+        "preimage",
+        HexBinary::from(b"secret").to_hex()
+    )));
+}
+
+#[test]
+fn test_hash_lock_refund() {
+    let mut deps = mock_dependencies_custom(&[]);
+    let env = mock_env();
+    init(deps.as_mut());
+    hash_lock_nft(deps.as_mut(), env.clone(), "token1").unwrap();
+
+    let mut refund_env = env.clone();
+    refund_env.block.time = env.block.time.plus_seconds(ONE_DAY);
+
+    // The preimage is useless once the refund time has passed
+    let err = execute(
+        deps.as_mut(),
+        refund_env.clone(),
+        mock_info("counterparty", &[]),
+        ExecuteMsg::ClaimHashLockedNft {
+            cw721_contract: AndrAddr::from_string(MOCK_CW721_CONTRACT),
+            token_id: "token1".to_string(),
+            preimage: Binary::from(b"secret"),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::Expired {});
+
+    let refund_msg = ExecuteMsg::RefundHashLockedNft {
+        cw721_contract: AndrAddr::from_string(MOCK_CW721_CONTRACT),
+        token_id: "token1".to_string(),
+    };
+    let err = execute(
+        deps.as_mut(),
+        refund_env.clone(),
+        mock_info("counterparty", &[]),
+        refund_msg.clone(),
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+
+    let res = execute(
+        deps.as_mut(),
+        refund_env.clone(),
+        mock_info(MOCK_TOKEN_OWNER, &[]),
+        refund_msg,
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(WasmMsg::Execute {
+            contract_addr: MOCK_CW721_CONTRACT.to_string(),
+            msg: encode_binary(&Cw721ExecuteMsg::TransferNft {
+                recipient: AndrAddr::from_string(MOCK_TOKEN_OWNER),
+                token_id: "token1".to_string(),
+            })
+            .unwrap(),
+            funds: vec![],
+        })]
+    );
+
+    let res: LockHistoryResponse = from_json(
+        query(
+            deps.as_ref(),
+            refund_env,
+            QueryMsg::LockHistory {
+                cw721_contract: AndrAddr::from_string(MOCK_CW721_CONTRACT),
+                token_id: "token1".to_string(),
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(res.locks[0].status, LockStatus::Revoked);
+}
+
+fn open_swap(deps: DepsMut, env: Env, token_id: &str) -> Result<Response, ContractError> {
+    send_nft(
+        deps,
+        env.clone(),
+        MOCK_TOKEN_OWNER,
+        token_id,
+        Cw721HookMsg::OpenSwap {
+            ask_cw721_contract: AndrAddr::from_string("other_cw721"),
+            ask_token_id: "tokenB".to_string(),
+            counterparty: Some(AndrAddr::from_string("taker")),
+            deadline: Milliseconds::from_seconds(env.block.time.seconds() + ONE_DAY),
+        },
+    )
+}
+
+fn accept_swap(
+    deps: DepsMut,
+    env: Env,
+    sender: &str,
+    cw721_contract: &str,
+    token_id: &str,
+) -> Result<Response, ContractError> {
+    let msg = ExecuteMsg::ReceiveNft(Cw721ReceiveMsg {
+        sender: sender.to_string(),
+        token_id: token_id.to_string(),
+        msg: encode_binary(&Cw721HookMsg::AcceptSwap { offer_id: 1 }).unwrap(),
+    });
+    execute(deps, env, mock_info(cw721_contract, &[]), msg)
+}
+
+#[test]
+fn test_swap() {
+    let mut deps = mock_dependencies_custom(&[]);
+    let env = mock_env();
+    init(deps.as_mut());
+    let res = open_swap(deps.as_mut(), env.clone(), "token1").unwrap();
+    assert!(res.attributes.contains(&Attribute::new("offer_id", "1")));
+
+    let res: SwapOfferResponse = from_json(
+        query(
+            deps.as_ref(),
+            env.clone(),
+            QueryMsg::SwapOffer { offer_id: 1 },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        res,
+        SwapOfferResponse {
+            offer_id: 1,
+            offer: SwapOffer {
+                maker: Addr::unchecked(MOCK_TOKEN_OWNER),
+                offered: SwapNft {
+                    cw721_contract: Addr::unchecked(MOCK_CW721_CONTRACT),
+                    token_id: "token1".to_string(),
+                },
+                ask: SwapNft {
+                    cw721_contract: Addr::unchecked("other_cw721"),
+                    token_id: "tokenB".to_string(),
+                },
+                counterparty: Some(Addr::unchecked("taker")),
+            },
+            deadline: Milliseconds::from_seconds(env.block.time.seconds() + ONE_DAY),
+        }
+    );
+
+    // The offered NFT cannot leave before the deadline other than through the swap
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(MOCK_TOKEN_OWNER, &[]),
+        ExecuteMsg::ClaimNft {
+            cw721_contract: AndrAddr::from_string(MOCK_CW721_CONTRACT),
+            token_id: "token1".to_string(),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::UnsupportedOperation {});
+
+    let err =
+        accept_swap(deps.as_mut(), env.clone(), "taker", "other_cw721", "tokenC").unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidAsset {
+            asset: "other_cw721:tokenC".to_string()
+        }
+    );
+    let err = accept_swap(
+        deps.as_mut(),
+        env.clone(),
+        "stranger",
+        "other_cw721",
+        "tokenB",
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+
+    let res = accept_swap(deps.as_mut(), env.clone(), "taker", "other_cw721", "tokenB").unwrap();
+    assert_eq!(
+        res.messages,
+        vec![
+            SubMsg::new(WasmMsg::Execute {
+                contract_addr: MOCK_CW721_CONTRACT.to_string(),
+                msg: encode_binary(&Cw721ExecuteMsg::TransferNft {
+                    recipient: AndrAddr::from_string("taker"),
+                    token_id: "token1".to_string(),
+                })
+                .unwrap(),
+                funds: vec![],
+            }),
+            SubMsg::new(WasmMsg::Execute {
+                contract_addr: "other_cw721".to_string(),
+                msg: encode_binary(&Cw721ExecuteMsg::TransferNft {
+                    recipient: AndrAddr::from_string(MOCK_TOKEN_OWNER),
+                    token_id: "tokenB".to_string(),
+                })
+                .unwrap(),
+                funds: vec![],
+            }),
+        ]
+    );
+
+    // The offer is gone once accepted
+    let err = accept_swap(deps.as_mut(), env, "taker", "other_cw721", "tokenB").unwrap_err();
+    assert_eq!(err, ContractError::NFTNotFound {});
+}
+
+#[test]
+fn test_swap_reclaim_after_deadline() {
+    let mut deps = mock_dependencies_custom(&[]);
+    let env = mock_env();
+    init(deps.as_mut());
+    open_swap(deps.as_mut(), env.clone(), "token1").unwrap();
+
+    let mut expired_env = env.clone();
+    expired_env.block.time = env.block.time.plus_seconds(ONE_DAY);
+    let err = accept_swap(
+        deps.as_mut(),
+        expired_env.clone(),
+        "taker",
+        "other_cw721",
+        "tokenB",
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::Expired {});
+
+    // The swap is not claimed, so the claim fee the maker could not pay does not apply
+    update_rate(
+        deps.as_mut(),
+        RateAction::ClaimNft,
+        Rate {
+            asset: Asset::Native("uandr".to_string()),
+            amount: Uint128::new(10),
+            recipients: vec![RateRecipient {
+                address: Addr::unchecked("treasury"),
+                share: Decimal::one(),
+            }],
+        },
+    )
+    .unwrap();
+    let err = execute(
+        deps.as_mut(),
+        expired_env.clone(),
+        mock_info(MOCK_TOKEN_OWNER, &[]),
+        ExecuteMsg::ClaimNft {
+            cw721_contract: AndrAddr::from_string(MOCK_CW721_CONTRACT),
+            token_id: "token1".to_string(),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::UnsupportedOperation {});
+    let err = execute(
+        deps.as_mut(),
+        env,
+        mock_info("anyone", &[]),
+        ExecuteMsg::ReclaimSwap { offer_id: 1 },
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::LockedNFT {});
+
+    let res = execute(
+        deps.as_mut(),
+        expired_env.clone(),
+        mock_info("anyone", &[]),
+        ExecuteMsg::ReclaimSwap { offer_id: 1 },
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(WasmMsg::Execute {
+            contract_addr: MOCK_CW721_CONTRACT.to_string(),
+            msg: encode_binary(&Cw721ExecuteMsg::TransferNft {
+                recipient: AndrAddr::from_string(MOCK_TOKEN_OWNER),
+                token_id: "token1".to_string(),
+            })
+            .unwrap(),
+            funds: vec![],
+        })]
+    );
+    let err = query(
+        deps.as_ref(),
+        expired_env.clone(),
+        QueryMsg::SwapOffer { offer_id: 1 },
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::NFTNotFound {});
+
+    let res: LockHistoryResponse = from_json(
+        query(
+            deps.as_ref(),
+            expired_env,
+            QueryMsg::LockHistory {
+                cw721_contract: AndrAddr::from_string(MOCK_CW721_CONTRACT),
+                token_id: "token1".to_string(),
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(res.locks[0].status, LockStatus::Revoked);
+}
+
+fn arbitrated_lock(
+    deps: DepsMut,
+    env: Env,
+    members: &[&str],
+    threshold: u32,
+) -> Result<Response, ContractError> {
+    send_nft(
+        deps,
+        env,
+        MOCK_TOKEN_OWNER,
+        "token1",
+        Cw721HookMsg::TimelockNft {
+            lock_duration: MillisecondsDuration::from_seconds(3 * ONE_DAY),
+            recipient: Recipient::from_string("recipient"),
+            revocable: false,
+            arbiters: Some(Arbiters {
+                members: members.iter().map(|m| AndrAddr::from_string(*m)).collect(),
+                threshold,
+            }),
+            claim_window: None,
+            receipt: false,
+        },
+    )
+}
+
+fn arbitrate(
+    deps: DepsMut,
+    env: Env,
+    arbiter: &str,
+    decision: ArbiterDecision,
+) -> Result<Response, ContractError> {
+    execute(
+        deps,
+        env,
+        mock_info(arbiter, &[]),
+        ExecuteMsg::ArbitrateLock {
+            cw721_contract: AndrAddr::from_string(MOCK_CW721_CONTRACT),
+            token_id: "token1".to_string(),
+            decision,
+            reason: format!("{arbiter} ruling"),
+        },
+    )
+}
+
+#[test]
+fn test_arbiters_freeze_and_refund() {
+    let mut deps = mock_dependencies_custom(&[]);
+    let env = mock_env();
+    init(deps.as_mut());
+
+    // Duplicate members only count once
+    let err =
+        arbitrated_lock(deps.as_mut(), env.clone(), &["arbiter1", "arbiter1"], 2).unwrap_err();
+    assert_eq!(err, ContractError::InvalidWeight {});
+    arbitrated_lock(
+        deps.as_mut(),
+        env.clone(),
+        &["arbiter1", "arbiter2", "arbiter3"],
+        2,
+    )
+    .unwrap();
+
+    let err = arbitrate(
+        deps.as_mut(),
+        env.clone(),
+        "recipient",
+        ArbiterDecision::Freeze,
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+
+    let res = arbitrate(
+        deps.as_mut(),
+        env.clone(),
+        "arbiter1",
+        ArbiterDecision::Freeze,
+    )
+    .unwrap();
+    assert!(res
+        .attributes
+        .contains(&Attribute::new("executed", "false")));
+    let res = arbitrate(
+        deps.as_mut(),
+        env.clone(),
+        "arbiter2",
+        ArbiterDecision::Freeze,
+    )
+    .unwrap();
+    assert_eq!(
+        res.events,
+        vec![
+            Event::new("arbiter_vote")
+                .add_attribute("cw721_contract", MOCK_CW721_CONTRACT)
+                .add_attribute("token_id", "token1")
+                .add_attribute("lock_id", "1")
+                .add_attribute("arbiter", "arbiter2")
+                .add_attribute("decision", "freeze")
+                .add_attribute("reason", "arbiter2 ruling"),
+            Event::new("arbiter_decision")
+                .add_attribute("cw721_contract", MOCK_CW721_CONTRACT)
+                .add_attribute("token_id", "token1")
+                .add_attribute("lock_id", "1")
+                .add_attribute("decision", "freeze")
+                .add_attribute("reason", "arbiter2 ruling"),
+        ]
+    );
+
+    // The frozen NFT stays in the contract past the unlock time
+    let mut later_env = env.clone();
+    later_env.block.time = env.block.time.plus_seconds(3 * ONE_DAY);
+    let err = execute(
+        deps.as_mut(),
+        later_env.clone(),
+        mock_info("recipient", &[]),
+        ExecuteMsg::ClaimNft {
+            cw721_contract: AndrAddr::from_string(MOCK_CW721_CONTRACT),
+            token_id: "token1".to_string(),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::Paused {});
+
+    // Votes before the freeze was carried out no longer count, and arbiters can change their vote
+    let res = arbitrate(
+        deps.as_mut(),
+        later_env.clone(),
+        "arbiter1",
+        ArbiterDecision::Release,
+    )
+    .unwrap();
+    assert!(res
+        .attributes
+        .contains(&Attribute::new("executed", "false")));
+    arbitrate(
+        deps.as_mut(),
+        later_env.clone(),
+        "arbiter1",
+        ArbiterDecision::Refund,
+    )
+    .unwrap();
+    let res = arbitrate(
+        deps.as_mut(),
+        later_env.clone(),
+        "arbiter3",
+        ArbiterDecision::Refund,
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(WasmMsg::Execute {
+            contract_addr: MOCK_CW721_CONTRACT.to_string(),
+            msg: encode_binary(&Cw721ExecuteMsg::TransferNft {
+                recipient: AndrAddr::from_string(MOCK_TOKEN_OWNER),
+                token_id: "token1".to_string(),
+            })
+            .unwrap(),
+            funds: vec![],
+        })]
+    );
+
+    let res: ArbiterVotesResponse = from_json(
+        query(
+            deps.as_ref(),
+            later_env,
+            QueryMsg::ArbiterVotes { lock_id: 1 },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        res.votes
+            .iter()
+            .map(|vote| (vote.arbiter.as_str(), vote.executed))
+            .collect::<Vec<_>>(),
+        vec![
+            ("arbiter1", false),
+            ("arbiter2", true),
+            ("arbiter1", false),
+            ("arbiter1", false),
+            ("arbiter3", true),
+        ]
+    );
+}
+
+#[test]
+fn test_arbiter_early_release() {
+    let mut deps = mock_dependencies_custom(&[]);
+    let env = mock_env();
+    init(deps.as_mut());
+    update_rate(
+        deps.as_mut(),
+        RateAction::ClaimNft,
+        Rate {
+            asset: Asset::Native("uandr".to_string()),
+            amount: Uint128::new(10),
+            recipients: vec![RateRecipient {
+                address: Addr::unchecked("treasury"),
+                share: Decimal::one(),
+            }],
+        },
+    )
+    .unwrap();
+    arbitrated_lock(deps.as_mut(), env.clone(), &["arbiter"], 1).unwrap();
+
+    // The recipient pays the claim fee for an arbiter release
+    let err = arbitrate(
+        deps.as_mut(),
+        env.clone(),
+        "arbiter",
+        ArbiterDecision::Release,
+    )
+    .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidFunds {
+            msg: "Fee balance of recipient does not cover the claim_nft fee of 10 native:uandr"
+                .to_string(),
+        }
+    );
+
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("recipient", &[coin(10, "uandr")]),
+        ExecuteMsg::DepositFeeBalance { recipient: None },
+    )
+    .unwrap();
+    let res = arbitrate(deps.as_mut(), env, "arbiter", ArbiterDecision::Release).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![
+            SubMsg::new(WasmMsg::Execute {
+                contract_addr: MOCK_CW721_CONTRACT.to_string(),
+                msg: encode_binary(&Cw721ExecuteMsg::TransferNft {
+                    recipient: AndrAddr::from_string("recipient"),
+                    token_id: "token1".to_string(),
+                })
+                .unwrap(),
+                funds: vec![],
+            }),
+            SubMsg::new(BankMsg::Send {
+                to_address: "treasury".to_string(),
+                amount: vec![coin(10, "uandr")],
+            }),
+        ]
+    );
+}
+
+#[test]
+fn test_early_unlock() {
+    let mut deps = mock_dependencies_custom(&[]);
+    let env = mock_env();
+    init(deps.as_mut());
+    timelock_nft(
+        deps.as_mut(),
+        env.clone(),
+        "token1",
+        3 * ONE_DAY,
+        "recipient",
+    )
+    .unwrap();
+
+    let early_unlock_msg = ExecuteMsg::EarlyUnlock {
+        cw721_contract: AndrAddr::from_string(MOCK_CW721_CONTRACT),
+        token_id: "token1".to_string(),
+    };
+    // Early unlocks are only available if the lock policy allows them
+    assert_eq!(
+        query_is_locked(deps.as_ref(), env.clone(), "token1").early_unlock_price,
+        None
+    );
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("recipient", &[]),
+        early_unlock_msg.clone(),
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::UnsupportedOperation {});
+
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("creator", &[]),
+        ExecuteMsg::UpdateLockPolicy {
+            cw721_contract: Some(AndrAddr::from_string(MOCK_CW721_CONTRACT)),
+            policy: Some(LockPolicy {
+                min_duration: MillisecondsDuration::from_seconds(ONE_DAY),
+                max_duration: MillisecondsDuration::from_seconds(ONE_YEAR),
+                allow_revocable: false,
+                forced_recipient: None,
+                early_unlock: Some(EarlyUnlockPolicy {
+                    caller: EarlyUnlockCaller::Recipient,
+                    treasury: Recipient::from_string("treasury"),
+                    penalty: PenaltyCurve {
+                        denom: "uandr".to_string(),
+                        base: Uint128::new(100),
+                        per_second: Uint128::one(),
+                    },
+                }),
+                height_limits: HeightLimits::default(),
+            }),
+        },
+    )
+    .unwrap();
+
+    // The price goes down as the unlock time gets closer
+    let mut later_env = env.clone();
+    later_env.block.time = env.block.time.plus_seconds(ONE_DAY);
+    let price = (100 + 2 * ONE_DAY) as u128;
+    assert_eq!(
+        query_is_locked(deps.as_ref(), later_env.clone(), "token1").early_unlock_price,
+        Some(coin(price, "uandr"))
+    );
+
+    let err = execute(
+        deps.as_mut(),
+        later_env.clone(),
+        mock_info(MOCK_TOKEN_OWNER, &[coin(price, "uandr")]),
+        early_unlock_msg.clone(),
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+
+    let err = execute(
+        deps.as_mut(),
+        later_env.clone(),
+        mock_info("recipient", &[coin(price - 1, "uandr")]),
+        early_unlock_msg.clone(),
+    )
+    .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidFunds {
+            msg: format!("Early unlock requires a penalty of {price}uandr"),
+        }
+    );
+
+    // Anything paid above the penalty is refunded
+    let res = execute(
+        deps.as_mut(),
+        later_env,
+        mock_info("recipient", &[coin(price + 50, "uandr")]),
+        early_unlock_msg,
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![
+            SubMsg::new(WasmMsg::Execute {
+                contract_addr: MOCK_CW721_CONTRACT.to_string(),
+                msg: encode_binary(&Cw721ExecuteMsg::TransferNft {
+                    recipient: AndrAddr::from_string("recipient"),
+                    token_id: "token1".to_string(),
+                })
+                .unwrap(),
+                funds: vec![],
+            }),
+            SubMsg::new(BankMsg::Send {
+                to_address: "treasury".to_string(),
+                amount: vec![coin(price, "uandr")],
+            }),
+            SubMsg::new(BankMsg::Send {
+                to_address: "recipient".to_string(),
+                amount: vec![coin(50, "uandr")],
+            }),
+        ]
+    );
+}
+
+fn query_fee_balance(deps: Deps, env: Env, address: &str) -> Vec<AssetAmount> {
+    let res: FeeBalanceResponse = from_json(
+        query(
+            deps,
+            env,
+            QueryMsg::FeeBalance {
+                address: AndrAddr::from_string(address),
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    res.balances
+}
+
+fn update_rate(deps: DepsMut, action: RateAction, rate: Rate) -> Result<Response, ContractError> {
+    execute(
+        deps,
+        mock_env(),
+        mock_info("creator", &[]),
+        ExecuteMsg::UpdateRate {
+            action,
+            rate: Some(rate),
+        },
+    )
+}
+
+#[test]
+fn test_lock_and_claim_fees() {
+    let mut deps = mock_dependencies_custom(&[]);
+    let env = mock_env();
+    init(deps.as_mut());
+
+    let lock_rate = Rate {
+        asset: Asset::Native("uandr".to_string()),
+        amount: Uint128::new(100),
+        recipients: vec![
+            RateRecipient {
+                address: Addr::unchecked("treasury"),
+                share: Decimal::percent(70),
+            },
+            RateRecipient {
+                address: Addr::unchecked("dev"),
+                share: Decimal::percent(30),
+            },
+        ],
+    };
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("anyone", &[]),
+        ExecuteMsg::UpdateRate {
+            action: RateAction::TimelockNft,
+            rate: Some(lock_rate.clone()),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+
+    let mut uneven_rate = lock_rate.clone();
+    uneven_rate.recipients[1].share = Decimal::percent(20);
+    let err = update_rate(deps.as_mut(), RateAction::TimelockNft, uneven_rate).unwrap_err();
+    assert_eq!(err, ContractError::InvalidRate {});
+
+    update_rate(deps.as_mut(), RateAction::TimelockNft, lock_rate).unwrap();
+    update_rate(
+        deps.as_mut(),
+        RateAction::ClaimNft,
+        Rate {
+            asset: Asset::Native("uandr".to_string()),
+            amount: Uint128::new(10),
+            recipients: vec![RateRecipient {
+                address: Addr::unchecked("treasury"),
+                share: Decimal::one(),
+            }],
+        },
+    )
+    .unwrap();
+
+    let simulated: SimulateFeeResponse = from_json(
+        query(
+            deps.as_ref(),
+            env.clone(),
+            QueryMsg::SimulateFee {
+                action: RateAction::TimelockNft,
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        simulated,
+        SimulateFeeResponse {
+            fee: Some(AssetAmount {
+                asset: Asset::Native("uandr".to_string()),
+                amount: Uint128::new(100),
+            }),
+            payouts: vec![
+                (Addr::unchecked("treasury"), Uint128::new(70)),
+                (Addr::unchecked("dev"), Uint128::new(30)),
+            ],
+        }
+    );
+
+    // The lock fee is taken from the depositor's prepaid balance
+    let err = timelock_nft(
+        deps.as_mut(),
+        env.clone(),
+        "token1",
+        3 * ONE_DAY,
+        "recipient",
+    )
+    .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidFunds {
+            msg: format!(
+                "Fee balance of {MOCK_TOKEN_OWNER} does not cover the timelock_nft fee of 100 native:uandr"
+            ),
+        }
+    );
+
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(MOCK_TOKEN_OWNER, &[coin(150, "uandr")]),
+        ExecuteMsg::DepositFeeBalance { recipient: None },
+    )
+    .unwrap();
+    let res = timelock_nft(
+        deps.as_mut(),
+        env.clone(),
+        "token1",
+        3 * ONE_DAY,
+        "recipient",
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![
+            SubMsg::new(BankMsg::Send {
+                to_address: "treasury".to_string(),
+                amount: vec![coin(70, "uandr")],
+            }),
+            SubMsg::new(BankMsg::Send {
+                to_address: "dev".to_string(),
+                amount: vec![coin(30, "uandr")],
+            }),
+        ]
+    );
+    assert_eq!(
+        query_fee_balance(deps.as_ref(), env.clone(), MOCK_TOKEN_OWNER),
+        vec![AssetAmount {
+            asset: Asset::Native("uandr".to_string()),
+            amount: Uint128::new(50),
+        }]
+    );
+
+    // The claim fee can be attached to the claim
+    let mut later_env = env.clone();
+    later_env.block.time = env.block.time.plus_seconds(3 * ONE_DAY);
+    let claim_msg = ExecuteMsg::ClaimNft {
+        cw721_contract: AndrAddr::from_string(MOCK_CW721_CONTRACT),
+        token_id: "token1".to_string(),
+    };
+    let err = execute(
+        deps.as_mut(),
+        later_env.clone(),
+        mock_info("recipient", &[]),
+        claim_msg.clone(),
+    )
+    .unwrap_err();
+    assert!(matches!(err, ContractError::InvalidFunds { .. }));
+
+    let res = execute(
+        deps.as_mut(),
+        later_env.clone(),
+        mock_info("recipient", &[coin(10, "uandr")]),
+        claim_msg,
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![
+            SubMsg::new(WasmMsg::Execute {
+                contract_addr: MOCK_CW721_CONTRACT.to_string(),
+                msg: encode_binary(&Cw721ExecuteMsg::TransferNft {
+                    recipient: AndrAddr::from_string("recipient"),
+                    token_id: "token1".to_string(),
+                })
+                .unwrap(),
+                funds: vec![],
+            }),
+            SubMsg::new(BankMsg::Send {
+                to_address: "treasury".to_string(),
+                amount: vec![coin(10, "uandr")],
+            }),
+        ]
+    );
+    assert!(query_fee_balance(deps.as_ref(), later_env.clone(), "recipient").is_empty());
+
+    // Unused balances can be withdrawn
+    let res = execute(
+        deps.as_mut(),
+        later_env.clone(),
+        mock_info(MOCK_TOKEN_OWNER, &[]),
+        ExecuteMsg::WithdrawFeeBalance {
+            asset: Asset::Native("uandr".to_string()),
+            amount: None,
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(BankMsg::Send {
+            to_address: MOCK_TOKEN_OWNER.to_string(),
+            amount: vec![coin(50, "uandr")],
+        })]
+    );
+    assert!(query_fee_balance(deps.as_ref(), later_env, MOCK_TOKEN_OWNER).is_empty());
+}
+
+#[test]
+fn test_claim_fee_fixed_at_lock_creation() {
+    let mut deps = mock_dependencies_custom(&[]);
+    let env = mock_env();
+    init(deps.as_mut());
+
+    timelock_nft(deps.as_mut(), env.clone(), "token1", ONE_DAY, "recipient").unwrap();
+    update_rate(
+        deps.as_mut(),
+        RateAction::ClaimNft,
+        Rate {
+            asset: Asset::Native("uandr".to_string()),
+            amount: Uint128::new(10),
+            recipients: vec![RateRecipient {
+                address: Addr::unchecked("treasury"),
+                share: Decimal::one(),
+            }],
+        },
+    )
+    .unwrap();
+    timelock_nft(deps.as_mut(), env.clone(), "token2", ONE_DAY, "recipient").unwrap();
+
+    let claim = |token_id: &str| ExecuteMsg::ClaimNft {
+        cw721_contract: AndrAddr::from_string(MOCK_CW721_CONTRACT),
+        token_id: token_id.to_string(),
+    };
+    let mut claim_env = env;
+    claim_env.block.time = claim_env.block.time.plus_seconds(ONE_DAY);
+
+    // token1 was locked before the rate was set, so it is claimed for free
+    let res = execute(
+        deps.as_mut(),
+        claim_env.clone(),
+        mock_info("recipient", &[]),
+        claim("token1"),
+    )
+    .unwrap();
+    assert_eq!(res.messages.len(), 1);
+    let err = execute(
+        deps.as_mut(),
+        claim_env.clone(),
+        mock_info("recipient", &[]),
+        claim("token2"),
+    )
+    .unwrap_err();
+    assert!(matches!(err, ContractError::InvalidFunds { .. }));
+
+    // Removing the rate does not waive the fee of locks created while it was set
+    execute(
+        deps.as_mut(),
+        claim_env.clone(),
+        mock_info("creator", &[]),
+        ExecuteMsg::UpdateRate {
+            action: RateAction::ClaimNft,
+            rate: None,
+        },
+    )
+    .unwrap();
+    let res = execute(
+        deps.as_mut(),
+        claim_env,
+        mock_info("recipient", &[coin(10, "uandr")]),
+        claim("token2"),
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages[1],
+        SubMsg::new(BankMsg::Send {
+            to_address: "treasury".to_string(),
+            amount: vec![coin(10, "uandr")],
+        })
+    );
+}
+
+#[test]
+fn test_cw20_claim_fee_skipped_by_keepers() {
+    let mut deps = mock_dependencies_custom(&[]);
+    let env = mock_env();
+    init(deps.as_mut());
+
+    update_rate(
+        deps.as_mut(),
+        RateAction::ClaimNft,
+        Rate {
+            asset: Asset::Cw20(Addr::unchecked("fee_token")),
+            amount: Uint128::new(5),
+            recipients: vec![RateRecipient {
+                address: Addr::unchecked("treasury"),
+                share: Decimal::one(),
+            }],
+        },
+    )
+    .unwrap();
+    timelock_nft(deps.as_mut(), env.clone(), "token1", ONE_DAY, "alice").unwrap();
+    timelock_nft(deps.as_mut(), env.clone(), "token2", ONE_DAY, "bob").unwrap();
+
+    // Anyone can top up someone else's balance
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("fee_token", &[]),
+        ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: "sponsor".to_string(),
+            amount: Uint128::new(5),
+            msg: encode_binary(&Cw20HookMsg::DepositFeeBalance {
+                recipient: Some(AndrAddr::from_string("bob")),
+            })
+            .unwrap(),
+        }),
+    )
+    .unwrap();
+
+    let mut later_env = env.clone();
+    later_env.block.time = env.block.time.plus_seconds(ONE_DAY);
+    let res = execute(
+        deps.as_mut(),
+        later_env.clone(),
+        mock_info("keeper", &[]),
+        ExecuteMsg::ReleaseExpired { limit: None },
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::reply_always(
+            WasmMsg::Execute {
+                contract_addr: MOCK_CW721_CONTRACT.to_string(),
+                msg: encode_binary(&Cw721ExecuteMsg::TransferNft {
+                    recipient: AndrAddr::from_string("bob"),
+                    token_id: "token2".to_string(),
+                })
+                .unwrap(),
+                funds: vec![],
+            },
+            2
+        )]
+    );
+
+    // The fee is paid out once the NFT has arrived
+    let reply_res = reply(
+        deps.as_mut(),
+        later_env.clone(),
+        Reply {
+            id: 2,
+            result: SubMsgResult::Ok(SubMsgResponse {
+                events: vec![],
+                data: None,
+            }),
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        reply_res.messages,
+        vec![SubMsg::new(WasmMsg::Execute {
+            contract_addr: "fee_token".to_string(),
+            msg: encode_binary(&Cw20ExecuteMsg::Transfer {
+                recipient: "treasury".to_string(),
+                amount: Uint128::new(5),
+            })
+            .unwrap(),
+            funds: vec![],
+        })]
+    );
+    assert!(res
+        .attributes
+        .contains(&Attribute::new("released", "1".to_string())));
+
+    // Alice's lock is set aside, so the keepers leave it even once she can pay the fee
+    assert!(!query_is_locked(deps.as_ref(), later_env.clone(), "token1").is_locked);
+    execute(
+        deps.as_mut(),
+        later_env.clone(),
+        mock_info("fee_token", &[]),
+        ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: "alice".to_string(),
+            amount: Uint128::new(5),
+            msg: encode_binary(&Cw20HookMsg::DepositFeeBalance { recipient: None }).unwrap(),
+        }),
+    )
+    .unwrap();
+    let res = execute(
+        deps.as_mut(),
+        later_env.clone(),
+        mock_info("keeper", &[]),
+        ExecuteMsg::ReleaseExpired { limit: None },
+    )
+    .unwrap();
+    assert!(res.messages.is_empty());
+    execute(
+        deps.as_mut(),
+        later_env,
+        mock_info("alice", &[]),
+        ExecuteMsg::ClaimNft {
+            cw721_contract: AndrAddr::from_string(MOCK_CW721_CONTRACT),
+            token_id: "token1".to_string(),
+        },
+    )
+    .unwrap();
+}
+
+#[test]
+fn test_release_expired_scan_limit() {
+    let mut deps = mock_dependencies_custom(&[]);
+    let env = mock_env();
+    init(deps.as_mut());
+
+    // token1 unlocks first but also waits for a block height, so it is left to its recipient
+    timelock_nft_until(
+        deps.as_mut(),
+        env.clone(),
+        "token1",
+        UnlockCondition::TimeAndHeight {
+            time: Milliseconds::from_seconds(env.block.time.seconds() + ONE_DAY),
+            height: env.block.height + ONE_DAY_BLOCKS,
+        },
+    )
+    .unwrap();
+    timelock_nft(
+        deps.as_mut(),
+        env.clone(),
+        "token2",
+        2 * ONE_DAY,
+        "recipient",
+    )
+    .unwrap();
+    claim_window_lock(deps.as_mut(), env.clone(), "token3").unwrap();
+
+    let mut later_env = env.clone();
+    later_env.block.time = env.block.time.plus_seconds(3 * ONE_DAY);
+    let release = |deps: DepsMut, env: Env, limit: u32| {
+        execute(
+            deps,
+            env,
+            mock_info("keeper", &[]),
+            ExecuteMsg::ReleaseExpired { limit: Some(limit) },
+        )
+        .unwrap()
+    };
+
+    // The one lock looked at is released: neither token1 nor the claim window lock use up the
+    // budget
+    let res = release(deps.as_mut(), later_env.clone(), 1);
+    assert_eq!(res.messages.len(), 1);
+    assert!(res.attributes.contains(&Attribute::new("released", "1")));
+    let cw721_contract = Addr::unchecked(MOCK_CW721_CONTRACT);
+    assert!(!timelocks().has(deps.as_ref().storage, (&cw721_contract, "token2")));
+    assert!(timelocks().has(deps.as_ref().storage, (&cw721_contract, "token3")));
+
+    later_env.block.height += ONE_DAY_BLOCKS;
+    let res = release(deps.as_mut(), later_env.clone(), 30);
+    assert!(res.attributes.contains(&Attribute::new("released", "0")));
+    execute(
+        deps.as_mut(),
+        later_env,
+        mock_info("recipient", &[]),
+        ExecuteMsg::ClaimNft {
+            cw721_contract: AndrAddr::from_string(MOCK_CW721_CONTRACT),
+            token_id: "token1".to_string(),
+        },
+    )
+    .unwrap();
+}
+
+#[test]
+fn test_release_due_budget_and_failures() {
+    let mut deps = mock_dependencies_custom(&[]);
+    let env = mock_env();
+    init(deps.as_mut());
+
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(MOCK_TOKEN_OWNER, &[]),
+        ExecuteMsg::CreateSchedule {
+            recipient: Recipient::from_string("recipient"),
+            cliff: MillisecondsDuration::from_seconds(ONE_DAY),
+            period: MillisecondsDuration::from_seconds(ONE_DAY),
+            per_period: 1,
+        },
+    )
+    .unwrap();
+    send_nft(
+        deps.as_mut(),
+        env.clone(),
+        MOCK_TOKEN_OWNER,
+        "token1",
+        Cw721HookMsg::AddToSchedule { schedule_id: 1 },
+    )
+    .unwrap();
+    timelock_nft(deps.as_mut(), env.clone(), "token2", ONE_DAY, "recipient").unwrap();
+    claim_window_lock(deps.as_mut(), env.clone(), "token3").unwrap();
+    // token1 can no longer be released
+    SCHEDULES.remove(deps.as_mut().storage, 1);
+
+    let mut later_env = env.clone();
+    later_env.block.height += 1;
+    later_env.block.time = env.block.time.plus_seconds(3 * ONE_DAY);
+    let release_due = |deps: DepsMut, limit: u32| {
+        sudo(
+            deps,
+            later_env.clone(),
+            SudoMsg::ReleaseDue { limit: Some(limit) },
+        )
+        .unwrap()
+    };
+
+    // The failed lock does not hold up the others, and the budget covers both locks looked
+    // at, so there is none left to take voting power from token3
+    let res = release_due(deps.as_mut(), 2);
+    assert_eq!(res.events.len(), 1);
+    assert_eq!(res.events[0].ty, "release_failed");
+    assert!(res.events[0]
+        .attributes
+        .contains(&Attribute::new("token_id", "token1")));
+    assert_eq!(res.messages.len(), 1);
+    assert_eq!(
+        res.attributes,
+        vec![
+            Attribute::new("method", "release_due"),
+            Attribute::new("released", "1"),
+            Attribute::new("power_expired", "0"),
+        ]
+    );
+
+    // token1 was set aside, so the next call only expires voting power
+    let res = release_due(deps.as_mut(), 1);
+    assert!(res.events.is_empty());
+    assert!(res.messages.is_empty());
+    assert_eq!(
+        res.attributes,
+        vec![
+            Attribute::new("method", "release_due"),
+            Attribute::new("released", "0"),
+            Attribute::new("power_expired", "1"),
+        ]
+    );
+}
+
+#[test]
+fn test_release_due_not_held_up_by_height_locks() {
+    let mut deps = mock_dependencies_custom(&[]);
+    let env = mock_env();
+    init(deps.as_mut());
+
+    // More locks waiting for a far off height than a call looks at unlock first
+    for i in 0..3 {
+        timelock_nft_until(
+            deps.as_mut(),
+            env.clone(),
+            &format!("height{i}"),
+            UnlockCondition::TimeAndHeight {
+                time: Milliseconds::from_seconds(env.block.time.seconds() + ONE_DAY),
+                height: env.block.height + HeightLimits::default().max_blocks,
+            },
+        )
+        .unwrap();
+    }
+    for i in 0..3 {
+        timelock_nft(
+            deps.as_mut(),
+            env.clone(),
+            &format!("time{i}"),
+            2 * ONE_DAY,
+            "recipient",
+        )
+        .unwrap();
+    }
+
+    let mut later_env = env.clone();
+    later_env.block.height += 1;
+    later_env.block.time = env.block.time.plus_seconds(3 * ONE_DAY);
+    let mut released = vec![];
+    for _ in 0..3 {
+        let res = sudo(
+            deps.as_mut(),
+            later_env.clone(),
+            SudoMsg::ReleaseDue { limit: Some(2) },
+        )
+        .unwrap();
+        released.push(
+            res.attributes
+                .into_iter()
+                .find(|attr| attr.key == "released")
+                .unwrap()
+                .value,
+        );
+    }
+    assert_eq!(released, vec!["2", "1", "0"]);
+
+    let cw721_contract = Addr::unchecked(MOCK_CW721_CONTRACT);
+    for i in 0..3 {
+        let time_lock = format!("time{i}");
+        let height_lock = format!("height{i}");
+        assert!(!timelocks().has(deps.as_ref().storage, (&cw721_contract, &time_lock)));
+        assert!(timelocks().has(deps.as_ref().storage, (&cw721_contract, &height_lock)));
+    }
+}
+
+#[test]
+fn test_release_expired_records_failed_delivery() {
+    let mut deps = mock_dependencies_custom(&[]);
+    let env = mock_env();
+    init(deps.as_mut());
+    update_rate(
+        deps.as_mut(),
+        RateAction::ClaimNft,
+        Rate {
+            asset: Asset::Native("uandr".to_string()),
+            amount: Uint128::new(5),
+            recipients: vec![RateRecipient {
+                address: Addr::unchecked("treasury"),
+                share: Decimal::one(),
+            }],
+        },
+    )
+    .unwrap();
+    timelock_nft(deps.as_mut(), env.clone(), "token1", ONE_DAY, "recipient").unwrap();
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("recipient", &[coin(5, "uandr")]),
+        ExecuteMsg::DepositFeeBalance { recipient: None },
+    )
+    .unwrap();
+
+    let mut later_env = env.clone();
+    later_env.block.time = env.block.time.plus_seconds(ONE_DAY);
+    execute(
+        deps.as_mut(),
+        later_env.clone(),
+        mock_info("keeper", &[]),
+        ExecuteMsg::ReleaseExpired { limit: None },
+    )
+    .unwrap();
+
+    let err = reply(
+        deps.as_mut(),
+        later_env.clone(),
+        Reply {
+            id: 2,
+            result: SubMsgResult::Err("rejected".to_string()),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::InvalidReplyId {});
+    let res = reply(
+        deps.as_mut(),
+        later_env.clone(),
+        Reply {
+            id: 1,
+            result: SubMsgResult::Err("rejected".to_string()),
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        res.events,
+        vec![Event::new("delivery_failed")
+            .add_attribute("cw721_contract", MOCK_CW721_CONTRACT)
+            .add_attribute("token_id", "token1")
+            .add_attribute("recipient", "recipient")
+            .add_attribute("reason", "rejected")]
+    );
+
+    // The fee taken for the delivery is given back, and the history shows the NFT is still here
+    let fee_balance = vec![AssetAmount {
+        asset: Asset::Native("uandr".to_string()),
+        amount: Uint128::new(5),
+    }];
+    assert_eq!(
+        query_fee_balance(deps.as_ref(), later_env.clone(), "recipient"),
+        fee_balance
+    );
+    let history = |deps: Deps| -> Vec<LockRecord> {
+        let res: LockHistoryResponse = from_json(
+            query(
+                deps,
+                mock_env(),
+                QueryMsg::LockHistory {
+                    cw721_contract: AndrAddr::from_string(MOCK_CW721_CONTRACT),
+                    token_id: "token1".to_string(),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        res.locks
+    };
+    let mut record = LockRecord {
+        lock_id: 1,
+        unlock_condition: UnlockCondition::Time(Milliseconds::from_seconds(
+            env.block.time.seconds() + ONE_DAY,
+        )),
+        depositor: Addr::unchecked(MOCK_TOKEN_OWNER),
+        recipient: Addr::unchecked("recipient"),
+        status: LockStatus::Undelivered,
+        claimer: None,
+        claimed_at: None,
+    };
+    assert_eq!(history(deps.as_ref()), vec![record.clone()]);
+
+    let claim_msg = ExecuteMsg::ClaimUndelivered {
+        cw721_contract: AndrAddr::from_string(MOCK_CW721_CONTRACT),
+        token_id: "token1".to_string(),
+    };
+    let err = execute(
+        deps.as_mut(),
+        later_env.clone(),
+        mock_info("anyone", &[]),
+        claim_msg.clone(),
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+    let res = execute(
+        deps.as_mut(),
+        later_env.clone(),
+        mock_info("recipient", &[]),
+        claim_msg.clone(),
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![
+            SubMsg::new(WasmMsg::Execute {
+                contract_addr: MOCK_CW721_CONTRACT.to_string(),
+                msg: encode_binary(&Cw721ExecuteMsg::TransferNft {
+                    recipient: AndrAddr::from_string("recipient"),
+                    token_id: "token1".to_string(),
+                })
+                .unwrap(),
+                funds: vec![],
+            }),
+            SubMsg::new(BankMsg::Send {
+                to_address: "treasury".to_string(),
+                amount: vec![coin(5, "uandr")],
+            }),
+        ]
+    );
+    assert!(query_fee_balance(deps.as_ref(), later_env.clone(), "recipient").is_empty());
+    record.status = LockStatus::Claimed;
+    record.claimer = Some(Addr::unchecked("recipient"));
+    record.claimed_at = Some(later_env.block.time.seconds());
+    assert_eq!(history(deps.as_ref()), vec![record]);
+    let err = execute(
+        deps.as_mut(),
+        later_env,
+        mock_info("recipient", &[]),
+        claim_msg,
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::NFTNotFound {});
+}
+
+fn set_permission(deps: DepsMut, actor: &str, action: &str, permission: Permission) {
+    execute(
+        deps,
+        mock_env(),
+        mock_info("creator", &[]),
+        ExecuteMsg::Permissioning(PermissioningMessage::SetPermission {
+            actor: AndrAddr::from_string(actor),
+            action: action.to_string(),
+            permission,
+        }),
+    )
+    .unwrap();
+}
+
+#[test]
+fn test_depositor_and_recipient_lists() {
+    let mut deps = mock_dependencies_custom(&[]);
+    let env = mock_env();
+    init(deps.as_mut());
+
+    // Denied depositors cannot create locks
+    set_permission(
+        deps.as_mut(),
+        MOCK_TOKEN_OWNER,
+        "DEPOSIT",
+        Permission::Blacklisted(None),
+    );
+    let err = timelock_nft(
+        deps.as_mut(),
+        env.clone(),
+        "token1",
+        3 * ONE_DAY,
+        "recipient",
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+    let err = hash_lock_nft(deps.as_mut(), env.clone(), "token1").unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("creator", &[]),
+        ExecuteMsg::Permissioning(PermissioningMessage::RemovePermission {
+            action: "DEPOSIT".to_string(),
+            actor: AndrAddr::from_string(MOCK_TOKEN_OWNER),
+        }),
+    )
+    .unwrap();
+
+    // Once the action is permissioned only allowed recipients can receive locks
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("creator", &[]),
+        ExecuteMsg::Permissioning(PermissioningMessage::PermissionAction {
+            action: "RECEIVE_LOCK".to_string(),
+        }),
+    )
+    .unwrap();
+    set_permission(
+        deps.as_mut(),
+        "recipient",
+        "RECEIVE_LOCK",
+        Permission::Whitelisted(None),
+    );
+    let err = timelock_nft(deps.as_mut(), env.clone(), "token1", 3 * ONE_DAY, "other").unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+    timelock_nft(
+        deps.as_mut(),
+        env.clone(),
+        "token1",
+        3 * ONE_DAY,
+        "recipient",
+    )
+    .unwrap();
+
+    // Nor can a lock be handed over to them
+    let err = execute(
+        deps.as_mut(),
+        env,
+        mock_info("recipient", &[]),
+        ExecuteMsg::TransferBeneficiary {
+            cw721_contract: AndrAddr::from_string(MOCK_CW721_CONTRACT),
+            token_id: "token1".to_string(),
+            new_recipient: Recipient::from_string("other"),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+}
+
+#[test]
+fn test_denied_recipients_cannot_take_swaps_or_receipts() {
+    let mut deps = mock_dependencies_custom(&[]);
+    let env = mock_env();
+    init(deps.as_mut());
+
+    // A swap cannot be offered to a denied counterparty
+    set_permission(
+        deps.as_mut(),
+        "taker",
+        "RECEIVE_LOCK",
+        Permission::Blacklisted(None),
+    );
+    let err = open_swap(deps.as_mut(), env.clone(), "token1").unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+
+    // Nor accepted by one denied after it was opened
+    set_permission(
+        deps.as_mut(),
+        "taker",
+        "RECEIVE_LOCK",
+        Permission::Whitelisted(None),
+    );
+    open_swap(deps.as_mut(), env.clone(), "token1").unwrap();
+    set_permission(
+        deps.as_mut(),
+        "taker",
+        "RECEIVE_LOCK",
+        Permission::Blacklisted(None),
+    );
+    let err =
+        accept_swap(deps.as_mut(), env.clone(), "taker", "other_cw721", "tokenB").unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+
+    // A receipt sold to a denied address cannot be redeemed by it
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("creator", &[]),
+        ExecuteMsg::UpdateReceiptContract {
+            address: Some(AndrAddr::from_string("receipt_cw721")),
+        },
+    )
+    .unwrap();
+    receipt_lock(deps.as_mut(), env.clone(), "token2").unwrap();
+    set_permission(
+        deps.as_mut(),
+        "receipt_buyer",
+        "RECEIVE_LOCK",
+        Permission::Blacklisted(None),
+    );
+    let mut claim_env = env;
+    claim_env.block.time = claim_env.block.time.plus_seconds(ONE_DAY);
+    let err = execute(
+        deps.as_mut(),
+        claim_env,
+        mock_info("receipt_cw721", &[]),
+        ExecuteMsg::ReceiveNft(Cw721ReceiveMsg {
+            sender: "receipt_buyer".to_string(),
+            token_id: "1".to_string(),
+            msg: encode_binary(&Cw721HookMsg::RedeemReceipt {}).unwrap(),
+        }),
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+}
+
+#[test]
+fn test_bundle() {
+    let mut deps = mock_dependencies_custom(&[]);
+    let env = mock_env();
+    init(deps.as_mut());
+
+    let unlock_condition = UnlockCondition::Time(Milliseconds::from_seconds(
+        env.block.time.seconds() + 3 * ONE_DAY,
+    ));
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(MOCK_TOKEN_OWNER, &[]),
+        ExecuteMsg::OpenBundle {
+            recipient: Recipient::from_string("recipient"),
+            unlock_condition: unlock_condition.clone(),
+        },
+    )
+    .unwrap();
+    assert!(res
+        .attributes
+        .contains(&Attribute::new("bundle_id", "1".to_string())));
+
+    // Only the owner of the bundle can add to it
+    let err = send_nft(
+        deps.as_mut(),
+        env.clone(),
+        "anyone",
+        "token1",
+        Cw721HookMsg::AddToBundle { bundle_id: 1 },
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+    for token_id in ["token1", "token2"] {
+        send_nft(
+            deps.as_mut(),
+            env.clone(),
+            MOCK_TOKEN_OWNER,
+            token_id,
+            Cw721HookMsg::AddToBundle { bundle_id: 1 },
+        )
+        .unwrap();
+    }
+
+    let bundle: BundleResponse = from_json(
+        query(
+            deps.as_ref(),
+            env.clone(),
+            QueryMsg::Bundle { bundle_id: 1 },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        bundle.bundle,
+        Bundle {
+            owner: Addr::unchecked(MOCK_TOKEN_OWNER),
+            recipient: Addr::unchecked("recipient"),
+            recipient_msg: None,
+            unlock_condition,
+            items: vec![
+                BundleItem {
+                    cw721_contract: Addr::unchecked(MOCK_CW721_CONTRACT),
+                    token_id: "token1".to_string(),
+                },
+                BundleItem {
+                    cw721_contract: Addr::unchecked(MOCK_CW721_CONTRACT),
+                    token_id: "token2".to_string(),
+                },
+            ],
+        }
+    );
+
+    let claim_bundle_msg = ExecuteMsg::ClaimBundle { bundle_id: 1 };
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("anyone", &[]),
+        claim_bundle_msg.clone(),
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::LockedNFT {});
+
+    // Bundled NFTs cannot be claimed or released one by one
+    let mut later_env = env.clone();
+    later_env.block.time = env.block.time.plus_seconds(3 * ONE_DAY);
+    let err = execute(
+        deps.as_mut(),
+        later_env.clone(),
+        mock_info("recipient", &[]),
+        ExecuteMsg::ClaimNft {
+            cw721_contract: AndrAddr::from_string(MOCK_CW721_CONTRACT),
+            token_id: "token1".to_string(),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::UnsupportedOperation {});
+    let res = execute(
+        deps.as_mut(),
+        later_env.clone(),
+        mock_info("keeper", &[]),
+        ExecuteMsg::ReleaseExpired { limit: None },
+    )
+    .unwrap();
+    assert!(res.messages.is_empty());
+
+    let res = execute(
+        deps.as_mut(),
+        later_env.clone(),
+        mock_info("anyone", &[]),
+        claim_bundle_msg,
+    )
+    .unwrap();
+    let transfer = |token_id: &str| {
+        SubMsg::new(WasmMsg::Execute {
+            contract_addr: MOCK_CW721_CONTRACT.to_string(),
+            msg: encode_binary(&Cw721ExecuteMsg::TransferNft {
+                recipient: AndrAddr::from_string("recipient"),
+                token_id: token_id.to_string(),
+            })
+            .unwrap(),
+            funds: vec![],
+        })
+    };
+    assert_eq!(res.messages, vec![transfer("token1"), transfer("token2")]);
+    assert!(query(deps.as_ref(), later_env, QueryMsg::Bundle { bundle_id: 1 }).is_err());
+}
+
+fn query_schedule(deps: Deps, env: Env, schedule_id: u64) -> ScheduleResponse {
+    from_json(query(deps, env, QueryMsg::Schedule { schedule_id }).unwrap()).unwrap()
+}
+
+#[test]
+fn test_vesting_schedule() {
+    let mut deps = mock_dependencies_custom(&[]);
+    let env = mock_env();
+    init(deps.as_mut());
+
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(MOCK_TOKEN_OWNER, &[]),
+        ExecuteMsg::CreateSchedule {
+            recipient: Recipient::from_string("recipient"),
+            cliff: MillisecondsDuration::from_seconds(90 * ONE_DAY),
+            period: MillisecondsDuration::zero(),
+            per_period: 2,
+        },
+    )
+    .unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidTimestamp {
+            msg: "Schedule period must be greater than zero".to_string(),
+        }
+    );
+
+    // Two NFTs unlock after 90 days, and two more every 30 days after that
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(MOCK_TOKEN_OWNER, &[]),
+        ExecuteMsg::CreateSchedule {
+            recipient: Recipient::from_string("recipient"),
+            cliff: MillisecondsDuration::from_seconds(90 * ONE_DAY),
+            period: MillisecondsDuration::from_seconds(30 * ONE_DAY),
+            per_period: 2,
+        },
+    )
+    .unwrap();
+    let start = env.block.time.seconds();
+    for (i, unlock_day) in [90, 90, 120, 120, 150].into_iter().enumerate() {
+        let res = send_nft(
+            deps.as_mut(),
+            env.clone(),
+            MOCK_TOKEN_OWNER,
+            &format!("token{i}"),
+            Cw721HookMsg::AddToSchedule { schedule_id: 1 },
+        )
+        .unwrap();
+        assert!(res.attributes.contains(&Attribute::new(
+            "unlock_time",
+            (start + unlock_day * ONE_DAY).to_string()
+        )));
+    }
+
+    let schedule = query_schedule(deps.as_ref(), env.clone(), 1);
+    assert_eq!(schedule.schedule.deposited, 5);
+    assert_eq!(
+        schedule.next_unlock,
+        Some(Milliseconds::from_seconds(start + 90 * ONE_DAY))
+    );
+    assert_eq!(schedule.claimable, 0);
+
+    // The terms of the schedule cannot be changed lock by lock
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("recipient", &[]),
+        ExecuteMsg::ExtendLock {
+            cw721_contract: AndrAddr::from_string(MOCK_CW721_CONTRACT),
+            token_id: "token4".to_string(),
+            additional_duration: MillisecondsDuration::from_seconds(ONE_DAY),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::UnsupportedOperation {});
+
+    let mut later_env = env.clone();
+    later_env.block.time = env.block.time.plus_seconds(120 * ONE_DAY);
+    let schedule = query_schedule(deps.as_ref(), later_env.clone(), 1);
+    assert_eq!(
+        schedule.next_unlock,
+        Some(Milliseconds::from_seconds(start + 150 * ONE_DAY))
+    );
+    assert_eq!(schedule.claimable, 4);
+
+    execute(
+        deps.as_mut(),
+        later_env.clone(),
+        mock_info("recipient", &[]),
+        ExecuteMsg::ClaimNft {
+            cw721_contract: AndrAddr::from_string(MOCK_CW721_CONTRACT),
+            token_id: "token0".to_string(),
+        },
+    )
+    .unwrap();
+    assert_eq!(query_schedule(deps.as_ref(), later_env, 1).claimable, 3);
+
+    let mut end_env = env.clone();
+    end_env.block.time = env.block.time.plus_seconds(150 * ONE_DAY);
+    let schedule = query_schedule(deps.as_ref(), end_env, 1);
+    assert_eq!(schedule.next_unlock, None);
+    assert_eq!(schedule.claimable, 4);
+    assert_eq!(schedule.schedule.released, 1);
+}
+
+fn claim_window_lock(deps: DepsMut, env: Env, token_id: &str) -> Result<Response, ContractError> {
+    send_nft(
+        deps,
+        env,
+        MOCK_TOKEN_OWNER,
+        token_id,
+        Cw721HookMsg::TimelockNft {
+            lock_duration: MillisecondsDuration::from_seconds(3 * ONE_DAY),
+            recipient: Recipient::from_string("recipient"),
+            revocable: false,
+            arbiters: None,
+            claim_window: Some(ClaimWindow {
+                duration: MillisecondsDuration::from_seconds(7 * ONE_DAY),
+                fallback: Recipient::from_string("heir"),
+            }),
+            receipt: false,
+        },
+    )
+}
+
+#[test]
+fn test_claim_window_fallback() {
+    let mut deps = mock_dependencies_custom(&[]);
+    let env = mock_env();
+    init(deps.as_mut());
+
+    claim_window_lock(deps.as_mut(), env.clone(), "token1").unwrap();
+    claim_window_lock(deps.as_mut(), env.clone(), "token2").unwrap();
+
+    let details: NftDetailsResponse = from_json(
+        query(
+            deps.as_ref(),
+            env.clone(),
+            QueryMsg::NftDetails {
+                cw721_contract: AndrAddr::from_string(MOCK_CW721_CONTRACT),
+                token_id: "token1".to_string(),
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        details.claim_deadline,
+        Some(Milliseconds::from_seconds(
+            env.block.time.seconds() + 10 * ONE_DAY
+        ))
+    );
+    assert_eq!(details.fallback.unwrap().recipient, Addr::unchecked("heir"));
+
+    let claim = |token_id: &str| ExecuteMsg::ClaimNft {
+        cw721_contract: AndrAddr::from_string(MOCK_CW721_CONTRACT),
+        token_id: token_id.to_string(),
+    };
+    let release = |token_id: &str| ExecuteMsg::ReleaseToFallback {
+        cw721_contract: AndrAddr::from_string(MOCK_CW721_CONTRACT),
+        token_id: token_id.to_string(),
+    };
+
+    // Within the window only the recipient can claim, and the fallback cannot take the NFT yet
+    let mut window_env = env.clone();
+    window_env.block.time = env.block.time.plus_seconds(5 * ONE_DAY);
+    let err = execute(
+        deps.as_mut(),
+        window_env.clone(),
+        mock_info("anyone", &[]),
+        claim("token1"),
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+    let err = execute(
+        deps.as_mut(),
+        window_env.clone(),
+        mock_info("anyone", &[]),
+        release("token1"),
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::LockedNFT {});
+    let res = execute(
+        deps.as_mut(),
+        window_env.clone(),
+        mock_info("anyone", &[]),
+        ExecuteMsg::ClaimAllUnlocked {
+            recipient: AndrAddr::from_string("recipient"),
+            start_after: None,
+            limit: None,
+        },
+    )
+    .unwrap();
+    assert!(res.messages.is_empty());
+    execute(
+        deps.as_mut(),
+        window_env,
+        mock_info("recipient", &[]),
+        claim("token1"),
+    )
+    .unwrap();
+
+    // After the window the NFT can only go to the fallback recipient, without a claim fee
+    let mut late_env = env.clone();
+    late_env.block.time = env.block.time.plus_seconds(10 * ONE_DAY);
+    let err = execute(
+        deps.as_mut(),
+        late_env.clone(),
+        mock_info("recipient", &[]),
+        claim("token2"),
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::Expired {});
+    let res = execute(
+        deps.as_mut(),
+        late_env.clone(),
+        mock_info("anyone", &[]),
+        release("token2"),
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(WasmMsg::Execute {
+            contract_addr: MOCK_CW721_CONTRACT.to_string(),
+            msg: encode_binary(&Cw721ExecuteMsg::TransferNft {
+                recipient: AndrAddr::from_string("heir"),
+                token_id: "token2".to_string(),
+            })
+            .unwrap(),
+            funds: vec![],
+        })]
+    );
+
+    let history: LockHistoryResponse = from_json(
+        query(
+            deps.as_ref(),
+            late_env.clone(),
+            QueryMsg::LockHistory {
+                cw721_contract: AndrAddr::from_string(MOCK_CW721_CONTRACT),
+                token_id: "token2".to_string(),
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(history.locks[0].status, LockStatus::Forfeited);
+    assert_eq!(history.locks[0].claimer, Some(Addr::unchecked("heir")));
+
+    // Locks without a claim window have no fallback to release to
+    timelock_nft(deps.as_mut(), env, "token3", ONE_DAY, "recipient").unwrap();
+    let err = execute(
+        deps.as_mut(),
+        late_env,
+        mock_info("anyone", &[]),
+        release("token3"),
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::UnsupportedOperation {});
+}
+
+#[test]
+fn test_claim_window_duration_bounds() {
+    let mut deps = mock_dependencies_custom(&[]);
+    let env = mock_env();
+    init(deps.as_mut());
+
+    let lock_with_window = |deps: DepsMut, duration: MillisecondsDuration| {
+        send_nft(
+            deps,
+            env.clone(),
+            MOCK_TOKEN_OWNER,
+            "token1",
+            Cw721HookMsg::TimelockNft {
+                lock_duration: MillisecondsDuration::from_seconds(3 * ONE_DAY),
+                recipient: Recipient::from_string("recipient"),
+                revocable: false,
+                arbiters: None,
+                claim_window: Some(ClaimWindow {
+                    duration,
+                    fallback: Recipient::from_string("heir"),
+                }),
+                receipt: false,
+            },
+        )
+    };
+
+    let err = lock_with_window(deps.as_mut(), MillisecondsDuration::zero()).unwrap_err();
+    assert_eq!(
+        err,
+        ContractError::InvalidTimestamp {
+            msg: "A claim window cannot be empty".to_string(),
+        }
+    );
+
+    // Held to the lock policy's maximum duration, so its end always fits in a timestamp
+    let err = lock_with_window(deps.as_mut(), Milliseconds(u64::MAX)).unwrap_err();
+    assert_eq!(err, ContractError::LockTimeTooLong {});
+    let err = lock_with_window(
+        deps.as_mut(),
+        MillisecondsDuration::from_seconds(ONE_YEAR + 1),
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::LockTimeTooLong {});
+    lock_with_window(deps.as_mut(), MillisecondsDuration::from_seconds(ONE_YEAR)).unwrap();
+}
+
+#[test]
+fn test_forced_recipient_cannot_be_bypassed() {
+    let mut deps = mock_dependencies_custom(&[]);
+    let env = mock_env();
+    init(deps.as_mut());
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("creator", &[]),
+        ExecuteMsg::UpdateLockPolicy {
+            cw721_contract: Some(AndrAddr::from_string(MOCK_CW721_CONTRACT)),
+            policy: Some(LockPolicy {
+                min_duration: MillisecondsDuration::from_seconds(ONE_DAY),
+                max_duration: MillisecondsDuration::from_seconds(ONE_YEAR),
+                allow_revocable: true,
+                forced_recipient: Some(Recipient::new("treasury", None)),
+                early_unlock: None,
+                height_limits: HeightLimits::default(),
+            }),
+        },
+    )
+    .unwrap();
+
+    // A fallback could claim the NFT in place of the forced recipient
+    let err = claim_window_lock(deps.as_mut(), env.clone(), "token1").unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+
+    // A revocable lock can be taken back, but not redirected
+    revocable_lock(deps.as_mut(), env.clone(), "token1");
+    let revoke_msg = |new_recipient: Option<Recipient>| ExecuteMsg::RevokeLock {
+        cw721_contract: AndrAddr::from_string(MOCK_CW721_CONTRACT),
+        token_id: "token1".to_string(),
+        new_recipient,
+    };
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(MOCK_TOKEN_OWNER, &[]),
+        revoke_msg(Some(Recipient::from_string("other"))),
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(MOCK_TOKEN_OWNER, &[]),
+        revoke_msg(None),
+    )
+    .unwrap();
+
+    // Bundles only take the collection's NFTs when they go to the forced recipient
+    let unlock_condition = UnlockCondition::Time(Milliseconds::from_seconds(
+        env.block.time.seconds() + 3 * ONE_DAY,
+    ));
+    for recipient in ["recipient", "treasury"] {
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info(MOCK_TOKEN_OWNER, &[]),
+            ExecuteMsg::OpenBundle {
+                recipient: Recipient::from_string(recipient),
+                unlock_condition: unlock_condition.clone(),
+            },
+        )
+        .unwrap();
+    }
+    let err = send_nft(
+        deps.as_mut(),
+        env.clone(),
+        MOCK_TOKEN_OWNER,
+        "token2",
+        Cw721HookMsg::AddToBundle { bundle_id: 1 },
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+    send_nft(
+        deps.as_mut(),
+        env,
+        MOCK_TOKEN_OWNER,
+        "token2",
+        Cw721HookMsg::AddToBundle { bundle_id: 2 },
+    )
+    .unwrap();
+}
+
+#[test]
+fn test_heartbeat_lock() {
+    let mut deps = mock_dependencies_custom(&[]);
+    let env = mock_env();
+    init(deps.as_mut());
+
+    for token_id in ["token1", "token2"] {
+        send_nft(
+            deps.as_mut(),
+            env.clone(),
+            MOCK_TOKEN_OWNER,
+            token_id,
+            Cw721HookMsg::HeartbeatLockNft {
+                interval: MillisecondsDuration::from_seconds(30 * ONE_DAY),
+                recipient: Recipient::from_string("recipient"),
+            },
+        )
+        .unwrap();
+    }
+    timelock_nft(deps.as_mut(), env.clone(), "token3", ONE_DAY, "recipient").unwrap();
+
+    let heartbeat = |token_id: &str| ExecuteMsg::Heartbeat {
+        cw721_contract: AndrAddr::from_string(MOCK_CW721_CONTRACT),
+        token_id: token_id.to_string(),
+    };
+    let claim = |token_id: &str| ExecuteMsg::ClaimNft {
+        cw721_contract: AndrAddr::from_string(MOCK_CW721_CONTRACT),
+        token_id: token_id.to_string(),
+    };
+
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(MOCK_TOKEN_OWNER, &[]),
+        heartbeat("token3"),
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::UnsupportedOperation {});
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("recipient", &[]),
+        heartbeat("token1"),
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+
+    // Checking in on day 20 moves the unlock time to day 50
+    let mut checkin_env = env.clone();
+    checkin_env.block.time = env.block.time.plus_seconds(20 * ONE_DAY);
+    for token_id in ["token1", "token2"] {
+        let res = execute(
+            deps.as_mut(),
+            checkin_env.clone(),
+            mock_info(MOCK_TOKEN_OWNER, &[]),
+            heartbeat(token_id),
+        )
+        .unwrap();
+        assert!(res.attributes.contains(&Attribute::new(
+            "unlock_time",
+            (env.block.time.seconds() + 50 * ONE_DAY).to_string()
+        )));
+    }
+    let err = execute(
+        deps.as_mut(),
+        checkin_env,
+        mock_info("recipient", &[]),
+        ExecuteMsg::ExtendLock {
+            cw721_contract: AndrAddr::from_string(MOCK_CW721_CONTRACT),
+            token_id: "token1".to_string(),
+            additional_duration: MillisecondsDuration::from_seconds(ONE_DAY),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::UnsupportedOperation {});
+
+    // Heartbeat locks cannot be unlocked early, even if the lock policy allows it
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("creator", &[]),
+        ExecuteMsg::UpdateLockPolicy {
+            cw721_contract: Some(AndrAddr::from_string(MOCK_CW721_CONTRACT)),
+            policy: Some(LockPolicy {
+                min_duration: MillisecondsDuration::from_seconds(ONE_DAY),
+                max_duration: MillisecondsDuration::from_seconds(ONE_YEAR),
+                allow_revocable: true,
+                forced_recipient: None,
+                early_unlock: Some(EarlyUnlockPolicy {
+                    caller: EarlyUnlockCaller::Recipient,
+                    treasury: Recipient::from_string("treasury"),
+                    penalty: PenaltyCurve {
+                        denom: "uandr".to_string(),
+                        base: Uint128::new(100),
+                        per_second: Uint128::zero(),
+                    },
+                }),
+                height_limits: HeightLimits::default(),
+            }),
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        query_is_locked(deps.as_ref(), env.clone(), "token1").early_unlock_price,
+        None
+    );
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("recipient", &[coin(100, "uandr")]),
+        ExecuteMsg::EarlyUnlock {
+            cw721_contract: AndrAddr::from_string(MOCK_CW721_CONTRACT),
+            token_id: "token1".to_string(),
+        },
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::UnsupportedOperation {});
+
+    let mut locked_env = env.clone();
+    locked_env.block.time = env.block.time.plus_seconds(40 * ONE_DAY);
+    let err = execute(
+        deps.as_mut(),
+        locked_env,
+        mock_info("recipient", &[]),
+        claim("token1"),
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::LockedNFT {});
+
+    // Once check-ins stop the recipient can claim, but the depositor can still take the NFT back
+    let mut expired_env = env.clone();
+    expired_env.block.time = env.block.time.plus_seconds(50 * ONE_DAY);
+    let err = execute(
+        deps.as_mut(),
+        expired_env.clone(),
+        mock_info(MOCK_TOKEN_OWNER, &[]),
+        heartbeat("token1"),
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::Expired {});
+    let res = execute(
+        deps.as_mut(),
+        expired_env.clone(),
+        mock_info(MOCK_TOKEN_OWNER, &[]),
+        ExecuteMsg::RevokeLock {
+            cw721_contract: AndrAddr::from_string(MOCK_CW721_CONTRACT),
+            token_id: "token1".to_string(),
+            new_recipient: None,
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(WasmMsg::Execute {
+            contract_addr: MOCK_CW721_CONTRACT.to_string(),
+            msg: encode_binary(&Cw721ExecuteMsg::TransferNft {
+                recipient: AndrAddr::from_string(MOCK_TOKEN_OWNER),
+                token_id: "token1".to_string(),
+            })
+            .unwrap(),
+            funds: vec![],
+        })]
+    );
+    let res = execute(
+        deps.as_mut(),
+        expired_env,
+        mock_info("recipient", &[]),
+        claim("token2"),
+    )
+    .unwrap();
+    assert!(res
+        .attributes
+        .contains(&Attribute::new("recipient", "recipient")));
+}
+
+#[test]
+fn test_migrate_legacy_locks() {
+    let mut deps = mock_dependencies_custom(&[]);
+    let env = mock_env();
+    init(deps.as_mut());
+
+    // A 1.0.0 contract holding a lock from before lock IDs existed and one written since
+    cw2::set_contract_version(deps.as_mut().storage, "cw721-timelock", "1.0.0").unwrap();
+    timelock_nft(deps.as_mut(), env.clone(), "token1", ONE_DAY, "recipient").unwrap();
+    let cw721_contract = Addr::unchecked(MOCK_CW721_CONTRACT);
+    let unlock_time = Milliseconds::from_seconds(env.block.time.seconds() + 2 * ONE_DAY);
+    LEGACY_TIMELOCKS
+        .save(
+            deps.as_mut().storage,
+            (&cw721_contract, "token2"),
+            &LegacyTimelockInfo {
+                unlock_time,
+                recipient: Addr::unchecked("recipient"),
+            },
+        )
+        .unwrap();
+
+    let res = migrate(deps.as_mut(), env.clone(), MigrateMsg {}).unwrap();
+    assert!(res
+        .attributes
+        .contains(&Attribute::new("migrated_locks", "1")));
+    assert!(res.attributes.contains(&Attribute::new("pending", "false")));
+
+    let timelock_info = timelocks()
+        .load(deps.as_ref().storage, (&cw721_contract, "token2"))
+        .unwrap();
+    assert_eq!(timelock_info.lock_id, 2);
+    assert_eq!(
+        timelock_info.unlock_condition,
+        UnlockCondition::Time(unlock_time)
+    );
+    assert_eq!(timelock_info.depositor, Addr::unchecked("recipient"));
+    assert_eq!(
+        query_voting_power(deps.as_ref(), env.clone(), "recipient", None),
+        Uint128::from(3 * ONE_DAY)
+    );
+
+    // The rewritten lock is indexed
+    let locks: LocksResponse = from_json(
+        query(
+            deps.as_ref(),
+            env.clone(),
+            QueryMsg::LocksByRecipient {
+                recipient: AndrAddr::from_string("recipient"),
+                start_after: None,
+                limit: None,
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(locks.locks.len(), 2);
+
+    // The same version cannot be migrated to twice
+    let err = migrate(deps.as_mut(), env.clone(), MigrateMsg {}).unwrap_err();
+    assert!(matches!(err, ContractError::CannotMigrate { .. }));
+
+    let mut later_env = env.clone();
+    later_env.block.time = env.block.time.plus_seconds(2 * ONE_DAY);
+    let res = execute(
+        deps.as_mut(),
+        later_env,
+        mock_info("keeper", &[]),
+        ExecuteMsg::ReleaseExpired { limit: None },
+    )
+    .unwrap();
+    assert!(res.attributes.contains(&Attribute::new("released", "2")));
+}
+
+#[test]
+fn test_migrate_legacy_locks_in_batches() {
+    let mut deps = mock_dependencies_custom(&[]);
+    let env = mock_env();
+    init(deps.as_mut());
+
+    cw2::set_contract_version(deps.as_mut().storage, "cw721-timelock", "1.0.0").unwrap();
+    let cw721_contract = Addr::unchecked(MOCK_CW721_CONTRACT);
+    let unlock_time = Milliseconds::from_seconds(env.block.time.seconds() + ONE_DAY);
+    for i in 0..45 {
+        LEGACY_TIMELOCKS
+            .save(
+                deps.as_mut().storage,
+                (&cw721_contract, &format!("token{i:02}")),
+                &LegacyTimelockInfo {
+                    unlock_time,
+                    recipient: Addr::unchecked("recipient"),
+                },
+            )
+            .unwrap();
+    }
+    let migrate_legacy_locks = ExecuteMsg::MigrateLegacyLocks { limit: None };
+
+    // Only a bounded number of entries is looked at when migrating
+    let res = migrate(deps.as_mut(), env.clone(), MigrateMsg {}).unwrap();
+    assert!(res
+        .attributes
+        .contains(&Attribute::new("migrated_locks", "30")));
+    assert!(res.attributes.contains(&Attribute::new("pending", "true")));
+    assert!(timelocks()
+        .load(deps.as_ref().storage, (&cw721_contract, "token30"))
+        .is_err());
+
+    // The rest is migrated in pages, carrying on from the last entry looked at
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("anyone", &[]),
+        migrate_legacy_locks.clone(),
+    )
+    .unwrap();
+    assert!(res
+        .attributes
+        .contains(&Attribute::new("migrated_locks", "10")));
+    assert!(res.attributes.contains(&Attribute::new("pending", "true")));
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("anyone", &[]),
+        migrate_legacy_locks.clone(),
+    )
+    .unwrap();
+    assert!(res
+        .attributes
+        .contains(&Attribute::new("migrated_locks", "5")));
+    assert!(res.attributes.contains(&Attribute::new("pending", "false")));
+
+    let timelock_info = timelocks()
+        .load(deps.as_ref().storage, (&cw721_contract, "token44"))
+        .unwrap();
+    assert_eq!(timelock_info.lock_id, 45);
+
+    let err = execute(
+        deps.as_mut(),
+        env,
+        mock_info("anyone", &[]),
+        migrate_legacy_locks,
+    )
+    .unwrap_err();
+    assert_eq!(err, ContractError::UnsupportedOperation {});
 }